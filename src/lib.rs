@@ -1,11 +1,22 @@
 #![warn(clippy::pedantic)]
 #![allow(clippy::missing_errors_doc)]
 
+#[cfg(target_os = "linux")]
+mod linux;
 #[cfg(target_os = "macos")]
 mod macos;
 #[cfg(windows)]
 mod windows;
 
+#[cfg(test)]
+mod test_util;
+
+#[cfg(target_os = "linux")]
+use linux::{Context, Verifier};
+#[cfg(target_os = "linux")]
+pub use linux::{FsVerityAlgorithm, FsVerityDigest};
+#[cfg(target_os = "macos")]
+pub use macos::{Constraint, LaunchConstraints, PropertyValue, ProvisioningProfile};
 #[cfg(target_os = "macos")]
 use macos::{Context, Verifier};
 #[cfg(windows)]
@@ -42,35 +53,539 @@ pub struct Name {
     pub country: Option<String>,           // 2.5.4.6
 }
 
+///
+/// A valid ad-hoc signature: the code is sealed by its code directory but carries no certificate
+///
+/// # Fields
+///
+/// `cdhash`: the hex encoded code directory hash, which identifies the code
+///
+/// `flags`: the code signature flags (`kSecCodeSignature*`, e.g. 0x2 for ad-hoc)
+///
+#[cfg(target_os = "macos")]
+#[derive(Debug, PartialEq)]
+pub struct AdHocSignature {
+    pub cdhash: String,
+    pub flags: u32,
+}
+
+///
+/// The identity of a signer that signed with a bare key rather than an X.509 certificate
+///
+#[cfg(target_os = "linux")]
+#[derive(Debug, PartialEq, Clone)]
+pub enum SignerIdentity {
+    /// An OpenPGP key
+    ///
+    /// `fingerprint`: the hex encoded fingerprint of the signing key, which may be a subkey
+    ///
+    /// `user_ids`: the user IDs of the key, e.g. "Alice <alice@example.com>"
+    ///
+    /// `created`: the signature creation time
+    OpenPgp {
+        fingerprint: String,
+        user_ids: Vec<String>,
+        created: std::time::SystemTime,
+    },
+    /// A minisign or OpenBSD signify key
+    ///
+    /// `key_id`: the 64 bit key ID in hex, as displayed by minisign, e.g. "E7620F1842B4E81F"
+    ///
+    /// `trusted_comment`: the signed comment of a minisign signature, `None` for signify
+    Minisign {
+        key_id: String,
+        trusted_comment: Option<String>,
+    },
+    /// An SSH key listed in an `allowed_signers` file
+    ///
+    /// `principals`: every principal listed for the key in `allowed_signers`, which may be patterns.
+    /// An SSH signature doesn't name its principal, so this is the full list rather than one
+    /// identity, as printed by `ssh-keygen -Y find-principals`.
+    ///
+    /// `fingerprint`: the SHA-256 fingerprint of the key, e.g. "SHA256:I2qM8IOu..."
+    Ssh {
+        principals: Vec<String>,
+        fingerprint: String,
+    },
+    /// An EFI image allowed by its Authenticode digest in the UEFI `db`, rather than by a signature
+    ///
+    /// `sha256`: the hex encoded SHA-256 Authenticode digest of the image
+    ImageDigest { sha256: String },
+}
+
+///
+/// The identity a Sigstore (Fulcio) certificate was issued for
+///
+/// `issuer`: the OIDC issuer that authenticated the signer, e.g. `https://accounts.google.com`
+///
+/// `subject`: the email address or URI from the certificate subject alternative name
+///
+#[cfg(target_os = "linux")]
+#[derive(Debug, PartialEq, Clone)]
+pub struct SigstoreIdentity {
+    pub issuer: String,
+    pub subject: String,
+}
+
+///
+/// The state of a process verified by PID, besides the signature of its executable
+///
+/// `executable`: the path the process was executed from, in its own mount namespace
+///
+/// `deleted`: the executable was deleted since the process started
+///
+/// `matches_path`: the device and inode of the file at `executable` are those of the executable,
+/// `false` when the file was since deleted or replaced
+///
+/// `unverified_objects`: the mapped shared objects that failed verification, with the reason
+///
+/// `cgroup`: the cgroup of the process, e.g. `/system.slice/docker-<id>.scope`
+///
+/// `container_id`: the ID of the container the process runs in, found in its cgroup
+///
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub struct ProcessInfo {
+    pub executable: std::path::PathBuf,
+    pub deleted: bool,
+    pub matches_path: bool,
+    pub unverified_objects: Vec<(std::path::PathBuf, Error)>,
+    pub cgroup: Option<String>,
+    pub container_id: Option<String>,
+}
+
+///
+/// The credentials of the peer of a Unix domain socket, as of when it connected
+///
+/// `pid`: the process ID of the peer, in the PID namespace of the caller
+///
+/// `uid`, `gid`: the effective user and group IDs of the peer
+///
+#[cfg(target_os = "linux")]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PeerCredentials {
+    pub pid: i32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+///
+/// The kind of an Apple issued code signing certificate, determined by the Apple specific
+/// extensions (OID 1.2.840.113635.100.6) present on the leaf certificate
+///
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AppleCertKind {
+    DeveloperIdApplication, // 1.2.840.113635.100.6.1.13
+    DeveloperIdInstaller,   // 1.2.840.113635.100.6.1.14
+    MacAppStore,            // 1.2.840.113635.100.6.1.9, .1.10
+    AppleDevelopment,       // 1.2.840.113635.100.6.1.2, .1.12
+    AppleDistribution,      // 1.2.840.113635.100.6.1.4, .1.7
+    ApplePlatform,          // 1.2.840.113635.100.6.22
+}
+
+impl AppleCertKind {
+    // Checked in order, the first extension present on the certificate wins
+    const EXTENSIONS: [(&'static str, AppleCertKind); 9] = [
+        (
+            "1.2.840.113635.100.6.1.13",
+            AppleCertKind::DeveloperIdApplication,
+        ),
+        (
+            "1.2.840.113635.100.6.1.14",
+            AppleCertKind::DeveloperIdInstaller,
+        ),
+        ("1.2.840.113635.100.6.1.9", AppleCertKind::MacAppStore),
+        ("1.2.840.113635.100.6.1.10", AppleCertKind::MacAppStore),
+        ("1.2.840.113635.100.6.1.4", AppleCertKind::AppleDistribution),
+        ("1.2.840.113635.100.6.1.7", AppleCertKind::AppleDistribution),
+        ("1.2.840.113635.100.6.1.2", AppleCertKind::AppleDevelopment),
+        ("1.2.840.113635.100.6.1.12", AppleCertKind::AppleDevelopment),
+        ("1.2.840.113635.100.6.22", AppleCertKind::ApplePlatform),
+    ];
+
+    fn from_extensions<F: Fn(&str) -> bool>(has_extension: F) -> Option<Self> {
+        Self::EXTENSIONS
+            .iter()
+            .find(|(oid, _)| has_extension(oid))
+            .map(|(_, kind)| *kind)
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
-    Unsigned,         // The binary file didn't have any singature
-    OsError(i32),     // Warps an inner provider error code
-    InvalidPath,      // The provided path was malformed
-    LeafCertNotFound, // Unable to fetch certificate information
+    Unsigned,             // The binary file didn't have any singature
+    OsError(i32),         // Warps an inner provider error code
+    InvalidPath,          // The provided path was malformed
+    LeafCertNotFound,     // Unable to fetch certificate information
+    MalformedSignature,   // The signature data could not be parsed
+    BadSignature,         // The signature doesn't match the signed content
+    UnknownSigner,        // None of the provided certificates issued the signature
+    UnsupportedAlgorithm, // The signature uses an algorithm that can't be verified
+    InvalidKey,           // The provided certificate or key could not be parsed
+    UnsupportedInput, // The input can't be verified on this platform, e.g. in-memory data on macOS
+    Revoked,          // The signer or the code is listed as revoked, e.g. in the UEFI dbx
     #[cfg(target_os = "macos")]
     CFError(String),
-    #[cfg(windows)]
+    #[cfg(target_os = "macos")]
+    AdHocSigned(AdHocSignature), // The code has a valid ad-hoc signature without a certificate
     IoError(std::io::Error),
 }
 
 impl CodeSignVerifier {
     /// Create a verifier for a binary at a given path.
-    /// On macOS it can be either a binary or an application package.
+    /// On macOS it can be either a binary, an application package or a signed disk image (.dmg).
+    /// On Linux the IMA signature in the `security.ima` extended attribute is verified
+    /// with the keys in `/etc/keys`.
     pub fn for_file<P: AsRef<std::path::Path>>(path: P) -> Self {
         CodeSignVerifier(Verifier::for_file(path))
     }
 
+    /// Create a verifier for a file with an IMA signature in its `security.ima` extended attribute.
+    /// `keyring` is a certificate file, or a directory of DER or PEM encoded certificates.
+    #[cfg(target_os = "linux")]
+    pub fn for_file_with_keyring<P: AsRef<std::path::Path>, K: AsRef<std::path::Path>>(
+        path: P,
+        keyring: K,
+    ) -> Self {
+        CodeSignVerifier(Verifier::for_file_with_keyring(path, keyring))
+    }
+
+    /// Create a verifier for an open file. The file is read through a duplicate of its handle,
+    /// so exactly the opened file is verified and the caller can then load or execute that same
+    /// file without a path being replaced in between. Its offset is left unchanged.
+    /// On Linux its IMA signature is verified with the keys in `/etc/keys`.
+    #[cfg(any(target_os = "linux", windows))]
+    pub fn for_file_handle(file: &std::fs::File) -> Result<Self, Error> {
+        #[cfg(target_os = "linux")]
+        return Self::for_fd(std::os::unix::io::AsFd::as_fd(file));
+        #[cfg(windows)]
+        return Self::for_handle(std::os::windows::io::AsHandle::as_handle(file));
+    }
+
+    /// Create a verifier for an open file descriptor, as `for_file_handle` does
+    #[cfg(target_os = "linux")]
+    pub fn for_fd(fd: std::os::unix::io::BorrowedFd<'_>) -> Result<Self, Error> {
+        Verifier::for_fd(fd).map(CodeSignVerifier)
+    }
+
+    /// Create a verifier for an open file descriptor, as `for_file_handle` does, verifying its
+    /// IMA signature with the keys in `keyring` as `for_file_with_keyring` does
+    #[cfg(target_os = "linux")]
+    pub fn for_fd_with_keyring<K: AsRef<std::path::Path>>(
+        fd: std::os::unix::io::BorrowedFd<'_>,
+        keyring: K,
+    ) -> Result<Self, Error> {
+        Verifier::for_fd_with_keyring(fd, keyring).map(CodeSignVerifier)
+    }
+
+    /// Create a verifier for an open file handle, as `for_file_handle` does
+    #[cfg(windows)]
+    pub fn for_handle(handle: std::os::windows::io::BorrowedHandle<'_>) -> Result<Self, Error> {
+        Verifier::for_handle(handle).map(CodeSignVerifier)
+    }
+
+    /// Create a verifier for a signed file held in memory, e.g. an update verified before it is
+    /// written to disk. On Linux the format is found from the content and verified as its file
+    /// verifier does, with the same results, trusting the certificates in `/etc/keys`:
+    /// Java archives (`for_jar`), APKs (`for_apk`), iOS application archives (`for_ipa`),
+    /// installer packages (`for_pkg`), kernel modules (`for_kernel_module`), Mach-O files as the
+    /// executable of a bundle is checked, and PE images as `for_uefi_image` does with the
+    /// certificates as `db` and an empty `dbx`. For fs-verity, compute the digest with
+    /// `FsVerityDigest::for_reader` and use `for_fsverity`.
+    /// Other content fails with `Error::UnsupportedInput`: IMA signatures are kept in an extended
+    /// attribute, not in the content of the file. So does any content on macOS and Windows,
+    /// whose system APIs verify files by path.
+    pub fn for_bytes(data: &[u8]) -> Result<Self, Error> {
+        #[cfg(target_os = "linux")]
+        return Ok(CodeSignVerifier(Verifier::for_bytes(data)));
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = data;
+            Err(Error::UnsupportedInput)
+        }
+    }
+
+    /// Create a verifier for a signed file held in memory, as `for_bytes` does, trusting the
+    /// certificates in `keyring`, a certificate file or a directory of DER or PEM encoded
+    /// certificates
+    #[cfg(target_os = "linux")]
+    pub fn for_bytes_with_keyring<K: AsRef<std::path::Path>>(data: &[u8], keyring: K) -> Self {
+        CodeSignVerifier(Verifier::for_bytes_with_keyring(data, keyring))
+    }
+
+    /// Create a verifier for a signed file read from `reader`, from its start, as `for_bytes`
+    /// does. The position of the reader is left unchanged.
+    pub fn for_reader<R: std::io::Read + std::io::Seek>(reader: R) -> Result<Self, Error> {
+        #[cfg(target_os = "linux")]
+        return Verifier::for_reader(reader).map(CodeSignVerifier);
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = reader;
+            Err(Error::UnsupportedInput)
+        }
+    }
+
+    /// Create a verifier for a signed file read from `reader`, as `for_reader` does, trusting the
+    /// certificates in `keyring` as `for_bytes_with_keyring` does
+    #[cfg(target_os = "linux")]
+    pub fn for_reader_with_keyring<R: std::io::Read + std::io::Seek, K: AsRef<std::path::Path>>(
+        reader: R,
+        keyring: K,
+    ) -> Result<Self, Error> {
+        Verifier::for_reader_with_keyring(reader, keyring).map(CodeSignVerifier)
+    }
+
     /// Create a verifier for a running application by PID.
     /// On Windows it will get the full path to the running application first.
+    /// On Linux the executable is opened through `/proc/<pid>/exe` right away, so the file that was
+    /// executed is verified even if its path was since replaced, and its IMA signature is verified
+    /// with the keys in `/etc/keys`. The state of the process and of its shared objects is reported
+    /// through `SignatureContext::process_info`. Paths are resolved in the root directory of the
+    /// process (`/proc/<pid>/root`), so processes in containers are verified as they see their files.
     /// This can be used for e.g. verifying the app on the other end of a pipe.
     pub fn for_pid(pid: i32) -> Result<Self, Error> {
         Verifier::for_pid(pid).map(CodeSignVerifier)
     }
 
+    /// Create a verifier for the process on the other end of a Unix domain socket, as `for_pid`
+    /// does, along with the credentials of the peer (`SO_PEERCRED`).
+    /// The peer is tracked with a pidfd (`SO_PEERPIDFD`, or `pidfd_open` before Linux 6.5), which
+    /// is checked after its files are opened and after verification: if the peer exited in
+    /// between, its PID may have been reused and verification fails with `ESRCH`.
+    #[cfg(target_os = "linux")]
+    pub fn for_unix_peer(
+        stream: &std::os::unix::net::UnixStream,
+    ) -> Result<(Self, PeerCredentials), Error> {
+        Verifier::for_unix_peer(stream)
+            .map(|(verifier, credentials)| (CodeSignVerifier(verifier), credentials))
+    }
+
+    /// Create a verifier for a Linux kernel module with an appended PKCS#7 signature.
+    /// `key` holds the trusted signing certificates, DER or PEM encoded,
+    /// e.g. the `certs/signing_key.x509` produced by the kernel build.
+    #[cfg(target_os = "linux")]
+    pub fn for_kernel_module<P: AsRef<std::path::Path>, K: AsRef<std::path::Path>>(
+        path: P,
+        key: K,
+    ) -> Self {
+        CodeSignVerifier(Verifier::for_kernel_module(path, key))
+    }
+
+    /// Create a verifier for a detached fs-verity signature, a PKCS#7 signature over the
+    /// formatted fs-verity digest as produced by `fsverity sign`.
+    /// `key` holds the trusted signing certificates, DER or PEM encoded.
+    /// This allows validating a file before fs-verity is enabled on it.
+    #[cfg(target_os = "linux")]
+    pub fn for_fsverity<S: AsRef<std::path::Path>, K: AsRef<std::path::Path>>(
+        digest: FsVerityDigest,
+        signature: S,
+        key: K,
+    ) -> Self {
+        CodeSignVerifier(Verifier::for_fsverity(digest, signature, key))
+    }
+
+    /// Create a verifier for a file with a detached OpenPGP signature (`.sig` or `.asc`),
+    /// made by a key in `keyring`, a binary or ASCII armored file of public keys.
+    /// Only version 4 RSA and Ed25519 signatures are supported, other signatures in the file
+    /// (e.g. ECDSA or DSA) are skipped.
+    /// The signer is reported through `SignatureContext::signer_identity`.
+    #[cfg(target_os = "linux")]
+    pub fn for_openpgp<
+        P: AsRef<std::path::Path>,
+        S: AsRef<std::path::Path>,
+        K: AsRef<std::path::Path>,
+    >(
+        path: P,
+        signature: S,
+        keyring: K,
+    ) -> Self {
+        CodeSignVerifier(Verifier::for_openpgp(path, signature, keyring))
+    }
+
+    /// Create a verifier for a file with a detached minisign (`.minisig`) or signify signature.
+    /// Both the legacy and the prehashed (BLAKE2b-512) minisign signatures are supported,
+    /// the trusted comment is verified along with the signature.
+    /// `public_key` is the public key file, which holds the base64 encoded key. Its untrusted
+    /// comment tells a minisign key, whose signatures must have a trusted comment, from a
+    /// signify key.
+    #[cfg(target_os = "linux")]
+    pub fn for_minisign<
+        P: AsRef<std::path::Path>,
+        S: AsRef<std::path::Path>,
+        K: AsRef<std::path::Path>,
+    >(
+        path: P,
+        signature: S,
+        public_key: K,
+    ) -> Self {
+        CodeSignVerifier(Verifier::for_minisign(path, signature, public_key))
+    }
+
+    /// Create a verifier for a file with an armored SSH signature, as made by `ssh-keygen -Y sign`.
+    /// The signature must be made for `namespace`, by a key listed for that namespace
+    /// in `allowed_signers` (see `ssh-keygen(1)`). Certificate authorities aren't supported.
+    #[cfg(target_os = "linux")]
+    pub fn for_sshsig<
+        P: AsRef<std::path::Path>,
+        S: AsRef<std::path::Path>,
+        A: AsRef<std::path::Path>,
+    >(
+        path: P,
+        signature: S,
+        allowed_signers: A,
+        namespace: &str,
+    ) -> Self {
+        CodeSignVerifier(Verifier::for_sshsig(
+            path,
+            signature,
+            allowed_signers,
+            namespace,
+        ))
+    }
+
+    /// Create a verifier for an RPM package signed with `rpmsign`, by a key in `keyring`
+    /// (as exported by `gpg --export`). The OpenPGP signatures of the header and of the header
+    /// and payload are checked, as are the SHA-256 digests of the header and payload.
+    /// As with `for_openpgp`, only RSA and Ed25519 keys are supported.
+    /// The signer is reported through `SignatureContext::signer_identity`.
+    #[cfg(target_os = "linux")]
+    pub fn for_rpm<P: AsRef<std::path::Path>, K: AsRef<std::path::Path>>(
+        path: P,
+        keyring: K,
+    ) -> Self {
+        CodeSignVerifier(Verifier::for_rpm(path, keyring))
+    }
+
+    /// Create a verifier for a Debian package signed with `debsig` (a `_gpgorigin` member) or
+    /// `dpkg-sig` (a `_gpgbuilder` member), by a key in `keyring`. Every signature present
+    /// must verify, the origin signer is reported when there are both.
+    /// As with `for_openpgp`, only RSA and Ed25519 keys are supported.
+    /// The signer is reported through `SignatureContext::signer_identity`.
+    #[cfg(target_os = "linux")]
+    pub fn for_deb<P: AsRef<std::path::Path>, K: AsRef<std::path::Path>>(
+        path: P,
+        keyring: K,
+    ) -> Self {
+        CodeSignVerifier(Verifier::for_deb(path, keyring))
+    }
+
+    /// Create a verifier for a signed Java archive. Each signature block (`.RSA`, `.EC` or `.DSA`)
+    /// is checked over its signature file (`.SF`), the signature file over the manifest and the
+    /// manifest over the entries. Entries that aren't covered by every signer, or only by digests
+    /// that can't be checked (MD5), don't fail the verification, they are reported through
+    /// `SignatureContext::unsigned_entries`. Archives with duplicate entry names are refused.
+    /// JAR signers are self-asserted: the first signer is reported, not trusted.
+    #[cfg(target_os = "linux")]
+    pub fn for_jar<P: AsRef<std::path::Path>>(path: P) -> Self {
+        CodeSignVerifier(Verifier::for_jar(path))
+    }
+
+    /// Create a verifier for an Android package. The v3 and v2 APK Signature Scheme blocks are
+    /// checked, including a v3 key rotation proof, or the v1 (JAR) signature when there are none.
+    /// APK signing certificates are self-signed: the signer is reported, not trusted, so check
+    /// its thumbprint against the expected one.
+    #[cfg(target_os = "linux")]
+    pub fn for_apk<P: AsRef<std::path::Path>>(path: P) -> Self {
+        CodeSignVerifier(Verifier::for_apk(path))
+    }
+
+    /// Create a verifier for a file with a Sigstore bundle (`.sigstore.json`), as made by `cosign`.
+    /// The verification is offline: the Fulcio certificate chain, its embedded SCT, the Rekor
+    /// signed entry timestamp and inclusion proof are all checked against `trusted_root`
+    /// (a `trusted_root.json` as distributed by TUF), whose keys and certificate authorities must
+    /// have been in use (`validFor`) when the entry was logged or the SCT issued.
+    /// Only message signature bundles are supported.
+    #[cfg(target_os = "linux")]
+    pub fn for_sigstore_bundle<
+        P: AsRef<std::path::Path>,
+        B: AsRef<std::path::Path>,
+        T: AsRef<std::path::Path>,
+    >(
+        path: P,
+        bundle: B,
+        trusted_root: T,
+    ) -> Self {
+        CodeSignVerifier(Verifier::for_sigstore_bundle(path, bundle, trusted_root))
+    }
+
+    /// Create a verifier for a macOS flat installer package (`.pkg`, a xar archive). The RSA
+    /// signature over the table of contents is checked, its certificate chain to one of the
+    /// certificates in `anchors` (e.g. the Apple Root CA) and the checksum of every file.
+    /// The signature carries no trusted timestamp, so certificate expiry isn't checked.
+    /// A Developer ID Installer signer is reported as such by `SignatureContext::apple_cert_kind`.
+    #[cfg(target_os = "linux")]
+    pub fn for_pkg<P: AsRef<std::path::Path>, A: AsRef<std::path::Path>>(
+        path: P,
+        anchors: A,
+    ) -> Self {
+        CodeSignVerifier(Verifier::for_pkg(path, anchors))
+    }
+
+    /// Create a verifier for an iOS application archive (`.ipa`). The bundle in
+    /// `Payload/<name>.app` is checked: the signature of its main executable (every architecture)
+    /// and the chain of its signer to one of the certificates in `anchors` (e.g. the Apple Root
+    /// CA), the Info.plist and resource seal bound to it, every sealed resource and, recursively,
+    /// the nested frameworks, plug-ins and libraries against the code directory hashes recorded
+    /// in the seal.
+    /// A modified or missing resource fails the verification. Nested code that fails is reported
+    /// through `SignatureContext::broken_nested_code` and files that aren't sealed through
+    /// `SignatureContext::unsigned_entries`: the bundle is intact only when both are empty.
+    /// Designated requirements aren't evaluated and certificate expiry isn't checked.
+    #[cfg(target_os = "linux")]
+    pub fn for_ipa<P: AsRef<std::path::Path>, A: AsRef<std::path::Path>>(
+        path: P,
+        anchors: A,
+    ) -> Self {
+        CodeSignVerifier(Verifier::for_ipa(path, anchors))
+    }
+
+    /// Create a verifier for a PE/COFF EFI image, checking its Authenticode signatures the way
+    /// firmware does with Secure Boot enabled. `db` and `dbx` are signature databases, either
+    /// `EFI_SIGNATURE_LIST` (`.esl`) files or the efivarfs variables, with X.509 and SHA-256 entries.
+    /// The image is refused with `Error::Revoked` when its digest, a certificate of its signature
+    /// chain or the issuer of one is in `dbx`. An image allowed by its digest in `db` rather than by a signature is
+    /// reported through `SignatureContext::signer_identity`. Certificate expiry is ignored.
+    #[cfg(target_os = "linux")]
+    pub fn for_uefi_image<
+        P: AsRef<std::path::Path>,
+        D: AsRef<std::path::Path>,
+        X: AsRef<std::path::Path>,
+    >(
+        path: P,
+        db: D,
+        dbx: X,
+    ) -> Self {
+        CodeSignVerifier(Verifier::for_uefi_image(path, db, dbx, None))
+    }
+
+    /// Create a verifier for a PE/COFF EFI image as `for_uefi_image` does, also checking its
+    /// `.sbat` section against `sbat_level`, the SBAT revocations applied by shim
+    /// (e.g. the `SbatLevelRT` variable). Images without SBAT data are refused.
+    #[cfg(target_os = "linux")]
+    pub fn for_uefi_image_with_sbat<
+        P: AsRef<std::path::Path>,
+        D: AsRef<std::path::Path>,
+        X: AsRef<std::path::Path>,
+        S: AsRef<std::path::Path>,
+    >(
+        path: P,
+        db: D,
+        dbx: X,
+        sbat_level: S,
+    ) -> Self {
+        CodeSignVerifier(Verifier::for_uefi_image(
+            path,
+            db,
+            dbx,
+            Some(sbat_level.as_ref()),
+        ))
+    }
+
     /// Perform the verification itself.
     /// On macOS the verification uses the Security framework with "anchor trusted" as the requirement.
+    /// Code with a valid ad-hoc signature is reported as `Error::AdHocSigned` instead.
     /// On Windows the verification uses `WinTrust` and the `WINTRUST_ACTION_GENERIC_VERIFY_V2` action.
+    /// On Linux the signature is checked against the provided certificates, there is no system trust store.
     ///
     /// # Examples
     ///
@@ -97,7 +612,6 @@ impl SignatureContext {
     ///    ctx.subject_name().organization.as_deref(),
     ///    Some("Microsoft Corporation")
     /// );
-    ///
     /// ```
     #[must_use]
     pub fn subject_name(&self) -> Name {
@@ -127,10 +641,103 @@ impl SignatureContext {
     pub fn serial(&self) -> String {
         self.0.serial()
     }
+
+    /// Retrieve the identifier of the signing key.
+    /// For certificates this is the 32 bit key identifier used by IMA and `evmctl`,
+    /// for OpenPGP, minisign and signify keys the 64 bit key ID, for SSH keys the fingerprint.
+    #[cfg(target_os = "linux")]
+    #[must_use]
+    pub fn key_id(&self) -> String {
+        self.0.key_id()
+    }
+
+    /// Retrieve the identity of a signer that signed with a bare key.
+    /// Returns `None` for signatures made with a certificate.
+    /// For bare keys the certificate fields (names, thumbprints, serial) are empty.
+    #[cfg(target_os = "linux")]
+    #[must_use]
+    pub fn signer_identity(&self) -> Option<SignerIdentity> {
+        self.0.signer_identity()
+    }
+
+    /// Retrieve the entries of a signed Java archive that aren't covered by every signature,
+    /// or the files of an iOS application bundle that aren't sealed, e.g. entries added after
+    /// signing. Empty for other signatures.
+    #[cfg(target_os = "linux")]
+    #[must_use]
+    pub fn unsigned_entries(&self) -> Vec<String> {
+        self.0.unsigned_entries()
+    }
+
+    /// Retrieve the nested code of an iOS application bundle (frameworks, plug-ins, libraries)
+    /// that failed verification, by path in the bundle, with the reason. Empty for other
+    /// signatures.
+    #[cfg(target_os = "linux")]
+    #[must_use]
+    pub fn broken_nested_code(&self) -> &[(String, Error)] {
+        self.0.broken_nested_code()
+    }
+
+    /// Retrieve the state of a process verified by PID: whether its executable was deleted or
+    /// replaced, and which mapped shared objects failed verification.
+    /// Returns `None` for other verifications.
+    #[cfg(target_os = "linux")]
+    #[must_use]
+    pub fn process_info(&self) -> Option<&ProcessInfo> {
+        self.0.process_info()
+    }
+
+    /// Retrieve the OIDC issuer and subject of a Sigstore (Fulcio) certificate.
+    /// Returns `None` for certificates without the Fulcio issuer extension.
+    #[cfg(target_os = "linux")]
+    #[must_use]
+    pub fn sigstore_identity(&self) -> Option<SigstoreIdentity> {
+        self.0.sigstore_identity()
+    }
+
+    /// Classify the leaf certificate by its Apple code signing extensions.
+    /// Returns `None` for certificates that weren't issued for Apple code signing.
+    #[must_use]
+    pub fn apple_cert_kind(&self) -> Option<AppleCertKind> {
+        AppleCertKind::from_extensions(|oid| self.0.has_extension(oid))
+    }
+
+    /// Retrieve the launch constraints (self, parent, responsible) and library constraints
+    /// embedded in the code signature of the main executable.
+    /// Constraints applied to platform binaries through the trust cache are not included.
+    /// Fails with `BadSignature` if the executable no longer matches the verified signature.
+    #[cfg(target_os = "macos")]
+    pub fn launch_constraints(&self) -> Result<LaunchConstraints, Error> {
+        self.0.launch_constraints()
+    }
+
+    /// Retrieve the entitlements the code was signed with
+    #[cfg(target_os = "macos")]
+    #[must_use]
+    pub fn entitlements(&self) -> std::collections::BTreeMap<String, PropertyValue> {
+        self.0.entitlements()
+    }
+
+    /// Retrieve and verify the provisioning profile embedded in the application bundle.
+    /// The profile signature is evaluated against the system trust anchors.
+    /// Returns `None` when the code isn't a bundle with an embedded profile.
+    #[cfg(target_os = "macos")]
+    pub fn provisioning_profile(&self) -> Result<Option<ProvisioningProfile>, Error> {
+        self.0.provisioning_profile()
+    }
+}
+
+/// Format `bytes` as lowercase hexadecimal
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::new(), |s, byte| s + &format!("{byte:02x}"))
 }
 
 #[cfg(test)]
 mod tests {
+    #[cfg(target_os = "linux")]
+    use crate::test_util::testdata;
     use crate::Error;
 
     #[test]
@@ -154,6 +761,11 @@ mod tests {
             ctx.sha1_thumbprint(),
             "013e2787748a74103d62d2cdbf77a1345517c482"
         );
+
+        assert_eq!(
+            ctx.apple_cert_kind(),
+            Some(super::AppleCertKind::ApplePlatform)
+        );
     }
 
     #[test]
@@ -180,6 +792,601 @@ mod tests {
         );
 
         assert_eq!(ctx.serial(), "3300000460cf42a912315f6fb3000000000460");
+
+        assert_eq!(ctx.apple_cert_kind(), None);
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_kernel_module() {
+        let verifier = super::CodeSignVerifier::for_kernel_module(
+            testdata("module.ko"),
+            testdata("signing_key.x509"),
+        );
+        let ctx = verifier.verify().unwrap();
+
+        assert_eq!(
+            ctx.subject_name().common_name.as_deref(),
+            Some("codesign-verify test signing key")
+        );
+
+        assert_eq!(ctx.subject_name(), ctx.issuer_name()); // Self signed
+        assert_eq!(ctx.key_id(), "e6daa615");
+        assert_eq!(ctx.apple_cert_kind(), None);
+
+        // The test binary itself has no module signature
+        let path = std::env::args().next().unwrap();
+        assert!(matches!(
+            super::CodeSignVerifier::for_kernel_module(path, testdata("signing_key.x509")).verify(),
+            Err(Error::Unsigned)
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_fsverity() {
+        use super::{FsVerityAlgorithm, FsVerityDigest};
+
+        let digest =
+            FsVerityDigest::for_file(testdata("module.ko"), FsVerityAlgorithm::Sha256, &[])
+                .unwrap();
+
+        assert_eq!(
+            digest.to_string(),
+            "sha256:1bdc619a05592bb6462cddf3241343991e5ab5f856d1f57e3c751b284a3a2760"
+        );
+
+        let ctx = super::CodeSignVerifier::for_fsverity(
+            digest,
+            testdata("module.ko.fsv-sig"),
+            testdata("signing_key.x509"),
+        )
+        .verify()
+        .unwrap();
+
+        assert_eq!(ctx.key_id(), "e6daa615");
+
+        // The signature doesn't cover a digest computed with a salt
+        let digest =
+            FsVerityDigest::for_file(testdata("module.ko"), FsVerityAlgorithm::Sha256, b"salt")
+                .unwrap();
+
+        assert!(matches!(
+            super::CodeSignVerifier::for_fsverity(
+                digest,
+                testdata("module.ko.fsv-sig"),
+                testdata("signing_key.x509"),
+            )
+            .verify(),
+            Err(Error::BadSignature)
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_openpgp() {
+        use super::SignerIdentity;
+
+        let verify = |signature: &str| {
+            super::CodeSignVerifier::for_openpgp(
+                testdata("module.ko"),
+                testdata(signature),
+                testdata("keyring.asc"),
+            )
+            .verify()
+        };
+
+        let ctx = verify("module.ko.sig").unwrap(); // Ed25519, binary
+        assert_eq!(ctx.key_id(), "5f14892377a7574b");
+        assert_eq!(
+            ctx.signer_identity(),
+            Some(SignerIdentity::OpenPgp {
+                fingerprint: "d77d3937ec7f05b1e89d36435f14892377a7574b".to_string(),
+                user_ids: vec!["Test Signer <signer@example.com>".to_string()],
+                created: std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_792_343_703),
+            })
+        );
+        assert_eq!(ctx.subject_name().common_name, None);
+
+        let ctx = verify("module.ko.asc").unwrap(); // RSA, ASCII armored
+        assert_eq!(ctx.key_id(), "88765b2a2dbdb569");
+
+        // SHA-1 is too weak for a document signature
+        assert!(matches!(
+            verify("module.ko.sha1.sig"),
+            Err(Error::UnsupportedAlgorithm)
+        ));
+
+        // Not an OpenPGP signature
+        assert!(matches!(
+            verify("module.ko.fsv-sig"),
+            Err(Error::MalformedSignature)
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_minisign() {
+        use super::SignerIdentity;
+
+        let verify = |path: &str, signature: &str, public_key: &str| {
+            super::CodeSignVerifier::for_minisign(
+                testdata(path),
+                testdata(signature),
+                testdata(public_key),
+            )
+            .verify()
+        };
+
+        let ctx = verify("module.ko", "module.ko.minisig", "minisign.pub").unwrap();
+        assert_eq!(ctx.key_id(), "88796A5B4C3D2E1F");
+        assert_eq!(
+            ctx.signer_identity(),
+            Some(SignerIdentity::Minisign {
+                key_id: "88796A5B4C3D2E1F".to_string(),
+                trusted_comment: Some("timestamp:1792343703\tfile:module.ko\thashed".to_string()),
+            })
+        );
+
+        let ctx = verify("module.ko", "module.ko.signify-sig", "signify.pub").unwrap();
+        assert_eq!(
+            ctx.signer_identity(),
+            Some(SignerIdentity::Minisign {
+                key_id: "0807060504030201".to_string(),
+                trusted_comment: None,
+            })
+        );
+
+        // A signify key only accepts signatures without a trusted comment
+        assert!(matches!(
+            verify("module.ko", "module.ko.minisig", "signify.pub"),
+            Err(Error::MalformedSignature)
+        ));
+        assert!(matches!(
+            verify("module.ko", "module.ko.signify-sig", "minisign.pub"),
+            Err(Error::MalformedSignature)
+        ));
+
+        assert!(matches!(
+            verify("signing_key.x509", "module.ko.minisig", "minisign.pub"),
+            Err(Error::BadSignature)
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_sshsig() {
+        use super::SignerIdentity;
+
+        let verify = |signature: &str, namespace: &str| {
+            super::CodeSignVerifier::for_sshsig(
+                testdata("module.ko"),
+                testdata(signature),
+                testdata("allowed_signers"),
+                namespace,
+            )
+            .verify()
+        };
+
+        // Ed25519, the key is listed on three lines, the last one has expired
+        let ctx = verify("module.ko.alice.sshsig", "file").unwrap();
+        assert_eq!(
+            ctx.signer_identity(),
+            Some(SignerIdentity::Ssh {
+                principals: vec![
+                    "alice@example.com".to_string(),
+                    "alice@corp.example.com".to_string(),
+                    "release@example.com".to_string()
+                ],
+                fingerprint: "SHA256:I2qM8IOuIHIj6C+GRliJIFqfjx2gLUZib3Fv6jqtGhQ".to_string(),
+            })
+        );
+
+        let ctx = verify("module.ko.bob.sshsig", "release").unwrap(); // RSA
+        assert_eq!(
+            ctx.key_id(),
+            "SHA256:XpzqcZqSLPgV/JlWGCmdJqpuOtK5+tf4I0lCmqG+/fM"
+        );
+
+        // The namespace must match the one the signature was made for
+        assert!(matches!(
+            verify("module.ko.alice.sshsig", "git"),
+            Err(Error::BadSignature)
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_rpm() {
+        use super::SignerIdentity;
+
+        let verify = |path: &str| {
+            super::CodeSignVerifier::for_rpm(testdata(path), testdata("keyring.asc")).verify()
+        };
+
+        let ctx = verify("hello-1.0-1.noarch.rpm").unwrap();
+        assert_eq!(ctx.key_id(), "88765b2a2dbdb569");
+        assert!(matches!(
+            ctx.signer_identity(),
+            Some(SignerIdentity::OpenPgp { user_ids, .. }) if user_ids == ["RSA Signer <rsa@example.com>"]
+        ));
+
+        // Not an RPM package
+        assert!(matches!(
+            verify("module.ko"),
+            Err(Error::MalformedSignature)
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_deb() {
+        use super::SignerIdentity;
+
+        let verify = |path: &str| {
+            super::CodeSignVerifier::for_deb(testdata(path), testdata("keyring.asc")).verify()
+        };
+
+        let ctx = verify("hello_1.0_all.deb").unwrap(); // debsig, Ed25519
+        assert!(matches!(
+            ctx.signer_identity(),
+            Some(SignerIdentity::OpenPgp { fingerprint, .. })
+                if fingerprint == "d77d3937ec7f05b1e89d36435f14892377a7574b"
+        ));
+
+        let ctx = verify("hello-builder_1.0_all.deb").unwrap(); // dpkg-sig, RSA
+        assert_eq!(ctx.key_id(), "88765b2a2dbdb569");
+
+        // Not an ar archive
+        assert!(matches!(
+            verify("module.ko"),
+            Err(Error::MalformedSignature)
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_jar() {
+        // Signed with an EC and a DSA key, then an entry was added
+        let ctx = super::CodeSignVerifier::for_jar(testdata("plugin.jar"))
+            .verify()
+            .unwrap();
+        assert_eq!(
+            ctx.subject_name().common_name.as_deref(),
+            Some("Example Plugin EC")
+        );
+        assert_eq!(
+            ctx.unsigned_entries(),
+            vec!["com/example/plugin/Extra.class".to_string()]
+        );
+
+        // An APK signed with jarsigner is a JAR as well
+        let ctx = super::CodeSignVerifier::for_jar(testdata("app-v1.apk"))
+            .verify()
+            .unwrap();
+        assert!(ctx.unsigned_entries().is_empty());
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_pkg() {
+        let verify = |anchors: &str| {
+            super::CodeSignVerifier::for_pkg(testdata("installer.pkg"), testdata(anchors)).verify()
+        };
+
+        let ctx = verify("pkg_root.pem").unwrap();
+        assert_eq!(
+            ctx.subject_name().common_name.as_deref(),
+            Some("Developer ID Installer: Example Corp")
+        );
+        assert_eq!(
+            ctx.apple_cert_kind(),
+            Some(super::AppleCertKind::DeveloperIdInstaller)
+        );
+
+        assert!(matches!(
+            verify("signing_key.x509"),
+            Err(Error::UnknownSigner)
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_ipa() {
+        let verify = |ipa: &str| {
+            super::CodeSignVerifier::for_ipa(testdata(ipa), testdata("ipa_root.pem")).verify()
+        };
+
+        let ctx = verify("app.ipa").unwrap();
+        assert_eq!(
+            ctx.subject_name().common_name.as_deref(),
+            Some("Apple Distribution: Example Corp (ABCDE12345)")
+        );
+        assert_eq!(
+            ctx.apple_cert_kind(),
+            Some(super::AppleCertKind::AppleDistribution)
+        );
+        assert!(ctx.broken_nested_code().is_empty());
+        assert!(ctx.unsigned_entries().is_empty());
+
+        let ctx = verify("app-nested.ipa").unwrap();
+        let broken: Vec<_> = ctx
+            .broken_nested_code()
+            .iter()
+            .map(|(code, _)| code.as_str())
+            .collect();
+        assert_eq!(broken, ["Frameworks/Example.framework"]);
+        assert_eq!(ctx.unsigned_entries(), ["Frameworks/Inject.dylib"]);
+
+        assert!(matches!(
+            verify("app-resource.ipa"),
+            Err(super::Error::BadSignature)
+        ));
+        assert!(matches!(
+            super::CodeSignVerifier::for_ipa(testdata("app.ipa"), testdata("pkg_root.pem"))
+                .verify(),
+            Err(super::Error::UnknownSigner)
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_bytes() {
+        use std::io::{Seek, SeekFrom};
+
+        let thumbprint = |ctx: super::SignatureContext| {
+            (
+                ctx.sha256_thumbprint(),
+                ctx.unsigned_entries().clone(),
+                ctx.broken_nested_code().len(),
+            )
+        };
+        let verify = |name: &str, keyring: &str| {
+            let data = std::fs::read(testdata(name)).unwrap();
+            super::CodeSignVerifier::for_bytes_with_keyring(&data, testdata(keyring))
+                .verify()
+                .map(thumbprint)
+        };
+
+        let files = [
+            (
+                "plugin.jar",
+                "pkg_root.pem",
+                super::CodeSignVerifier::for_jar(testdata("plugin.jar")),
+            ),
+            (
+                "app-v3.apk",
+                "pkg_root.pem",
+                super::CodeSignVerifier::for_apk(testdata("app-v3.apk")),
+            ),
+            (
+                "installer.pkg",
+                "pkg_root.pem",
+                super::CodeSignVerifier::for_pkg(
+                    testdata("installer.pkg"),
+                    testdata("pkg_root.pem"),
+                ),
+            ),
+            (
+                "app-nested.ipa",
+                "ipa_root.pem",
+                super::CodeSignVerifier::for_ipa(
+                    testdata("app-nested.ipa"),
+                    testdata("ipa_root.pem"),
+                ),
+            ),
+            (
+                "module.ko",
+                "signing_key.x509",
+                super::CodeSignVerifier::for_kernel_module(
+                    testdata("module.ko"),
+                    testdata("signing_key.x509"),
+                ),
+            ),
+        ];
+        for (name, keyring, file) in files {
+            let expected = thumbprint(file.verify().unwrap());
+            assert_eq!(verify(name, keyring).unwrap(), expected, "{name}");
+        }
+
+        // The reader is read from its start and left where it was
+        let mut reader = std::io::Cursor::new(std::fs::read(testdata("module.ko")).unwrap());
+        reader.seek(SeekFrom::Start(16)).unwrap();
+        let ctx = super::CodeSignVerifier::for_reader_with_keyring(
+            &mut reader,
+            testdata("signing_key.x509"),
+        )
+        .unwrap()
+        .verify()
+        .unwrap();
+        assert_eq!(
+            thumbprint(ctx),
+            verify("module.ko", "signing_key.x509").unwrap()
+        );
+        assert_eq!(reader.position(), 16);
+
+        assert!(matches!(
+            verify("module.ko", "pkg_root.pem"),
+            Err(super::Error::UnknownSigner)
+        ));
+
+        // IMA signed files and plain ELF files carry no signature in their content
+        let module = std::fs::read(testdata("module.ko")).unwrap();
+        let elf = &module[..module.len() - 28];
+        for data in [&b"#!/bin/sh\n"[..], elf] {
+            assert!(matches!(
+                super::CodeSignVerifier::for_bytes(data).unwrap().verify(),
+                Err(super::Error::UnsupportedInput)
+            ));
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_apk() {
+        let verify = |path: &str| super::CodeSignVerifier::for_apk(testdata(path)).verify();
+
+        let ctx = verify("app-v1.apk").unwrap(); // JAR signature only
+        assert_eq!(
+            ctx.subject_name().common_name.as_deref(),
+            Some("Example App v1")
+        );
+
+        let ctx = verify("app-v3.apk").unwrap(); // v2 with the old key, v3 rotated to a new key
+        assert_eq!(
+            ctx.subject_name().common_name.as_deref(),
+            Some("Example App (rotated)")
+        );
+        assert_eq!(
+            ctx.sha1_thumbprint(),
+            "6bf9f9e5dd9b3a5d8a63808634ce31b5889729cd"
+        );
+
+        // The v1 signature file claims a v2 and v3 signature, which are gone
+        assert!(matches!(
+            verify("app-v1-stripped.apk"),
+            Err(Error::BadSignature)
+        ));
+
+        // Not a ZIP archive
+        assert!(matches!(
+            verify("module.ko"),
+            Err(Error::MalformedSignature)
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_sigstore_bundle() {
+        use super::SigstoreIdentity;
+
+        let verify = |path: &str| {
+            super::CodeSignVerifier::for_sigstore_bundle(
+                testdata(path),
+                testdata("module.ko.sigstore.json"),
+                testdata("trusted_root.json"),
+            )
+            .verify()
+        };
+
+        let ctx = verify("module.ko").unwrap();
+        assert_eq!(
+            ctx.sigstore_identity(),
+            Some(SigstoreIdentity {
+                issuer: "https://accounts.example.com".to_string(),
+                subject: "signer@example.com".to_string(),
+            })
+        );
+        assert_eq!(
+            ctx.sha1_thumbprint(),
+            "9b4f568a2a8d0c614dcdda37addf402731daa8e9"
+        );
+
+        // The bundle doesn't cover another file
+        assert!(matches!(
+            verify("signing_key.x509"),
+            Err(Error::BadSignature)
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_uefi_image() {
+        let image = testdata("bootx64.efi");
+
+        // The signer chains to the CA in db, the efivarfs variable with its attributes
+        let ctx = super::CodeSignVerifier::for_uefi_image_with_sbat(
+            &image,
+            testdata("db.esl"),
+            testdata("dbx.esl"),
+            testdata("sbat_level.csv"),
+        )
+        .verify()
+        .unwrap();
+        assert_eq!(
+            ctx.subject_name().common_name.as_deref(),
+            Some("Example Bootloader Signing")
+        );
+        assert_eq!(
+            ctx.issuer_name().common_name.as_deref(),
+            Some("Example Secure Boot CA")
+        );
+
+        // dbx only lists digests and certificates, it doesn't allow anything
+        assert!(matches!(
+            super::CodeSignVerifier::for_uefi_image(
+                &image,
+                testdata("dbx.esl"),
+                testdata("dbx.esl"),
+            )
+            .verify(),
+            Err(Error::UnknownSigner)
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_pid() {
+        use std::convert::TryFrom;
+
+        // Our own executable is unsigned
+        let pid = i32::try_from(std::process::id()).unwrap();
+        assert!(matches!(
+            super::CodeSignVerifier::for_pid(pid).unwrap().verify(),
+            Err(Error::Unsigned)
+        ));
+
+        assert!(matches!(
+            super::CodeSignVerifier::for_pid(-1),
+            Err(Error::IoError(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_unix_peer() {
+        use std::convert::TryFrom;
+
+        // The peer of a socket pair is ourselves, our executable is unsigned
+        let (stream, _peer) = std::os::unix::net::UnixStream::pair().unwrap();
+        let (verifier, credentials) = super::CodeSignVerifier::for_unix_peer(&stream).unwrap();
+
+        assert_eq!(
+            credentials,
+            super::PeerCredentials {
+                pid: i32::try_from(std::process::id()).unwrap(),
+                uid: unsafe { libc::geteuid() },
+                gid: unsafe { libc::getegid() },
+            }
+        );
+        assert!(matches!(verifier.verify(), Err(Error::Unsigned)));
+    }
+
+    #[test]
+    #[cfg(target_os = "macos")]
+    fn test_disk_image() {
+        use crate::test_util::TempFile;
+
+        // A UDIF image whose "koly" trailer references no code signature
+        let mut koly = vec![0; 512];
+        koly[0..4].copy_from_slice(b"koly");
+        koly[4..8].copy_from_slice(&4u32.to_be_bytes()); // Version
+        koly[8..12].copy_from_slice(&512u32.to_be_bytes()); // Header size
+        let unsigned = TempFile::new("unsigned.dmg", [vec![0; 4096], koly.clone()].concat());
+
+        assert!(matches!(
+            super::CodeSignVerifier::for_file(&unsigned).verify(),
+            Err(Error::Unsigned)
+        ));
+
+        // The trailer references a code signature that isn't a SuperBlob
+        koly[296..304].copy_from_slice(&0u64.to_be_bytes());
+        koly[304..312].copy_from_slice(&4096u64.to_be_bytes());
+        let malformed = TempFile::new("malformed.dmg", [vec![0; 4096], koly].concat());
+
+        assert!(super::CodeSignVerifier::for_file(&malformed)
+            .verify()
+            .is_err());
     }
 
     #[test]
@@ -191,4 +1398,21 @@ mod tests {
             Err(Error::Unsigned)
         ));
     }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_unsigned_file_handle() {
+        use std::io::{Read, Seek};
+
+        let mut file = std::fs::File::open(std::env::args().next().unwrap()).unwrap();
+        file.read_exact(&mut [0; 4]).unwrap();
+
+        assert!(matches!(
+            super::CodeSignVerifier::for_file_handle(&file)
+                .unwrap()
+                .verify(),
+            Err(Error::Unsigned)
+        ));
+        assert_eq!(file.stream_position().unwrap(), 4);
+    }
 }
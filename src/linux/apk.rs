@@ -0,0 +1,394 @@
+use super::crypto::{verify_signature, verify_spki, HashAlgorithm};
+use super::jar;
+use super::zip::Archive;
+use crate::Error;
+use der::{Decode, Encode};
+use std::convert::{TryFrom, TryInto};
+use std::path::Path;
+use x509_cert::Certificate;
+
+const APK_SIG_BLOCK_MAGIC: &[u8] = b"APK Sig Block 42";
+const APK_SIGNATURE_SCHEME_V2_ID: u32 = 0x7109_871a;
+const APK_SIGNATURE_SCHEME_V3_ID: u32 = 0xf053_68c0;
+const PROOF_OF_ROTATION_ATTR_ID: u32 = 0x3ba0_6f8c;
+const STRIPPING_PROTECTION_ATTR_ID: u32 = 0xbeef_f00d;
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// A little endian length prefixed reader, as used throughout the APK Signing Block
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < len {
+            return Err(Error::MalformedSignature);
+        }
+
+        let (value, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(value)
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn prefixed(&mut self) -> Result<Reader<'a>, Error> {
+        let len = self.u32()? as usize;
+        self.take(len).map(Reader)
+    }
+
+    /// Read a length prefixed sequence of length prefixed elements
+    fn sequence(&mut self) -> Result<Vec<Reader<'a>>, Error> {
+        let mut sequence = self.prefixed()?;
+        let mut elements = Vec::new();
+
+        while !sequence.0.is_empty() {
+            elements.push(sequence.prefixed()?);
+        }
+
+        Ok(elements)
+    }
+}
+
+/// A signature algorithm: the hash signed, and the chunked content digest it goes with
+fn signature_algorithm(id: u32) -> Option<HashAlgorithm> {
+    match id {
+        0x0103 | 0x0201 => Some(HashAlgorithm::Sha256), // RSASSA-PKCS1-v1_5, ECDSA with SHA-256
+        0x0104 | 0x0202 => Some(HashAlgorithm::Sha512), // RSASSA-PKCS1-v1_5, ECDSA with SHA-512
+        _ => None, // RSASSA-PSS, DSA and the verity digests aren't supported
+    }
+}
+
+/// The APK Signing Block: its offset and ID-value pairs
+struct SigningBlock<'a> {
+    offset: usize,
+    pairs: Vec<(u32, &'a [u8])>,
+}
+
+/// Find the APK Signing Block, which sits right before the central directory
+fn signing_block<'a>(data: &'a [u8], archive: &Archive<'_>) -> Option<SigningBlock<'a>> {
+    let end = archive.central_directory_offset;
+    let footer = data.get(end.checked_sub(24)?..end)?;
+
+    if &footer[8..] != APK_SIG_BLOCK_MAGIC {
+        return None;
+    }
+
+    // The block size is repeated in the header and footer, it excludes the header size field
+    let size = usize::try_from(u64::from_le_bytes(footer[..8].try_into().unwrap())).ok()?;
+    if size < 24 {
+        return None;
+    }
+
+    let start = end.checked_sub(size.checked_add(8)?)?;
+    let mut reader = Reader(&data[start..end - 24]);
+
+    if reader.u64().ok()? != size as u64 {
+        return None;
+    }
+
+    let mut pairs = Vec::new();
+    while !reader.0.is_empty() {
+        let len = usize::try_from(reader.u64().ok()?).ok()?;
+        let mut pair = Reader(reader.take(len).ok()?);
+        pairs.push((pair.u32().ok()?, pair.0));
+    }
+
+    Some(SigningBlock {
+        offset: start,
+        pairs,
+    })
+}
+
+/// Compute the chunked digest of the APK contents, the central directory and the end of
+/// central directory record, which are hashed as if the signing block wasn't there
+fn content_digest(
+    data: &[u8],
+    archive: &Archive<'_>,
+    block_offset: usize,
+    hash: HashAlgorithm,
+) -> Vec<u8> {
+    let mut eocd = data[archive.eocd_offset..].to_vec();
+    #[allow(clippy::cast_possible_truncation)]
+    eocd[16..20].copy_from_slice(&(block_offset as u32).to_le_bytes());
+
+    let sections = [
+        &data[..block_offset],
+        &data[archive.central_directory_offset..archive.eocd_offset],
+        &eocd,
+    ];
+
+    let chunks: Vec<&[u8]> = sections
+        .iter()
+        .flat_map(|section| section.chunks(CHUNK_SIZE))
+        .collect();
+
+    let mut digests = vec![0x5a];
+    #[allow(clippy::cast_possible_truncation)]
+    digests.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+
+    for chunk in chunks {
+        #[allow(clippy::cast_possible_truncation)]
+        let prefix = [&[0xa5], &(chunk.len() as u32).to_le_bytes()[..]].concat();
+        digests.extend(hash.digest(&[prefix.as_slice(), chunk].concat()));
+    }
+
+    hash.digest(&digests)
+}
+
+/// Verify a signing certificate lineage: each certificate signs the next one.
+/// Returns the last certificate, which must be the current signer.
+fn verify_lineage(lineage: &[u8]) -> Result<Certificate, Error> {
+    let mut reader = Reader(lineage);
+    if reader.u32()? != 1 {
+        return Err(Error::UnsupportedAlgorithm);
+    }
+
+    let mut previous: Option<(Certificate, u32)> = None;
+
+    while !reader.0.is_empty() {
+        // signed data (certificate, signature algorithm), flags, signature algorithm, signature
+        let mut node = reader.prefixed()?;
+        let signed_data = node.prefixed()?;
+        node.take(4)?; // Flags
+        let algorithm = node.u32()?;
+        let signature = node.prefixed()?;
+
+        let mut signed = Reader(signed_data.0);
+        let cert =
+            Certificate::from_der(signed.prefixed()?.0).map_err(|_| Error::MalformedSignature)?;
+        let signed_algorithm = signed.u32()?;
+
+        // The first certificate isn't signed, every other one by its predecessor
+        if let Some((parent, parent_algorithm)) = previous {
+            let hash = signature_algorithm(parent_algorithm).ok_or(Error::UnsupportedAlgorithm)?;
+            if parent_algorithm != signed_algorithm {
+                return Err(Error::BadSignature);
+            }
+            verify_signature(&parent, hash, &hash.digest(signed_data.0), signature.0)?;
+        }
+
+        previous = Some((cert, algorithm));
+    }
+
+    previous
+        .map(|(cert, _)| cert)
+        .ok_or(Error::MalformedSignature)
+}
+
+/// Verify the signers of a v2 or v3 signature scheme block, returning the certificate of the
+/// first signer. `has_v3` tells whether the APK Signing Block has a v3 signature scheme block.
+fn verify_signers(
+    value: &[u8],
+    v3: bool,
+    has_v3: bool,
+    digest: &mut dyn FnMut(HashAlgorithm) -> Vec<u8>,
+) -> Result<Certificate, Error> {
+    let signers = Reader(value).sequence()?;
+    let mut certs = Vec::new();
+
+    for mut signer in signers {
+        let mut signed_data = signer.prefixed()?;
+        if v3 {
+            signer.take(8)?; // Minimum and maximum SDK versions
+        }
+        let signatures = signer.sequence()?;
+        let public_key = signer.prefixed()?;
+        let key = x509_cert::spki::SubjectPublicKeyInfoOwned::from_der(public_key.0)
+            .map_err(|_| Error::MalformedSignature)?;
+
+        // Every supported signature must verify over the signed data
+        let mut algorithms = Vec::new();
+
+        for mut signature in signatures {
+            let id = signature.u32()?;
+            let value = signature.prefixed()?;
+            let Some(hash) = signature_algorithm(id) else {
+                continue;
+            };
+
+            verify_spki(&key, hash, &hash.digest(signed_data.0), value.0)?;
+            algorithms.push(id);
+        }
+
+        if algorithms.is_empty() {
+            return Err(Error::UnsupportedAlgorithm);
+        }
+
+        let digests = signed_data.sequence()?;
+        let signed_certs = signed_data.sequence()?;
+        if v3 {
+            signed_data.take(8)?;
+        }
+        let attributes = signed_data.sequence()?;
+
+        // The digests must be listed for the same algorithms as the signatures
+        let digests = digests
+            .into_iter()
+            .map(|mut entry| Ok((entry.u32()?, entry.prefixed()?.0)))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        for id in algorithms {
+            let (_, expected) = digests
+                .iter()
+                .find(|(digest_id, _)| *digest_id == id)
+                .ok_or(Error::MalformedSignature)?;
+
+            if digest(signature_algorithm(id).unwrap()) != *expected {
+                return Err(Error::BadSignature);
+            }
+        }
+
+        let cert = signed_certs
+            .first()
+            .ok_or(Error::MalformedSignature)
+            .and_then(|cert| {
+                Certificate::from_der(cert.0).map_err(|_| Error::MalformedSignature)
+            })?;
+
+        let spki = cert
+            .tbs_certificate
+            .subject_public_key_info
+            .to_der()
+            .map_err(|_| Error::MalformedSignature)?;
+        if spki != public_key.0 {
+            return Err(Error::BadSignature);
+        }
+
+        for mut attribute in attributes {
+            let id = attribute.u32()?;
+
+            // A rotated key proves it was signed by the previous keys, the lineage ends with it
+            if v3 && id == PROOF_OF_ROTATION_ATTR_ID && verify_lineage(attribute.0)? != cert {
+                return Err(Error::BadSignature);
+            }
+
+            // A v2 signer that declares a v3 signature was signed along with it, so the v3
+            // signature was stripped when its block is missing
+            if !v3 && id == STRIPPING_PROTECTION_ATTR_ID && attribute.u32()? == 3 && !has_v3 {
+                return Err(Error::BadSignature);
+            }
+        }
+
+        certs.push(cert);
+    }
+
+    certs.into_iter().next().ok_or(Error::Unsigned)
+}
+
+/// Verify the v1 (JAR) signature of an APK without v2 or v3 signatures
+fn verify_v1(archive: &Archive<'_>) -> Result<Certificate, Error> {
+    let signature = jar::verify_archive(archive)?;
+
+    // Android rejects v1 signed APKs with entries that aren't signed
+    if !signature.unsigned_entries.is_empty() {
+        return Err(Error::BadSignature);
+    }
+
+    // A signature file that claims a newer scheme was signed along with it, so that scheme's
+    // signature was stripped
+    if signature
+        .apk_signature_schemes
+        .iter()
+        .any(|scheme| *scheme == 2 || *scheme == 3)
+    {
+        return Err(Error::BadSignature);
+    }
+
+    Ok(signature.cert)
+}
+
+/// Verify the APK at `path`, as `verify_apk_data` does
+pub(crate) fn verify_apk(path: &Path) -> Result<Certificate, Error> {
+    verify_apk_data(&std::fs::read(path).map_err(Error::IoError)?)
+}
+
+/// Verify an APK with its v3 or v2 signature scheme blocks, or its v1 (JAR) signature when
+/// there are none. Returns the signer certificate, which is self-signed.
+pub(crate) fn verify_apk_data(data: &[u8]) -> Result<Certificate, Error> {
+    let archive = Archive::parse(data)?;
+
+    let Some(block) = signing_block(data, &archive) else {
+        return verify_v1(&archive);
+    };
+
+    let mut digests: Vec<(HashAlgorithm, Vec<u8>)> = Vec::new();
+    let mut digest = |hash: HashAlgorithm| {
+        if let Some((_, digest)) = digests.iter().find(|(algorithm, _)| *algorithm == hash) {
+            return digest.clone();
+        }
+
+        let digest = content_digest(data, &archive, block.offset, hash);
+        digests.push((hash, digest.clone()));
+        digest
+    };
+
+    let find = |id| {
+        block
+            .pairs
+            .iter()
+            .find(|(pair, _)| *pair == id)
+            .map(|(_, value)| *value)
+    };
+    let has_v3 = find(APK_SIGNATURE_SCHEME_V3_ID).is_some();
+    let v2 = find(APK_SIGNATURE_SCHEME_V2_ID)
+        .map(|value| verify_signers(value, false, has_v3, &mut digest))
+        .transpose()?;
+    let v3 = find(APK_SIGNATURE_SCHEME_V3_ID)
+        .map(|value| verify_signers(value, true, true, &mut digest))
+        .transpose()?;
+
+    // The v3 signer may have rotated to a new key, it is the current signer
+    match v3.or(v2) {
+        Some(cert) => Ok(cert),
+        None => verify_v1(&archive),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{testdata, TempFile};
+
+    #[test]
+    fn test_tampered_apk() {
+        for apk in ["app-v1.apk", "app-v3.apk"] {
+            let mut data = std::fs::read(testdata(apk)).unwrap();
+
+            // The stored resources.arsc is all zeros after its header
+            let archive = Archive::parse(&data).unwrap();
+            let contents = archive
+                .read(archive.find("resources.arsc").unwrap())
+                .unwrap();
+            let position = data
+                .windows(contents.len())
+                .position(|window| window == contents)
+                .unwrap()
+                + 100;
+            data[position] ^= 1;
+
+            let tampered = TempFile::new(apk, data);
+            let result = verify_apk(&tampered);
+
+            assert!(matches!(result, Err(Error::BadSignature)));
+        }
+    }
+
+    #[test]
+    fn test_stripped_v3_apk() {
+        let mut data = std::fs::read(testdata("app-v3.apk")).unwrap();
+
+        // Rename the v3 block to an unknown ID, the v2 signature still covers the same contents
+        let id = APK_SIGNATURE_SCHEME_V3_ID.to_le_bytes();
+        let position = data.windows(4).position(|window| window == id).unwrap();
+        data[position..position + 4].copy_from_slice(&0x4242_4242_u32.to_le_bytes());
+
+        let stripped = TempFile::new("app-v3-stripped.apk", data);
+        assert!(matches!(verify_apk(&stripped), Err(Error::BadSignature)));
+    }
+}
@@ -0,0 +1,229 @@
+use super::crypto::key_id;
+use crate::{hex, Error, Name, ProcessInfo, SignerIdentity, SigstoreIdentity};
+use der::asn1::{ObjectIdentifier, Utf8StringRef};
+use der::{Decode, Encode, Tag, Tagged};
+use x509_cert::ext::pkix::name::GeneralName;
+use x509_cert::ext::pkix::SubjectAltName;
+use x509_cert::Certificate;
+
+const FULCIO_ISSUER: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.1");
+const FULCIO_ISSUER_V2: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.57264.1.8");
+
+pub(crate) enum Context {
+    /// Signed with an X.509 certificate
+    Certificate {
+        cert: Box<Certificate>,
+        cert_data: Vec<u8>,
+        unsigned_entries: Vec<String>,
+        broken_nested_code: Vec<(String, Error)>,
+        process: Option<ProcessInfo>,
+    },
+    /// Signed with a bare key, the certificate fields are empty
+    Key(SignerIdentity),
+}
+
+impl Context {
+    pub fn new(cert: Certificate) -> Self {
+        Context::Certificate {
+            cert_data: cert.to_der().unwrap(), // The certificate was decoded from DER
+            cert: Box::new(cert),
+            unsigned_entries: Vec::new(),
+            broken_nested_code: Vec::new(),
+            process: None,
+        }
+    }
+
+    /// Record the entries of an archive that the signature doesn't cover
+    pub fn with_unsigned_entries(mut self, entries: Vec<String>) -> Self {
+        if let Context::Certificate {
+            unsigned_entries, ..
+        } = &mut self
+        {
+            *unsigned_entries = entries;
+        }
+        self
+    }
+
+    /// Record the nested code of a bundle that failed verification
+    pub fn with_broken_nested_code(mut self, code: Vec<(String, Error)>) -> Self {
+        if let Context::Certificate {
+            broken_nested_code, ..
+        } = &mut self
+        {
+            *broken_nested_code = code;
+        }
+        self
+    }
+
+    /// Record the state of the process whose executable was verified
+    pub fn with_process(mut self, info: ProcessInfo) -> Self {
+        if let Context::Certificate { process, .. } = &mut self {
+            *process = Some(info);
+        }
+        self
+    }
+
+    fn cert(&self) -> Option<&Certificate> {
+        match self {
+            Context::Certificate { cert, .. } => Some(cert),
+            Context::Key(_) => None,
+        }
+    }
+
+    fn get_oid_name(&self, issuer: bool, oid: &str) -> Option<String> {
+        let cert = self.cert()?;
+        let oid = ObjectIdentifier::new(oid).ok()?;
+        let name = if issuer {
+            &cert.tbs_certificate.issuer
+        } else {
+            &cert.tbs_certificate.subject
+        };
+
+        let value = &name
+            .0
+            .iter()
+            .flat_map(|rdn| rdn.0.iter())
+            .find(|attr| attr.oid == oid)?
+            .value;
+
+        match value.tag() {
+            Tag::Utf8String | Tag::PrintableString | Tag::Ia5String | Tag::TeletexString => {
+                String::from_utf8(value.value().to_vec()).ok()
+            }
+            _ => None,
+        }
+    }
+
+    pub fn has_extension(&self, oid: &str) -> bool {
+        let (Some(cert), Ok(oid)) = (self.cert(), ObjectIdentifier::new(oid)) else {
+            return false;
+        };
+
+        cert.tbs_certificate
+            .extensions
+            .iter()
+            .flatten()
+            .any(|ext| ext.extn_id == oid)
+    }
+
+    pub fn signer_identity(&self) -> Option<SignerIdentity> {
+        match self {
+            Context::Certificate { .. } => None,
+            Context::Key(identity) => Some(identity.clone()),
+        }
+    }
+
+    pub fn sigstore_identity(&self) -> Option<SigstoreIdentity> {
+        let cert = self.cert()?;
+        let extension = |oid| {
+            cert.tbs_certificate
+                .extensions
+                .iter()
+                .flatten()
+                .find(|ext| ext.extn_id == oid)
+                .map(|ext| ext.extn_value.as_bytes())
+        };
+
+        // The DER encoded issuer extension replaced the raw one, which older certificates only have
+        let issuer = match extension(FULCIO_ISSUER_V2) {
+            Some(value) => Utf8StringRef::from_der(value).ok()?.as_str().to_string(),
+            None => String::from_utf8(extension(FULCIO_ISSUER)?.to_vec()).ok()?,
+        };
+
+        let subject = match cert.tbs_certificate.get::<SubjectAltName>() {
+            Ok(Some((_, names))) => names.0.into_iter().find_map(|name| match name {
+                GeneralName::Rfc822Name(name) | GeneralName::UniformResourceIdentifier(name) => {
+                    Some(name.to_string())
+                }
+                _ => None,
+            }),
+            _ => None,
+        };
+
+        Some(SigstoreIdentity {
+            issuer,
+            subject: subject.unwrap_or_default(),
+        })
+    }
+
+    pub fn unsigned_entries(&self) -> Vec<String> {
+        match self {
+            Context::Certificate {
+                unsigned_entries, ..
+            } => unsigned_entries.clone(),
+            Context::Key(_) => Vec::new(),
+        }
+    }
+
+    pub fn broken_nested_code(&self) -> &[(String, Error)] {
+        match self {
+            Context::Certificate {
+                broken_nested_code, ..
+            } => broken_nested_code,
+            Context::Key(_) => &[],
+        }
+    }
+
+    pub fn process_info(&self) -> Option<&ProcessInfo> {
+        match self {
+            Context::Certificate { process, .. } => process.as_ref(),
+            Context::Key(_) => None,
+        }
+    }
+
+    pub fn key_id(&self) -> String {
+        match self {
+            Context::Certificate { cert, .. } => hex(&key_id(cert)),
+            Context::Key(SignerIdentity::OpenPgp { fingerprint, .. }) => {
+                fingerprint[fingerprint.len().saturating_sub(16)..].to_string()
+            }
+            Context::Key(SignerIdentity::Minisign { key_id, .. }) => key_id.clone(),
+            Context::Key(SignerIdentity::Ssh { fingerprint, .. }) => fingerprint.clone(),
+            Context::Key(SignerIdentity::ImageDigest { sha256 }) => sha256.clone(),
+        }
+    }
+
+    pub fn serial(&self) -> String {
+        self.cert().map_or_else(String::new, |cert| {
+            hex(cert.tbs_certificate.serial_number.as_bytes())
+        })
+    }
+
+    pub fn subject_name(&self) -> Name {
+        Name {
+            common_name: self.get_oid_name(false, "2.5.4.3"),
+            organization: self.get_oid_name(false, "2.5.4.10"),
+            organization_unit: self.get_oid_name(false, "2.5.4.11"),
+            country: self.get_oid_name(false, "2.5.4.6"),
+        }
+    }
+
+    pub fn issuer_name(&self) -> Name {
+        Name {
+            common_name: self.get_oid_name(true, "2.5.4.3"),
+            organization: self.get_oid_name(true, "2.5.4.10"),
+            organization_unit: self.get_oid_name(true, "2.5.4.11"),
+            country: self.get_oid_name(true, "2.5.4.6"),
+        }
+    }
+
+    #[allow(clippy::items_after_statements)]
+    pub fn sha1_thumbprint(&self) -> String {
+        let Context::Certificate { cert_data, .. } = self else {
+            return String::new();
+        };
+
+        use sha1::Digest;
+        hex(&sha1::Sha1::digest(cert_data))
+    }
+
+    #[allow(clippy::items_after_statements)]
+    pub fn sha256_thumbprint(&self) -> String {
+        let Context::Certificate { cert_data, .. } = self else {
+            return String::new();
+        };
+
+        use sha2::Digest;
+        hex(&sha2::Sha256::digest(cert_data))
+    }
+}
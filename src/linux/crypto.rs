@@ -0,0 +1,310 @@
+use crate::Error;
+use der::asn1::ObjectIdentifier;
+use der::{Decode, Encode};
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use sha1::Sha1;
+use sha2::digest::const_oid::AssociatedOid;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use signature::hazmat::PrehashVerifier;
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::path::Path;
+use x509_cert::ext::pkix::{BasicConstraints, SubjectKeyIdentifier};
+use x509_cert::spki::{SubjectPublicKeyInfoOwned, SubjectPublicKeyInfoRef};
+use x509_cert::Certificate;
+
+const RSA_ENCRYPTION: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.1");
+const ID_DSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10040.4.1");
+const EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+const SECP256R1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.3.1.7");
+const SECP384R1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.132.0.34");
+
+const SHA256_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.11");
+const SHA384_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.12");
+const SHA512_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.1.13");
+const ECDSA_WITH_SHA256: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.2");
+const ECDSA_WITH_SHA384: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.3");
+const ECDSA_WITH_SHA512: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.4.3.4");
+
+/// The longest certificate chain followed from the signer to a trusted certificate
+const MAX_CHAIN_LENGTH: usize = 8;
+
+/// The digest algorithms supported for signatures
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum HashAlgorithm {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl HashAlgorithm {
+    pub fn from_oid(oid: ObjectIdentifier) -> Result<Self, Error> {
+        match oid {
+            Sha1::OID => Ok(HashAlgorithm::Sha1),
+            Sha256::OID => Ok(HashAlgorithm::Sha256),
+            Sha384::OID => Ok(HashAlgorithm::Sha384),
+            Sha512::OID => Ok(HashAlgorithm::Sha512),
+            _ => Err(Error::UnsupportedAlgorithm),
+        }
+    }
+
+    /// Map an IMA `hash_algo` identifier (`HASH_ALGO_*` in the kernel) to the algorithm
+    pub fn from_ima(id: u8) -> Result<Self, Error> {
+        match id {
+            2 => Ok(HashAlgorithm::Sha1),
+            4 => Ok(HashAlgorithm::Sha256),
+            5 => Ok(HashAlgorithm::Sha384),
+            6 => Ok(HashAlgorithm::Sha512),
+            _ => Err(Error::UnsupportedAlgorithm),
+        }
+    }
+
+    /// Map an OpenPGP hash algorithm identifier (RFC 4880 section 9.4) to the algorithm
+    pub fn from_openpgp(id: u8) -> Result<Self, Error> {
+        match id {
+            2 => Ok(HashAlgorithm::Sha1),
+            8 => Ok(HashAlgorithm::Sha256),
+            9 => Ok(HashAlgorithm::Sha384),
+            10 => Ok(HashAlgorithm::Sha512),
+            _ => Err(Error::UnsupportedAlgorithm),
+        }
+    }
+
+    pub fn digest_reader<R: Read>(self, reader: &mut R) -> std::io::Result<Vec<u8>> {
+        fn hash<D: Digest + Write, R: Read>(
+            mut hasher: D,
+            reader: &mut R,
+        ) -> std::io::Result<Vec<u8>> {
+            std::io::copy(reader, &mut hasher)?;
+            Ok(hasher.finalize().to_vec())
+        }
+
+        match self {
+            HashAlgorithm::Sha1 => hash(Sha1::new(), reader),
+            HashAlgorithm::Sha256 => hash(Sha256::new(), reader),
+            HashAlgorithm::Sha384 => hash(Sha384::new(), reader),
+            HashAlgorithm::Sha512 => hash(Sha512::new(), reader),
+        }
+    }
+
+    pub fn digest(self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgorithm::Sha1 => Sha1::digest(data).to_vec(),
+            HashAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+            HashAlgorithm::Sha384 => Sha384::digest(data).to_vec(),
+            HashAlgorithm::Sha512 => Sha512::digest(data).to_vec(),
+        }
+    }
+
+    fn pkcs1v15(self) -> Pkcs1v15Sign {
+        match self {
+            HashAlgorithm::Sha1 => Pkcs1v15Sign::new::<Sha1>(),
+            HashAlgorithm::Sha256 => Pkcs1v15Sign::new::<Sha256>(),
+            HashAlgorithm::Sha384 => Pkcs1v15Sign::new::<Sha384>(),
+            HashAlgorithm::Sha512 => Pkcs1v15Sign::new::<Sha512>(),
+        }
+    }
+}
+
+/// Verify a `signature` over `digest` with the public key of `cert`
+pub(crate) fn verify_signature(
+    cert: &Certificate,
+    hash: HashAlgorithm,
+    digest: &[u8],
+    signature: &[u8],
+) -> Result<(), Error> {
+    verify_spki(
+        &cert.tbs_certificate.subject_public_key_info,
+        hash,
+        digest,
+        signature,
+    )
+}
+
+/// Verify a `signature` over `digest` with an RSA (PKCS#1 v1.5), ECDSA (P-256, P-384) or DSA public key
+pub(crate) fn verify_spki(
+    spki: &SubjectPublicKeyInfoOwned,
+    hash: HashAlgorithm,
+    digest: &[u8],
+    signature: &[u8],
+) -> Result<(), Error> {
+    let key = spki.subject_public_key.raw_bytes();
+
+    match spki.algorithm.oid {
+        RSA_ENCRYPTION => {
+            let key = RsaPublicKey::from_pkcs1_der(key).map_err(|_| Error::InvalidKey)?;
+            verify_rsa_key(&key, hash, digest, signature)
+        }
+        EC_PUBLIC_KEY => {
+            let curve = spki
+                .algorithm
+                .parameters
+                .as_ref()
+                .and_then(|params| params.decode_as::<ObjectIdentifier>().ok());
+
+            match curve {
+                Some(SECP256R1) => {
+                    verify_prehash::<p256::ecdsa::VerifyingKey, p256::ecdsa::Signature>(
+                        p256::ecdsa::VerifyingKey::from_sec1_bytes(key),
+                        p256::ecdsa::Signature::from_der(signature),
+                        digest,
+                    )
+                }
+                Some(SECP384R1) => {
+                    verify_prehash::<p384::ecdsa::VerifyingKey, p384::ecdsa::Signature>(
+                        p384::ecdsa::VerifyingKey::from_sec1_bytes(key),
+                        p384::ecdsa::Signature::from_der(signature),
+                        digest,
+                    )
+                }
+                _ => Err(Error::UnsupportedAlgorithm),
+            }
+        }
+        ID_DSA => {
+            let spki = spki.to_der().map_err(|_| Error::InvalidKey)?;
+            let key = SubjectPublicKeyInfoRef::from_der(&spki).map_err(|_| Error::InvalidKey)?;
+
+            verify_prehash::<dsa::VerifyingKey, dsa::Signature>(
+                dsa::VerifyingKey::try_from(key).map_err(|_| signature::Error::new()),
+                dsa::Signature::try_from(signature),
+                digest,
+            )
+        }
+        _ => Err(Error::UnsupportedAlgorithm),
+    }
+}
+
+fn verify_prehash<K: PrehashVerifier<S>, S>(
+    key: Result<K, ecdsa::Error>,
+    signature: Result<S, ecdsa::Error>,
+    digest: &[u8],
+) -> Result<(), Error> {
+    let key = key.map_err(|_| Error::InvalidKey)?;
+    let signature = signature.map_err(|_| Error::MalformedSignature)?;
+
+    key.verify_prehash(digest, &signature)
+        .map_err(|_| Error::BadSignature)
+}
+
+/// Verify that `cert` was signed by `issuer`
+pub(crate) fn verify_certificate(cert: &Certificate, issuer: &Certificate) -> Result<(), Error> {
+    let hash = match cert.signature_algorithm.oid {
+        SHA256_WITH_RSA | ECDSA_WITH_SHA256 => HashAlgorithm::Sha256,
+        SHA384_WITH_RSA | ECDSA_WITH_SHA384 => HashAlgorithm::Sha384,
+        SHA512_WITH_RSA | ECDSA_WITH_SHA512 => HashAlgorithm::Sha512,
+        _ => return Err(Error::UnsupportedAlgorithm),
+    };
+
+    let tbs = cert
+        .tbs_certificate
+        .to_der()
+        .map_err(|_| Error::MalformedSignature)?;
+    let signature = cert.signature.as_bytes().ok_or(Error::MalformedSignature)?;
+
+    verify_signature(issuer, hash, &hash.digest(&tbs), signature)
+}
+
+fn is_ca(cert: &Certificate) -> bool {
+    matches!(
+        cert.tbs_certificate.get::<BasicConstraints>(),
+        Ok(Some((_, constraints))) if constraints.ca
+    )
+}
+
+/// Chain `leaf` to one of the `trusted` certificates through the certificates of the signature
+pub(crate) fn verify_chain(
+    leaf: &Certificate,
+    certificates: &[Certificate],
+    trusted: &[Certificate],
+) -> Result<(), Error> {
+    let mut cert = leaf;
+
+    for _ in 0..MAX_CHAIN_LENGTH {
+        if trusted.iter().any(|anchor| {
+            anchor == cert
+                || (anchor.tbs_certificate.subject == cert.tbs_certificate.issuer
+                    && verify_certificate(cert, anchor).is_ok())
+        }) {
+            return Ok(());
+        }
+
+        let Some(issuer) = certificates.iter().find(|issuer| {
+            *issuer != cert
+                && is_ca(issuer)
+                && issuer.tbs_certificate.subject == cert.tbs_certificate.issuer
+                && verify_certificate(cert, issuer).is_ok()
+        }) else {
+            break;
+        };
+        cert = issuer;
+    }
+
+    Err(Error::UnknownSigner)
+}
+
+/// Verify an RSA PKCS#1 v1.5 `signature` over `digest` with `key`
+pub(crate) fn verify_rsa_key(
+    key: &RsaPublicKey,
+    hash: HashAlgorithm,
+    digest: &[u8],
+    signature: &[u8],
+) -> Result<(), Error> {
+    key.verify(hash.pkcs1v15(), digest, signature)
+        .map_err(|_| Error::BadSignature)
+}
+
+/// Load the X.509 certificates from a DER or PEM encoded file
+pub(crate) fn load_certificates(path: &Path) -> Result<Vec<Certificate>, Error> {
+    let data = std::fs::read(path).map_err(Error::IoError)?;
+
+    if data.starts_with(b"-----BEGIN") {
+        Certificate::load_pem_chain(&data).map_err(|_| Error::InvalidKey)
+    } else {
+        Certificate::from_der(&data)
+            .map(|cert| vec![cert])
+            .map_err(|_| Error::InvalidKey)
+    }
+}
+
+/// Load all certificates from a keyring, either a single file or a directory of DER or PEM files.
+/// Files in a directory that don't hold certificates are skipped.
+pub(crate) fn load_keyring(path: &Path) -> Result<Vec<Certificate>, Error> {
+    if !path.is_dir() {
+        return load_certificates(path);
+    }
+
+    let mut certs = Vec::new();
+
+    for entry in std::fs::read_dir(path).map_err(Error::IoError)? {
+        let path = entry.map_err(Error::IoError)?.path();
+
+        if path.is_file() {
+            certs.extend(load_certificates(&path).unwrap_or_default());
+        }
+    }
+
+    Ok(certs)
+}
+
+/// The 32 bit key identifier used by IMA: the last 4 bytes of the subject key identifier,
+/// or of the SHA-1 hash of the public key when the certificate has no such extension
+pub(crate) fn key_id(cert: &Certificate) -> [u8; 4] {
+    let skid = match cert.tbs_certificate.get::<SubjectKeyIdentifier>() {
+        Ok(Some((_, skid))) => skid.0.as_bytes().to_vec(),
+        _ => Sha1::digest(
+            cert.tbs_certificate
+                .subject_public_key_info
+                .subject_public_key
+                .raw_bytes(),
+        )
+        .to_vec(),
+    };
+
+    // A subject key identifier shorter than the key ID is left padded with zeros
+    let tail = &skid[skid.len().saturating_sub(4)..];
+    let mut id = [0; 4];
+    id[4 - tail.len()..].copy_from_slice(tail);
+    id
+}
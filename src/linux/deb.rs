@@ -0,0 +1,196 @@
+use super::crypto::HashAlgorithm;
+use super::openpgp::{dearmor, load_keyring, verify_cleartext, verify_packets};
+use crate::{hex, Error, SignerIdentity};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+const AR_MAGIC: &[u8] = b"!<arch>\n";
+const AR_HEADER_SIZE: usize = 60;
+
+const ORIGIN_SIGNATURE: &str = "_gpgorigin"; // debsig-verify
+const BUILDER_SIGNATURE: &str = "_gpgbuilder"; // dpkg-sig
+
+/// A member of an `ar` archive
+struct Member {
+    name: String,
+    offset: u64,
+    size: u64,
+}
+
+/// List the members of an `ar` archive
+fn read_members(file: &mut File) -> Result<Vec<Member>, Error> {
+    let mut magic = [0; AR_MAGIC.len()];
+    file.read_exact(&mut magic)
+        .map_err(|_| Error::MalformedSignature)?;
+    if magic != AR_MAGIC {
+        return Err(Error::MalformedSignature);
+    }
+
+    let length = file.metadata().map_err(Error::IoError)?.len();
+    let mut offset = AR_MAGIC.len() as u64;
+    let mut members = Vec::new();
+
+    while offset < length {
+        // name[16], mtime[12], uid[6], gid[6], mode[8], size[10], "`\n"
+        let mut header = [0; AR_HEADER_SIZE];
+        file.seek(SeekFrom::Start(offset)).map_err(Error::IoError)?;
+        file.read_exact(&mut header)
+            .map_err(|_| Error::MalformedSignature)?;
+
+        if &header[58..] != b"`\n" {
+            return Err(Error::MalformedSignature);
+        }
+
+        let field = |range: std::ops::Range<usize>| {
+            std::str::from_utf8(&header[range])
+                .map(str::trim_end)
+                .map_err(|_| Error::MalformedSignature)
+        };
+
+        // GNU ar terminates names with a slash
+        let name = field(0..16)?;
+        let name = name.strip_suffix('/').unwrap_or(name).to_string();
+        let size = field(48..58)?
+            .parse::<u64>()
+            .map_err(|_| Error::MalformedSignature)?;
+
+        offset += AR_HEADER_SIZE as u64;
+        if offset.saturating_add(size) > length {
+            return Err(Error::MalformedSignature);
+        }
+
+        members.push(Member { name, offset, size });
+        offset += size + size % 2; // Members are aligned to 2 bytes
+    }
+
+    Ok(members)
+}
+
+fn read_member(file: &mut File, member: &Member) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::new();
+    file.seek(SeekFrom::Start(member.offset))
+        .map_err(Error::IoError)?;
+    file.take(member.size)
+        .read_to_end(&mut data)
+        .map_err(Error::IoError)?;
+    Ok(data)
+}
+
+/// Check a dpkg-sig manifest, which lists the MD5 and SHA-1 digests and size of every member
+fn verify_manifest(manifest: &str, file: &mut File, members: &[&Member]) -> Result<(), Error> {
+    // Version, Signer, Date, Role, then "Files:" followed by one indented line per member
+    let files = manifest
+        .lines()
+        .skip_while(|line| !line.starts_with("Files:"))
+        .skip(1)
+        .take_while(|line| line.starts_with([' ', '\t']))
+        .map(
+            |line| match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [_md5, sha1, size, name] => Ok((sha1, size, name)),
+                _ => Err(Error::MalformedSignature),
+            },
+        )
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    // Every member must be listed, so none can be added or replaced
+    for member in members {
+        let Some((sha1, size, _)) = files.iter().find(|file| file.2 == member.name) else {
+            return Err(Error::BadSignature);
+        };
+
+        file.seek(SeekFrom::Start(member.offset))
+            .map_err(Error::IoError)?;
+        let digest = HashAlgorithm::Sha1
+            .digest_reader(&mut file.take(member.size))
+            .map_err(Error::IoError)?;
+        let digest = hex(&digest);
+
+        if *size != member.size.to_string() || !sha1.eq_ignore_ascii_case(&digest) {
+            return Err(Error::BadSignature);
+        }
+    }
+
+    Ok(())
+}
+
+/// Verify the Debian package at `path` with its `_gpgorigin` (debsig) and `_gpgbuilder` (dpkg-sig)
+/// signatures, made by one of the keys in `keyring`. The origin signer is reported when both are present.
+pub(crate) fn verify_package(path: &Path, keyring: &Path) -> Result<SignerIdentity, Error> {
+    let mut file = File::open(path).map_err(Error::IoError)?;
+    let members = read_members(&mut file)?;
+
+    let origin = members
+        .iter()
+        .find(|member| member.name == ORIGIN_SIGNATURE);
+    let builder = members
+        .iter()
+        .find(|member| member.name == BUILDER_SIGNATURE);
+    let contents: Vec<&Member> = members
+        .iter()
+        .filter(|member| !member.name.starts_with("_gpg"))
+        .collect();
+
+    if origin.is_none() && builder.is_none() {
+        return Err(Error::Unsigned);
+    }
+
+    let certs = load_keyring(keyring)?;
+    let mut identity = None;
+
+    if let Some(builder) = builder {
+        let manifest = read_member(&mut file, builder)?;
+        let manifest = String::from_utf8(manifest).map_err(|_| Error::MalformedSignature)?;
+        let (signer, manifest) = verify_cleartext(&manifest, &certs)?;
+
+        verify_manifest(&manifest, &mut file, &contents)?;
+        identity = Some(signer);
+    }
+
+    if let Some(origin) = origin {
+        // The signature covers the concatenated contents of the other members
+        let signature = read_member(&mut file, origin)?;
+        let signature = dearmor(&signature)?;
+
+        identity = Some(verify_packets(&signature, &certs, || {
+            let mut data: Box<dyn Read> = Box::new(std::io::empty());
+
+            for member in &contents {
+                let mut file = File::open(path).map_err(Error::IoError)?;
+                file.seek(SeekFrom::Start(member.offset))
+                    .map_err(Error::IoError)?;
+                data = Box::new(data.chain(file.take(member.size)));
+            }
+
+            Ok(data)
+        })?);
+    }
+
+    identity.ok_or(Error::Unsigned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{testdata, TempFile};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_tampered_member() {
+        for package in ["hello_1.0_all.deb", "hello-builder_1.0_all.deb"] {
+            let mut data = std::fs::read(testdata(package)).unwrap();
+            let mut file = File::open(testdata(package)).unwrap();
+            let members = read_members(&mut file).unwrap();
+            assert_eq!(members[0].name, "debian-binary");
+
+            // "2.0\n" becomes "2.1\n"
+            let offset = usize::try_from(members[0].offset).unwrap();
+            data[offset + 2] = b'1';
+
+            let tampered = TempFile::new(package, data);
+            let result = verify_package(&tampered, &testdata("keyring.asc"));
+
+            assert!(matches!(result, Err(Error::BadSignature)));
+        }
+    }
+}
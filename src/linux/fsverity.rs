@@ -0,0 +1,211 @@
+use crate::Error;
+use sha2::{Digest, Sha256, Sha512};
+use std::io::Read;
+use std::path::Path;
+
+const BLOCK_SIZE: usize = 4096;
+const LOG_BLOCK_SIZE: u8 = 12;
+const MAX_SALT_SIZE: usize = 32;
+const MAX_DIGEST_SIZE: usize = 64;
+
+/// The hash algorithms supported by fs-verity (`FS_VERITY_HASH_ALG_*`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FsVerityAlgorithm {
+    Sha256 = 1,
+    Sha512 = 2,
+}
+
+///
+/// The fs-verity digest of a file: the hash of its `fsverity_descriptor`,
+/// which holds the root of the Merkle tree over the file contents in 4K blocks
+///
+/// # Fields
+///
+/// `algorithm`: the hash algorithm of the Merkle tree and the digest
+///
+/// `digest`: the raw file digest, as printed by `fsverity digest`
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct FsVerityDigest {
+    pub algorithm: FsVerityAlgorithm,
+    pub digest: Vec<u8>,
+}
+
+impl FsVerityDigest {
+    /// Compute the fs-verity digest of the file at `path`, with an optional `salt` of up to 32 bytes
+    pub fn for_file<P: AsRef<Path>>(
+        path: P,
+        algorithm: FsVerityAlgorithm,
+        salt: &[u8],
+    ) -> Result<Self, Error> {
+        let mut file = std::fs::File::open(path).map_err(Error::IoError)?;
+        Self::for_reader(&mut file, algorithm, salt)
+    }
+
+    /// Compute the fs-verity digest of the contents of `reader`
+    pub fn for_reader<R: Read>(
+        reader: &mut R,
+        algorithm: FsVerityAlgorithm,
+        salt: &[u8],
+    ) -> Result<Self, Error> {
+        if salt.len() > MAX_SALT_SIZE {
+            return Err(Error::UnsupportedAlgorithm); // The descriptor can't hold a longer salt
+        }
+
+        let digest = match algorithm {
+            FsVerityAlgorithm::Sha256 => file_digest::<Sha256, R>(reader, algorithm, salt, 64),
+            FsVerityAlgorithm::Sha512 => file_digest::<Sha512, R>(reader, algorithm, salt, 128),
+        }
+        .map_err(Error::IoError)?;
+
+        Ok(FsVerityDigest { algorithm, digest })
+    }
+
+    /// The `fsverity_formatted_digest` covered by fs-verity signatures
+    #[must_use]
+    pub fn formatted(&self) -> Vec<u8> {
+        let mut formatted = b"FSVerity".to_vec();
+        formatted.extend_from_slice(&(self.algorithm as u16).to_le_bytes());
+        #[allow(clippy::cast_possible_truncation)]
+        formatted.extend_from_slice(&(self.digest.len() as u16).to_le_bytes());
+        formatted.extend_from_slice(&self.digest);
+        formatted
+    }
+}
+
+impl std::fmt::Display for FsVerityDigest {
+    /// Formats the digest like `fsverity digest`, e.g. "sha256:3d24..."
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.algorithm {
+            FsVerityAlgorithm::Sha256 => write!(f, "sha256:")?,
+            FsVerityAlgorithm::Sha512 => write!(f, "sha512:")?,
+        }
+
+        self.digest
+            .iter()
+            .try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+/// Fill `block` from `reader`, returning the number of bytes read, short only at the end of the data
+fn read_block<R: Read>(reader: &mut R, block: &mut [u8]) -> std::io::Result<usize> {
+    let mut read = 0;
+
+    while read < block.len() {
+        match reader.read(&mut block[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(read)
+}
+
+/// Hash a zero padded block, prefixed with the salt padded to the hash block size
+fn hash_block<D: Digest>(salt: &[u8], block: &[u8]) -> Vec<u8> {
+    let mut hasher = D::new();
+    hasher.update(salt);
+    hasher.update(block);
+    hasher.update(&[0; BLOCK_SIZE][block.len()..]);
+    hasher.finalize().to_vec()
+}
+
+fn file_digest<D: Digest, R: Read>(
+    reader: &mut R,
+    algorithm: FsVerityAlgorithm,
+    salt: &[u8],
+    hash_block_size: usize,
+) -> std::io::Result<Vec<u8>> {
+    let mut padded_salt = salt.to_vec();
+    if !salt.is_empty() {
+        padded_salt.resize(salt.len().div_ceil(hash_block_size) * hash_block_size, 0);
+    }
+
+    // The lowest level of the tree holds the hashes of the data blocks
+    let mut data_size = 0_u64;
+    let mut level = Vec::new();
+    let mut block = vec![0; BLOCK_SIZE];
+
+    loop {
+        let read = read_block(reader, &mut block)?;
+        if read == 0 {
+            break;
+        }
+
+        data_size += read as u64;
+        level.extend(hash_block::<D>(&padded_salt, &block[..read]));
+
+        if read < BLOCK_SIZE {
+            break;
+        }
+    }
+
+    // Each level above hashes the blocks of the one below, until a single root hash remains
+    let digest_size = <D as Digest>::output_size();
+    let root_hash = if data_size == 0 {
+        vec![0; digest_size]
+    } else {
+        while level.len() > digest_size {
+            level = level
+                .chunks(BLOCK_SIZE)
+                .flat_map(|block| hash_block::<D>(&padded_salt, block))
+                .collect();
+        }
+        level
+    };
+
+    // struct fsverity_descriptor
+    let mut descriptor = Vec::with_capacity(256);
+    descriptor.push(1); // version
+    descriptor.push(algorithm as u8);
+    descriptor.push(LOG_BLOCK_SIZE);
+    #[allow(clippy::cast_possible_truncation)]
+    descriptor.push(salt.len() as u8);
+    descriptor.extend_from_slice(&[0; 4]); // __reserved_0x04
+    descriptor.extend_from_slice(&data_size.to_le_bytes());
+    descriptor.extend_from_slice(&root_hash);
+    descriptor.resize(16 + MAX_DIGEST_SIZE, 0);
+    descriptor.extend_from_slice(salt);
+    descriptor.resize(256, 0); // The rest of the salt and __reserved
+
+    Ok(D::digest(&descriptor).to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(data: &[u8], algorithm: FsVerityAlgorithm, salt: &[u8]) -> String {
+        FsVerityDigest::for_reader(&mut &data[..], algorithm, salt)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_fsverity_digest() {
+        assert_eq!(
+            digest(&[], FsVerityAlgorithm::Sha256, &[]),
+            "sha256:3d248ca542a24fc62d1c43b916eae5016878e2533c88238480b26128a1f1af95"
+        );
+
+        assert_eq!(
+            digest(&[b'x'; BLOCK_SIZE], FsVerityAlgorithm::Sha256, &[]),
+            "sha256:3f128b8d5a052638172857f47f0110dc2fc2c234dc0c712c08a3bc6f6c540483"
+        );
+
+        // Spans more than one level of the tree
+        let data: Vec<u8> = (0..600_000_u32).map(|i| (i * 31 % 251) as u8).collect();
+
+        assert_eq!(
+            digest(&data, FsVerityAlgorithm::Sha256, &[]),
+            "sha256:b872f3534d9410c98a916b055059912f8895ea5f9d65419db49788e66eceb76c"
+        );
+
+        assert_eq!(
+            digest(&data, FsVerityAlgorithm::Sha512, b"salty"),
+            "sha512:e896cd582761ad2f4ea78454d083b2cf7b4d8f7b390bd2943e6e533696d2a90d5ee4c44d3cbb6368ea5d5242595214cad9598b568e25e3faa066c8319afe7a89"
+        );
+    }
+}
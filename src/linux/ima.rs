@@ -0,0 +1,177 @@
+use super::crypto::{key_id, load_keyring, verify_signature, HashAlgorithm};
+use crate::Error;
+use std::convert::{TryFrom, TryInto};
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use x509_cert::Certificate;
+
+const XATTR_NAME_IMA: &[u8] = b"security.ima\0";
+const EVM_IMA_XATTR_DIGSIG: u8 = 0x03;
+const SIGNATURE_V2: u8 = 2;
+const SIGNATURE_V2_HDR_SIZE: usize = 9; // sizeof(struct signature_v2_hdr)
+
+/// The fields of an IMA signature (`struct signature_v2_hdr`)
+struct ImaSignature<'a> {
+    hash: HashAlgorithm,
+    key_id: [u8; 4],
+    signature: &'a [u8],
+}
+
+impl<'a> ImaSignature<'a> {
+    fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        // struct signature_v2_hdr { u8 type, version, hash_algo; __be32 keyid; __be16 sig_size; u8 sig[]; }
+        match data.first() {
+            Some(&EVM_IMA_XATTR_DIGSIG) => {}
+            _ => return Err(Error::Unsigned), // Only a file hash, nothing was signed
+        }
+
+        if data.len() < SIGNATURE_V2_HDR_SIZE {
+            return Err(Error::MalformedSignature);
+        }
+
+        if data[1] != SIGNATURE_V2 {
+            return Err(Error::UnsupportedAlgorithm);
+        }
+
+        let sig_size = u16::from_be_bytes(data[7..9].try_into().unwrap()) as usize;
+        let signature = data[SIGNATURE_V2_HDR_SIZE..]
+            .get(..sig_size)
+            .ok_or(Error::MalformedSignature)?;
+
+        Ok(ImaSignature {
+            hash: HashAlgorithm::from_ima(data[2])?,
+            key_id: data[3..7].try_into().unwrap(),
+            signature,
+        })
+    }
+
+    /// Verify the signature over the file `digest` with the matching key from `keys`
+    fn verify(&self, digest: &[u8], keys: &[Certificate]) -> Result<Certificate, Error> {
+        // Key IDs are only 32 bits, so several keys may share the one of the signature
+        let mut result = Err(Error::UnknownSigner);
+
+        for cert in keys.iter().filter(|cert| key_id(cert) == self.key_id) {
+            match verify_signature(cert, self.hash, digest, self.signature) {
+                Ok(()) => return Ok(cert.clone()),
+                Err(err) => result = Err(err),
+            }
+        }
+
+        result
+    }
+}
+
+/// Read the `security.ima` extended attribute of an open file
+fn read_ima_xattr(file: &File) -> Result<Vec<u8>, Error> {
+    let get = |buf: &mut [u8]| unsafe {
+        libc::fgetxattr(
+            file.as_raw_fd(),
+            XATTR_NAME_IMA.as_ptr().cast(),
+            buf.as_mut_ptr().cast(),
+            buf.len(),
+        )
+    };
+
+    let mut data = Vec::new();
+    loop {
+        let size = get(&mut data);
+
+        let Ok(size) = usize::try_from(size) else {
+            let err = std::io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::ENODATA | libc::EOPNOTSUPP) => return Err(Error::Unsigned),
+                Some(libc::ERANGE) => data.clear(), // The attribute grew, query the size again
+                _ => return Err(Error::IoError(err)),
+            }
+            continue;
+        };
+
+        if size <= data.len() {
+            data.truncate(size);
+            return Ok(data);
+        }
+
+        data.resize(size, 0);
+    }
+}
+
+/// Verify the IMA signature of the file at `path` against the keys in `keyring`
+pub(crate) fn verify_file(path: &Path, keyring: &Path) -> Result<Certificate, Error> {
+    verify_open_file(&File::open(path).map_err(Error::IoError)?, keyring)
+}
+
+/// Reads a file from its start without moving its offset, which may be shared with the caller
+struct PositionalReader<'a> {
+    file: &'a File,
+    offset: u64,
+}
+
+impl Read for PositionalReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.file.read_at(buf, self.offset)?;
+        self.offset += len as u64;
+        Ok(len)
+    }
+}
+
+/// Verify the IMA signature of an open file against the keys in `keyring`.
+/// The whole file is hashed, from its start.
+pub(crate) fn verify_open_file(file: &File, keyring: &Path) -> Result<Certificate, Error> {
+    let xattr = read_ima_xattr(file)?;
+    let signature = ImaSignature::parse(&xattr)?;
+
+    let digest = signature
+        .hash
+        .digest_reader(&mut PositionalReader { file, offset: 0 })
+        .map_err(Error::IoError)?;
+
+    signature.verify(&digest, &load_keyring(keyring)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODULE: &[u8] = include_bytes!("testdata/module.ko");
+    const XATTR: &[u8] = include_bytes!("testdata/module.ko.ima");
+    const KEY: &[u8] = include_bytes!("testdata/signing_key.x509");
+    const COLLIDING_KEY: &[u8] = include_bytes!("testdata/colliding_key.x509");
+
+    #[test]
+    fn test_ima_signature() {
+        use der::Decode;
+
+        let keys = vec![Certificate::from_der(KEY).unwrap()];
+        let signature = ImaSignature::parse(XATTR).unwrap();
+        assert_eq!(signature.hash, HashAlgorithm::Sha256);
+        assert_eq!(signature.key_id, [0xe6, 0xda, 0xa6, 0x15]);
+
+        let digest = signature.hash.digest(MODULE);
+        assert!(signature.verify(&digest, &keys).is_ok());
+
+        let digest = signature.hash.digest(&MODULE[1..]);
+        assert!(matches!(
+            signature.verify(&digest, &keys),
+            Err(Error::BadSignature)
+        ));
+
+        // Another key with the same key ID is listed first
+        let keys = vec![
+            Certificate::from_der(COLLIDING_KEY).unwrap(),
+            keys[0].clone(),
+        ];
+        assert_eq!(key_id(&keys[0]), signature.key_id);
+        let digest = signature.hash.digest(MODULE);
+        assert!(signature.verify(&digest, &keys[..1]).is_err());
+        assert!(signature.verify(&digest, &keys).is_ok());
+
+        // An ima-ng file hash carries no signature
+        assert!(matches!(
+            ImaSignature::parse(&[0x04, 4]),
+            Err(Error::Unsigned)
+        ));
+    }
+}
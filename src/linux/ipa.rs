@@ -0,0 +1,279 @@
+use super::crypto::{load_keyring, HashAlgorithm};
+use super::macho::{self, BundleFiles};
+use super::plist::{self, Value};
+use super::zip::Archive;
+use crate::Error;
+use std::collections::BTreeSet;
+use std::path::Path;
+use x509_cert::Certificate;
+
+/// Files that the default resource rules leave out of the seal, besides the main executable
+/// and the signature directory
+const OMITTED: [&str; 2] = ["Info.plist", "PkgInfo"];
+
+/// A verified bundle, with the paths of its files relative to the bundle
+pub(crate) struct BundleSignature {
+    pub cert: Certificate,
+    pub cdhashes: Vec<Vec<u8>>,
+    /// Files that aren't sealed, e.g. added after signing
+    pub unsealed: Vec<String>,
+    /// Nested code (frameworks, plug-ins, libraries) that failed verification, with the reason
+    pub broken: Vec<(String, Error)>,
+}
+
+fn read(archive: &Archive<'_>, name: &str) -> Result<Option<Vec<u8>>, Error> {
+    archive
+        .find(name)
+        .map(|entry| archive.read(entry))
+        .transpose()
+}
+
+/// Whether the default resource rules leave `path` out of the seal
+fn is_omitted(path: &str, executable: &str) -> bool {
+    path == executable
+        || OMITTED.contains(&path)
+        || path.starts_with("_CodeSignature/")
+        || path.rsplit('/').next() == Some(".DS_Store")
+        || (path.ends_with("/locversion.plist") && path.contains(".lproj/"))
+}
+
+/// Check a sealed resource: its SHA-256 (`hash2`) or SHA-1 hash, or the target of a symlink.
+/// Optional resources (localizations) may be missing.
+fn verify_resource(content: Option<&[u8]>, seal: &Value) -> Result<(), Error> {
+    if let Value::Data(expected) = seal {
+        return verify_hash(content, HashAlgorithm::Sha1, expected);
+    }
+
+    let optional = seal.get("optional").and_then(Value::as_bool) == Some(true);
+    let Some(content) = content else {
+        return if optional {
+            Ok(())
+        } else {
+            Err(Error::BadSignature)
+        };
+    };
+
+    if let Some(target) = seal.get("symlink").and_then(Value::as_str) {
+        return if content == target.as_bytes() {
+            Ok(())
+        } else {
+            Err(Error::BadSignature)
+        };
+    }
+
+    match (
+        seal.get("hash2").and_then(Value::as_data),
+        seal.get("hash").and_then(Value::as_data),
+    ) {
+        (Some(expected), _) => verify_hash(Some(content), HashAlgorithm::Sha256, expected),
+        (None, Some(expected)) => verify_hash(Some(content), HashAlgorithm::Sha1, expected),
+        (None, None) => Err(Error::MalformedSignature),
+    }
+}
+
+fn verify_hash(content: Option<&[u8]>, hash: HashAlgorithm, expected: &[u8]) -> Result<(), Error> {
+    match content {
+        Some(content) if hash.digest(content) == expected => Ok(()),
+        _ => Err(Error::BadSignature),
+    }
+}
+
+/// Verify nested code sealed by its code directory hash: a bundle, or a single Mach-O file
+fn verify_nested(
+    archive: &Archive<'_>,
+    name: &str,
+    cdhash: &[u8],
+    trusted: &[Certificate],
+) -> Result<BundleSignature, Error> {
+    let prefix = format!("{name}/");
+
+    let signature = if archive
+        .entries
+        .iter()
+        .any(|entry| entry.name.starts_with(&prefix))
+    {
+        verify_bundle(archive, &prefix, trusted)?
+    } else {
+        let data = read(archive, name)?.ok_or(Error::BadSignature)?;
+        let signature = macho::verify(&data, BundleFiles::default(), trusted)?;
+
+        BundleSignature {
+            cert: signature.cert,
+            cdhashes: signature.cdhashes,
+            unsealed: Vec::new(),
+            broken: Vec::new(),
+        }
+    };
+
+    if !signature.cdhashes.iter().any(|hash| hash == cdhash) {
+        return Err(Error::BadSignature);
+    }
+
+    Ok(signature)
+}
+
+/// Verify the bundle whose files are the archive entries starting with `prefix`: the signature
+/// of its main executable, which binds its Info.plist and resource seal (`CodeResources`),
+/// every sealed resource, and the nested code recorded in the seal, recursively.
+/// A resource that doesn't match its seal fails the bundle, nested code that fails
+/// verification is reported.
+fn verify_bundle(
+    archive: &Archive<'_>,
+    prefix: &str,
+    trusted: &[Certificate],
+) -> Result<BundleSignature, Error> {
+    let read = |path: &str| read(archive, &format!("{prefix}{path}"));
+
+    let info_plist = read("Info.plist")?.ok_or(Error::MalformedSignature)?;
+    let executable = plist::parse(&info_plist)?
+        .get("CFBundleExecutable")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or(Error::MalformedSignature)?;
+    let code_resources = read("_CodeSignature/CodeResources")?.ok_or(Error::Unsigned)?;
+    let main = read(&executable)?.ok_or(Error::MalformedSignature)?;
+
+    let files = BundleFiles {
+        info_plist: Some(&info_plist),
+        code_resources: Some(&code_resources),
+    };
+    let signature = macho::verify(&main, files, trusted)?;
+
+    // Version 1 seals (`files` alone) don't seal nested code
+    let resources = plist::parse(&code_resources)?;
+    let seals = resources
+        .get("files2")
+        .and_then(Value::as_dict)
+        .ok_or(Error::UnsupportedAlgorithm)?;
+
+    let mut unsealed = Vec::new();
+    let mut broken = Vec::new();
+    let mut nested = Vec::new();
+
+    for (path, seal) in seals {
+        let Some(cdhash) = seal.get("cdhash").and_then(Value::as_data) else {
+            verify_resource(read(path)?.as_deref(), seal)?;
+            continue;
+        };
+
+        nested.push(format!("{path}/"));
+        match verify_nested(archive, &format!("{prefix}{path}"), cdhash, trusted) {
+            Ok(code) => {
+                unsealed.extend(code.unsealed.iter().map(|file| format!("{path}/{file}")));
+                broken.extend(
+                    code.broken
+                        .into_iter()
+                        .map(|(code, error)| (format!("{path}/{code}"), error)),
+                );
+            }
+            Err(error) => broken.push((path.clone(), error)),
+        }
+    }
+
+    let sealed: BTreeSet<&str> = seals.iter().map(|(path, _)| path.as_str()).collect();
+    unsealed.extend(
+        archive
+            .entries
+            .iter()
+            .filter_map(|entry| entry.name.strip_prefix(prefix))
+            .filter(|path| !path.is_empty() && !path.ends_with('/'))
+            .filter(|path| !sealed.contains(path) && !is_omitted(path, &executable))
+            .filter(|path| !nested.iter().any(|code| path.starts_with(code.as_str())))
+            .map(str::to_string),
+    );
+
+    Ok(BundleSignature {
+        cert: signature.cert,
+        cdhashes: signature.cdhashes,
+        unsealed,
+        broken,
+    })
+}
+
+/// The directory of the application bundle, the single `Payload/<name>.app`
+fn app_bundle(archive: &Archive<'_>) -> Result<String, Error> {
+    let bundles: BTreeSet<&str> = archive
+        .entries
+        .iter()
+        .filter_map(|entry| entry.name.strip_prefix("Payload/"))
+        .filter_map(|path| path.split_once('/'))
+        .map(|(bundle, _)| bundle)
+        .filter(|bundle| {
+            Path::new(bundle)
+                .extension()
+                .is_some_and(|ext| ext == "app")
+        })
+        .collect();
+
+    match bundles.into_iter().collect::<Vec<_>>()[..] {
+        [bundle] => Ok(format!("Payload/{bundle}/")),
+        _ => Err(Error::MalformedSignature),
+    }
+}
+
+/// Verify the iOS application archive (`.ipa`) at `path` against the certificates in `anchors`
+pub(crate) fn verify_ipa(path: &Path, anchors: &Path) -> Result<BundleSignature, Error> {
+    let data = std::fs::read(path).map_err(Error::IoError)?;
+
+    verify_ipa_archive(&Archive::parse(&data)?, &load_keyring(anchors)?)
+}
+
+/// Verify the application bundle of an iOS application archive against the `trusted`
+/// certificates
+pub(crate) fn verify_ipa_archive(
+    archive: &Archive<'_>,
+    trusted: &[Certificate],
+) -> Result<BundleSignature, Error> {
+    verify_bundle(archive, &app_bundle(archive)?, trusted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::testdata;
+
+    fn verify(name: &str) -> Result<BundleSignature, Error> {
+        verify_ipa(&testdata(name), &testdata("ipa_root.pem"))
+    }
+
+    #[test]
+    fn test_nested_code() {
+        let bundle = verify("app.ipa").unwrap();
+        assert!(bundle.unsealed.is_empty());
+        assert!(bundle.broken.is_empty());
+
+        // The framework executable was modified and a library was added
+        let bundle = verify("app-nested.ipa").unwrap();
+        assert_eq!(bundle.unsealed, vec!["Frameworks/Inject.dylib".to_string()]);
+        assert!(matches!(
+            bundle.broken[..],
+            [(ref code, Error::BadSignature)] if code == "Frameworks/Example.framework"
+        ));
+    }
+
+    #[test]
+    fn test_resources() {
+        assert!(matches!(
+            verify("app-resource.ipa"),
+            Err(Error::BadSignature)
+        ));
+
+        let seal = Value::Dict(vec![
+            (
+                "hash2".to_string(),
+                Value::Data(HashAlgorithm::Sha256.digest(b"data")),
+            ),
+            ("optional".to_string(), Value::Bool(true)),
+        ]);
+        assert!(verify_resource(Some(b"data"), &seal).is_ok());
+        assert!(verify_resource(None, &seal).is_ok());
+        assert!(verify_resource(Some(b"date"), &seal).is_err());
+
+        let link = Value::Dict(vec![(
+            "symlink".to_string(),
+            Value::String("Versions/Current".to_string()),
+        )]);
+        assert!(verify_resource(Some(b"Versions/Current"), &link).is_ok());
+        assert!(verify_resource(None, &link).is_err());
+    }
+}
@@ -0,0 +1,316 @@
+use super::crypto::HashAlgorithm;
+use super::pkcs7;
+use super::zip::Archive;
+use crate::Error;
+use base64::Engine;
+use x509_cert::Certificate;
+
+const MANIFEST: &str = "META-INF/MANIFEST.MF";
+const SIGNATURE_BLOCK_EXTENSIONS: &[&str] = &["RSA", "EC", "DSA"];
+
+/// A section of a manifest or signature file: its bytes, up to and including the blank line
+/// ending it, and its attributes
+struct Section<'a> {
+    raw: &'a [u8],
+    attributes: Vec<(String, String)>,
+}
+
+impl Section<'_> {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The digests in attributes named `<algorithm><suffix>`, e.g. "SHA-256-Digest".
+    /// Digests with unsupported algorithms (MD5) are skipped.
+    fn digests(&self, suffix: &str) -> Vec<(HashAlgorithm, Vec<u8>)> {
+        self.attributes
+            .iter()
+            .filter_map(|(key, value)| {
+                let algorithm = key.get(..key.len().checked_sub(suffix.len())?)?;
+                if !key[algorithm.len()..].eq_ignore_ascii_case(suffix) {
+                    return None;
+                }
+
+                let hash = match algorithm.to_ascii_uppercase().as_str() {
+                    "SHA1" | "SHA-1" => HashAlgorithm::Sha1,
+                    "SHA-256" => HashAlgorithm::Sha256,
+                    "SHA-384" => HashAlgorithm::Sha384,
+                    "SHA-512" => HashAlgorithm::Sha512,
+                    _ => return None,
+                };
+
+                let digest = base64::engine::general_purpose::STANDARD
+                    .decode(value)
+                    .ok()?;
+                Some((hash, digest))
+            })
+            .collect()
+    }
+}
+
+/// Split a manifest or signature file into its main section and named sections.
+/// Lines may end with CRLF, LF or CR, and continue on the next line starting with a space.
+fn sections(data: &[u8]) -> Vec<Section<'_>> {
+    let mut sections = Vec::new();
+    let mut attributes: Vec<(String, String)> = Vec::new();
+    let (mut start, mut position) = (0, 0);
+
+    while position < data.len() {
+        let end = data[position..]
+            .iter()
+            .position(|&c| c == b'\r' || c == b'\n')
+            .map_or(data.len(), |i| position + i);
+
+        let mut next = end;
+        if data.get(next) == Some(&b'\r') {
+            next += 1;
+        }
+        if data.get(next) == Some(&b'\n') {
+            next += 1;
+        }
+
+        let line = String::from_utf8_lossy(&data[position..end]);
+
+        if line.is_empty() {
+            if !attributes.is_empty() {
+                sections.push(Section {
+                    raw: &data[start..next],
+                    attributes: std::mem::take(&mut attributes),
+                });
+            }
+            start = next;
+        } else if let Some(continuation) = line.strip_prefix(' ') {
+            if let Some((_, value)) = attributes.last_mut() {
+                value.push_str(continuation);
+            }
+        } else if let Some((key, value)) = line.split_once(": ") {
+            attributes.push((key.to_string(), value.to_string()));
+        }
+
+        position = next;
+    }
+
+    if !attributes.is_empty() {
+        sections.push(Section {
+            raw: &data[start..],
+            attributes,
+        });
+    }
+
+    sections
+}
+
+/// Whether `name` is part of the signature itself rather than signed content
+fn is_signature_related(name: &str) -> bool {
+    let Some(file) = name.strip_prefix("META-INF/") else {
+        return false;
+    };
+
+    let upper = file.to_ascii_uppercase();
+    !file.contains('/')
+        && (upper == "MANIFEST.MF"
+            || upper.starts_with("SIG-")
+            || [&["SF"][..], SIGNATURE_BLOCK_EXTENSIONS]
+                .concat()
+                .iter()
+                .any(|extension| upper.ends_with(&format!(".{extension}"))))
+}
+
+/// Check the digests of a signature file over the manifest, returning the names of the entries it covers
+fn verify_signature_file(
+    signature_data: &[u8],
+    manifest: &[u8],
+    manifest_sections: &[Section<'_>],
+) -> Result<Vec<String>, Error> {
+    let signature_sections = sections(signature_data);
+    let Some((main, named)) = signature_sections.split_first() else {
+        return Err(Error::MalformedSignature);
+    };
+
+    // A digest of the whole manifest covers every entry, otherwise each section is checked
+    let whole_manifest = main.digests("-Digest-Manifest");
+    if !whole_manifest.is_empty()
+        && whole_manifest
+            .iter()
+            .all(|(hash, digest)| hash.digest(manifest) == *digest)
+    {
+        return Ok(manifest_sections
+            .iter()
+            .skip(1)
+            .filter_map(|section| section.get("Name"))
+            .map(str::to_string)
+            .collect());
+    }
+
+    let main_attributes = main.digests("-Digest-Manifest-Main-Attributes");
+    if let Some(manifest_main) = manifest_sections.first() {
+        if main_attributes
+            .iter()
+            .any(|(hash, digest)| hash.digest(manifest_main.raw) != *digest)
+        {
+            return Err(Error::BadSignature);
+        }
+    }
+
+    let mut covered = Vec::new();
+
+    for section in named {
+        let name = section.get("Name").ok_or(Error::MalformedSignature)?;
+        let manifest_section = manifest_sections
+            .iter()
+            .skip(1)
+            .find(|section| section.get("Name") == Some(name))
+            .ok_or(Error::BadSignature)?;
+
+        let digests = section.digests("-Digest");
+        if digests.is_empty()
+            || digests
+                .iter()
+                .any(|(hash, digest)| hash.digest(manifest_section.raw) != *digest)
+        {
+            return Err(Error::BadSignature);
+        }
+
+        covered.push(name.to_string());
+    }
+
+    Ok(covered)
+}
+
+/// The result of verifying a signed JAR
+pub(crate) struct JarSignature {
+    pub cert: Certificate,
+    pub unsigned_entries: Vec<String>, // Entries not covered by every signer
+    pub apk_signature_schemes: Vec<u32>, // APK signature schemes listed in `X-Android-APK-Signed`
+}
+
+/// Verify the v1 (JAR) signatures of a ZIP archive: each signature block over its signature file,
+/// the signature file digests over the manifest and the manifest digests over the entries.
+/// Signers are self-asserted, the certificate of the first one is returned.
+pub(crate) fn verify_archive(archive: &Archive<'_>) -> Result<JarSignature, Error> {
+    let manifest = archive.find(MANIFEST).ok_or(Error::Unsigned)?;
+    let manifest = archive.read(manifest)?;
+    let manifest_sections = sections(&manifest);
+
+    let signature_files: Vec<&str> = archive
+        .entries
+        .iter()
+        .map(|entry| entry.name.as_str())
+        .filter(|name| {
+            name.strip_prefix("META-INF/").is_some_and(|file| {
+                !file.contains('/') && file.to_ascii_uppercase().ends_with(".SF")
+            })
+        })
+        .collect();
+
+    let mut signer_certs = Vec::new();
+    let mut covered_by_all: Option<Vec<String>> = None;
+    let mut apk_signature_schemes = Vec::new();
+
+    for signature_file in signature_files {
+        let base = &signature_file[..signature_file.len() - 3];
+        let block = SIGNATURE_BLOCK_EXTENSIONS
+            .iter()
+            .find_map(|extension| archive.find(&format!("{base}.{extension}")))
+            .ok_or(Error::MalformedSignature)?;
+
+        let signature_data = archive.read(archive.find(signature_file).unwrap())?;
+        let block = archive.read(block)?;
+        signer_certs.push(pkcs7::verify_detached(
+            &block,
+            &signature_data,
+            &pkcs7::certificates(&block)?,
+        )?);
+
+        let covered = verify_signature_file(&signature_data, &manifest, &manifest_sections)?;
+
+        // apksigner lists the other schemes an APK is signed with, e.g. "2, 3"
+        if let Some(schemes) = sections(&signature_data)
+            .first()
+            .and_then(|main| main.get("X-Android-APK-Signed"))
+        {
+            apk_signature_schemes.extend(
+                schemes
+                    .split(',')
+                    .filter_map(|scheme| scheme.trim().parse::<u32>().ok()),
+            );
+        }
+
+        covered_by_all = Some(match covered_by_all {
+            Some(previous) => previous
+                .into_iter()
+                .filter(|name| covered.contains(name))
+                .collect(),
+            None => covered,
+        });
+    }
+
+    let mut covered = covered_by_all.ok_or(Error::Unsigned)?;
+
+    // The manifest digests of every entry must match, signed or not. An entry without a digest
+    // that can be checked isn't protected by the signature, it is reported as unsigned.
+    for section in manifest_sections.iter().skip(1) {
+        let Some((name, entry)) = section
+            .get("Name")
+            .and_then(|name| Some((name, archive.find(name)?)))
+        else {
+            continue;
+        };
+
+        let digests = section.digests("-Digest");
+        if digests.is_empty() {
+            covered.retain(|covered| covered != name);
+            continue;
+        }
+
+        let contents = archive.read(entry)?;
+        if digests
+            .iter()
+            .any(|(hash, digest)| hash.digest(&contents) != *digest)
+        {
+            return Err(Error::BadSignature);
+        }
+    }
+
+    let unsigned_entries = archive
+        .entries
+        .iter()
+        .map(|entry| entry.name.as_str())
+        .filter(|name| !name.ends_with('/') && !is_signature_related(name))
+        .filter(|name| !covered.iter().any(|covered| covered == name))
+        .map(str::to_string)
+        .collect();
+
+    Ok(JarSignature {
+        cert: signer_certs.swap_remove(0),
+        unsigned_entries,
+        apk_signature_schemes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sections() {
+        let manifest = b"Manifest-Version: 1.0\r\nCreated-By: test\r\n\r\n\
+            Name: com/example/A\r\n very/long/Name.class\r\nSHA-256-Digest: AAAA\r\n\r\n";
+        let sections = sections(manifest);
+
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].get("created-by"), Some("test"));
+        assert_eq!(
+            sections[1].get("Name"),
+            Some("com/example/Avery/long/Name.class")
+        );
+        assert_eq!(
+            sections[1].raw,
+            &b"Name: com/example/A\r\n very/long/Name.class\r\nSHA-256-Digest: AAAA\r\n\r\n"[..]
+        );
+        assert_eq!(sections[1].digests("-Digest")[0].1, vec![0, 0, 0]);
+    }
+}
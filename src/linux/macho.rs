@@ -0,0 +1,361 @@
+use super::crypto::{verify_chain, HashAlgorithm};
+use super::pkcs7;
+use crate::Error;
+use std::convert::{TryFrom, TryInto};
+use x509_cert::Certificate;
+
+const MH_MAGIC: u32 = 0xfeed_face;
+const MH_MAGIC_64: u32 = 0xfeed_facf;
+const FAT_MAGIC: u32 = 0xcafe_babe;
+const FAT_MAGIC_64: u32 = 0xcafe_babf;
+const LC_CODE_SIGNATURE: u32 = 0x1d;
+
+const CSMAGIC_EMBEDDED_SIGNATURE: u32 = 0xfade_0cc0;
+const CSMAGIC_CODEDIRECTORY: u32 = 0xfade_0c02;
+const CSMAGIC_BLOBWRAPPER: u32 = 0xfade_0b01;
+
+const CSSLOT_CODEDIRECTORY: u32 = 0;
+const CSSLOT_INFOSLOT: u32 = 1;
+const CSSLOT_RESOURCEDIR: u32 = 3;
+const CSSLOT_ALTERNATE_CODEDIRECTORIES: std::ops::Range<u32> = 0x1000..0x1005;
+const CSSLOT_SIGNATURESLOT: u32 = 0x10000;
+
+/// The special slots checked: Info.plist, requirements, resource directory, entitlements and
+/// DER entitlements. Requirements and entitlements are blobs of the signature.
+const SPECIAL_SLOTS: [u32; 5] = [1, 2, 3, 5, 7];
+
+/// The code directory versions that added the scatter vector and the 64 bit code limit
+const CS_SUPPORTSSCATTER: u32 = 0x20100;
+const CS_SUPPORTSCODELIMIT64: u32 = 0x20300;
+
+/// The length of the code directory hashes recorded for nested code
+const CDHASH_LEN: usize = 20;
+
+/// The files of a bundle bound to the code directories through their special slots
+#[derive(Clone, Copy, Default)]
+pub(crate) struct BundleFiles<'a> {
+    pub info_plist: Option<&'a [u8]>,
+    pub code_resources: Option<&'a [u8]>,
+}
+
+/// A verified Mach-O code signature
+pub(crate) struct CodeSignature {
+    pub cert: Certificate,
+    /// The hashes of the code directories of every architecture, truncated to 20 bytes
+    pub cdhashes: Vec<Vec<u8>>,
+}
+
+fn be32(data: &[u8], offset: usize) -> Result<u32, Error> {
+    data.get(offset..offset.saturating_add(4))
+        .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+        .ok_or(Error::MalformedSignature)
+}
+
+fn be64(data: &[u8], offset: usize) -> Result<u64, Error> {
+    data.get(offset..offset.saturating_add(8))
+        .map(|bytes| u64::from_be_bytes(bytes.try_into().unwrap()))
+        .ok_or(Error::MalformedSignature)
+}
+
+fn le32(data: &[u8], offset: usize) -> Result<u32, Error> {
+    data.get(offset..offset.saturating_add(4))
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(Error::MalformedSignature)
+}
+
+fn range(data: &[u8], offset: u64, size: u64) -> Result<&[u8], Error> {
+    let offset = usize::try_from(offset).map_err(|_| Error::MalformedSignature)?;
+    let size = usize::try_from(size).map_err(|_| Error::MalformedSignature)?;
+
+    data.get(offset..offset.saturating_add(size))
+        .ok_or(Error::MalformedSignature)
+}
+
+/// Whether `data` is a Mach-O file or a universal binary
+pub(crate) fn is_macho(data: &[u8]) -> bool {
+    matches!(le32(data, 0), Ok(MH_MAGIC | MH_MAGIC_64))
+        || matches!(be32(data, 0), Ok(FAT_MAGIC | FAT_MAGIC_64))
+}
+
+/// The architecture slices of a universal binary, or the file itself
+fn slices(data: &[u8]) -> Result<Vec<&[u8]>, Error> {
+    let magic = be32(data, 0)?;
+    if magic != FAT_MAGIC && magic != FAT_MAGIC_64 {
+        return Ok(vec![data]);
+    }
+
+    (0..be32(data, 4)? as usize)
+        .map(|i| {
+            if magic == FAT_MAGIC {
+                let entry = 8 + i * 20;
+                range(
+                    data,
+                    be32(data, entry + 8)?.into(),
+                    be32(data, entry + 12)?.into(),
+                )
+            } else {
+                let entry = 8 + i * 32;
+                range(data, be64(data, entry + 8)?, be64(data, entry + 16)?)
+            }
+        })
+        .collect()
+}
+
+/// The blobs of an embedded signature, by slot
+fn blobs(superblob: &[u8]) -> Result<Vec<(u32, &[u8])>, Error> {
+    if be32(superblob, 0)? != CSMAGIC_EMBEDDED_SIGNATURE {
+        return Err(Error::MalformedSignature);
+    }
+
+    (0..be32(superblob, 8)? as usize)
+        .map(|i| {
+            let slot = be32(superblob, 12 + i * 8)?;
+            let offset = be32(superblob, 16 + i * 8)?;
+            let blob = range(
+                superblob,
+                offset.into(),
+                be32(superblob, offset as usize + 4)?.into(),
+            )?;
+
+            Ok((slot, blob))
+        })
+        .collect()
+}
+
+/// Check a code directory against the `code` it covers and the contents of its special slots.
+/// Returns the code directory hash.
+fn verify_code_directory<'a>(
+    directory: &[u8],
+    code: &[u8],
+    special: impl Fn(u32) -> Option<&'a [u8]>,
+) -> Result<Vec<u8>, Error> {
+    if be32(directory, 0)? != CSMAGIC_CODEDIRECTORY {
+        return Err(Error::MalformedSignature);
+    }
+
+    let version = be32(directory, 8)?;
+    let hash_offset = be32(directory, 16)? as usize;
+    let special_slots = be32(directory, 24)?;
+    let code_slots = be32(directory, 28)? as usize;
+    let mut code_limit = u64::from(be32(directory, 32)?);
+    let header = directory.get(36..40).ok_or(Error::MalformedSignature)?;
+    let (hash_size, hash_type, page_shift) = (usize::from(header[0]), header[1], header[3]);
+
+    if version >= CS_SUPPORTSSCATTER && be32(directory, 44)? != 0 {
+        return Err(Error::UnsupportedAlgorithm);
+    }
+    if version >= CS_SUPPORTSCODELIMIT64 && be64(directory, 56)? != 0 {
+        code_limit = be64(directory, 56)?;
+    }
+
+    // SHA-256 truncated to 20 bytes is type 3
+    let hash = match (hash_type, hash_size) {
+        (1, 20) => HashAlgorithm::Sha1,
+        (2, 32) | (3, 20) => HashAlgorithm::Sha256,
+        (4, 48) => HashAlgorithm::Sha384,
+        _ => return Err(Error::UnsupportedAlgorithm),
+    };
+    let digest = |data: &[u8]| {
+        let mut digest = hash.digest(data);
+        digest.truncate(hash_size);
+        digest
+    };
+    let slot = |start: usize| {
+        directory
+            .get(start..start.saturating_add(hash_size))
+            .ok_or(Error::MalformedSignature)
+    };
+
+    // Everything up to the signature is covered, nothing can be added after the code limit
+    if u64::try_from(code.len()).ok() != Some(code_limit) {
+        return Err(Error::BadSignature);
+    }
+
+    let page_size = match page_shift {
+        0 => code.len().max(1),
+        1..=31 => 1 << page_shift,
+        _ => return Err(Error::MalformedSignature),
+    };
+    if code.chunks(page_size).len() != code_slots {
+        return Err(Error::BadSignature);
+    }
+
+    for (i, page) in code.chunks(page_size).enumerate() {
+        if slot(hash_offset.saturating_add(i * hash_size))? != digest(page).as_slice() {
+            return Err(Error::BadSignature);
+        }
+    }
+
+    // A zero hash is an empty slot, its content must be absent as well
+    for number in SPECIAL_SLOTS {
+        let expected = if number <= special_slots {
+            let start = hash_offset
+                .checked_sub(number as usize * hash_size)
+                .ok_or(Error::MalformedSignature)?;
+            Some(slot(start)?).filter(|hash| hash.iter().any(|byte| *byte != 0))
+        } else {
+            None
+        };
+
+        match (expected, special(number)) {
+            (None, None) => {}
+            (Some(expected), Some(content)) if expected == digest(content).as_slice() => {}
+            _ => return Err(Error::BadSignature),
+        }
+    }
+
+    let mut cdhash = hash.digest(directory);
+    cdhash.truncate(CDHASH_LEN);
+    Ok(cdhash)
+}
+
+/// Verify the signature of a single architecture: the CMS signature over the code directory,
+/// the chain of its signer to one of the `trusted` certificates, and every code directory
+/// against the code and the bound `files`
+fn verify_slice(
+    slice: &[u8],
+    files: BundleFiles<'_>,
+    trusted: &[Certificate],
+) -> Result<CodeSignature, Error> {
+    let header_size = match le32(slice, 0)? {
+        MH_MAGIC => 28,
+        MH_MAGIC_64 => 32,
+        _ => return Err(Error::MalformedSignature),
+    };
+
+    let mut offset = header_size;
+    let mut code_signature = None;
+
+    for _ in 0..le32(slice, 16)? {
+        let command_size = le32(slice, offset + 4)? as usize;
+        if le32(slice, offset)? == LC_CODE_SIGNATURE {
+            code_signature = Some((le32(slice, offset + 8)?, le32(slice, offset + 12)?));
+        }
+        if command_size < 8 {
+            return Err(Error::MalformedSignature);
+        }
+        offset = offset.saturating_add(command_size);
+    }
+
+    let (signature_offset, signature_size) = code_signature.ok_or(Error::Unsigned)?;
+    let blobs = blobs(range(
+        slice,
+        signature_offset.into(),
+        signature_size.into(),
+    )?)?;
+    let blob = |slot: u32| {
+        blobs
+            .iter()
+            .find(|(number, _)| *number == slot)
+            .map(|(_, blob)| *blob)
+    };
+
+    let code_directory = blob(CSSLOT_CODEDIRECTORY).ok_or(Error::Unsigned)?;
+
+    // Ad hoc signatures have an empty signature blob
+    let signature = blob(CSSLOT_SIGNATURESLOT)
+        .filter(|wrapper| be32(wrapper, 0).ok() == Some(CSMAGIC_BLOBWRAPPER))
+        .map(|wrapper| &wrapper[8..])
+        .filter(|signature| !signature.is_empty())
+        .ok_or(Error::Unsigned)?;
+
+    // The signature covers the first code directory, the others are checked against the code
+    let certificates = pkcs7::certificates(signature)?;
+    let cert = pkcs7::verify_detached(signature, code_directory, &certificates)?;
+    verify_chain(&cert, &certificates, trusted)?;
+
+    let code = &slice[..signature_offset as usize];
+    let special = |slot| match slot {
+        CSSLOT_INFOSLOT => files.info_plist,
+        CSSLOT_RESOURCEDIR => files.code_resources,
+        _ => blob(slot),
+    };
+
+    let cdhashes = std::iter::once(code_directory)
+        .chain(CSSLOT_ALTERNATE_CODEDIRECTORIES.filter_map(blob))
+        .map(|directory| verify_code_directory(directory, code, special))
+        .collect::<Result<_, _>>()?;
+
+    Ok(CodeSignature { cert, cdhashes })
+}
+
+/// Verify the embedded signature of a Mach-O file, every architecture of a universal binary,
+/// against the `trusted` certificates. The architectures must have the same signer.
+/// The Info.plist and resource seal of a bundle are checked against the special slots.
+pub(crate) fn verify(
+    data: &[u8],
+    files: BundleFiles<'_>,
+    trusted: &[Certificate],
+) -> Result<CodeSignature, Error> {
+    let mut signature: Option<CodeSignature> = None;
+
+    for slice in slices(data)? {
+        let slice_signature = verify_slice(slice, files, trusted)?;
+
+        match &mut signature {
+            Some(signature) if signature.cert != slice_signature.cert => {
+                return Err(Error::BadSignature)
+            }
+            Some(signature) => signature.cdhashes.extend(slice_signature.cdhashes),
+            None => signature = Some(slice_signature),
+        }
+    }
+
+    signature.ok_or(Error::MalformedSignature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linux::crypto::load_keyring;
+    use crate::linux::zip::Archive;
+    use crate::test_util::testdata;
+
+    fn read(name: &str) -> Vec<u8> {
+        let data = std::fs::read(testdata("app.ipa")).unwrap();
+        let archive = Archive::parse(&data).unwrap();
+        archive
+            .read(archive.find(&format!("Payload/App.app/{name}")).unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_code_directories() {
+        let trusted = load_keyring(&testdata("ipa_root.pem")).unwrap();
+        let executable = read("App");
+        let info_plist = read("Info.plist");
+        let code_resources = read("_CodeSignature/CodeResources");
+        let files = BundleFiles {
+            info_plist: Some(&info_plist),
+            code_resources: Some(&code_resources),
+        };
+
+        // A SHA-1 code directory, signed, and a SHA-256 one
+        let signature = verify(&executable, files, &trusted).unwrap();
+        assert_eq!(signature.cdhashes.len(), 2);
+
+        let mut code = executable.clone();
+        code[100] ^= 1;
+        assert!(matches!(
+            verify(&code, files, &trusted),
+            Err(Error::BadSignature)
+        ));
+
+        // The Info.plist and resource seal are bound to the signature
+        assert!(matches!(
+            verify(&executable, BundleFiles::default(), &trusted),
+            Err(Error::BadSignature)
+        ));
+
+        // Both architectures of the universal library are signed
+        let library = read("Frameworks/libexample.dylib");
+        let signature = verify(&library, BundleFiles::default(), &trusted).unwrap();
+        assert_eq!(signature.cdhashes.len(), 2);
+
+        let untrusted = load_keyring(&testdata("pkg_root.pem")).unwrap();
+        assert!(matches!(
+            verify(&library, BundleFiles::default(), &untrusted),
+            Err(Error::UnknownSigner)
+        ));
+    }
+}
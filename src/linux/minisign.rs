@@ -0,0 +1,173 @@
+use crate::{Error, SignerIdentity};
+use base64::Engine;
+use blake2::{Blake2b512, Digest};
+use ed25519_dalek::{Signature, VerifyingKey};
+use std::convert::TryInto;
+use std::io::Read;
+use std::path::Path;
+
+const ALGO_ED25519: &[u8] = b"Ed"; // The signature covers the file itself
+const ALGO_ED25519_PREHASHED: &[u8] = b"ED"; // The signature covers the BLAKE2b-512 hash of the file
+
+const UNTRUSTED_COMMENT: &str = "untrusted comment: ";
+const MINISIGN_KEY_COMMENT: &str = "untrusted comment: minisign public key";
+const TRUSTED_COMMENT: &str = "trusted comment: ";
+
+/// Decode the base64 lines of a key or signature file, skipping the untrusted comment
+fn decode_lines(text: &str) -> Result<Vec<Vec<u8>>, Error> {
+    text.lines()
+        .filter(|line| !line.trim_end().is_empty() && !line.starts_with(UNTRUSTED_COMMENT))
+        .map(|line| {
+            // The trusted comment is signed as is, including trailing whitespace, it isn't base64
+            if let Some(comment) = line.strip_prefix(TRUSTED_COMMENT) {
+                return Ok(comment.as_bytes().to_vec());
+            }
+
+            base64::engine::general_purpose::STANDARD
+                .decode(line.trim_end())
+                .map_err(|_| Error::MalformedSignature)
+        })
+        .collect()
+}
+
+/// A minisign or signify public key: the algorithm, an 8 byte key ID and the Ed25519 key
+struct PublicKey {
+    key_id: [u8; 8],
+    key: VerifyingKey,
+    minisign: bool, // Signatures by the key must carry a trusted comment
+}
+
+impl PublicKey {
+    fn load(path: &Path) -> Result<Self, Error> {
+        let text = std::fs::read_to_string(path).map_err(Error::IoError)?;
+        let lines = decode_lines(&text).map_err(|_| Error::InvalidKey)?;
+
+        match lines.as_slice() {
+            [key] if key.len() == 42 && key.starts_with(ALGO_ED25519) => Ok(PublicKey {
+                key_id: key[2..10].try_into().unwrap(),
+                key: VerifyingKey::from_bytes(key[10..].try_into().unwrap())
+                    .map_err(|_| Error::InvalidKey)?,
+                minisign: text.starts_with(MINISIGN_KEY_COMMENT),
+            }),
+            _ => Err(Error::InvalidKey),
+        }
+    }
+}
+
+/// Verify `path` with a minisign signature, or a signify signature which has no trusted comment.
+/// The format is chosen by the public key, so a minisign signature can't be stripped down to a
+/// signify one.
+pub(crate) fn verify_file(
+    path: &Path,
+    signature: &Path,
+    public_key: &Path,
+) -> Result<SignerIdentity, Error> {
+    let text = std::fs::read_to_string(signature).map_err(Error::IoError)?;
+    let public_key = PublicKey::load(public_key)?;
+
+    let (signature, trusted_comment) = match decode_lines(&text)?.as_slice() {
+        [] => return Err(Error::Unsigned),
+        [signature] if !public_key.minisign => (signature.clone(), None),
+        [signature, comment, global_signature] if public_key.minisign => (
+            signature.clone(),
+            Some((comment.clone(), global_signature.clone())),
+        ),
+        _ => return Err(Error::MalformedSignature),
+    };
+
+    if signature.len() != 74 {
+        return Err(Error::MalformedSignature);
+    }
+
+    let (algorithm, signature) = signature.split_at(2);
+    let (key_id, signature) = signature.split_at(8);
+
+    if key_id != public_key.key_id {
+        return Err(Error::UnknownSigner);
+    }
+
+    let mut file = std::fs::File::open(path).map_err(Error::IoError)?;
+    let mut data = Vec::new();
+
+    match algorithm {
+        ALGO_ED25519 => {
+            file.read_to_end(&mut data).map_err(Error::IoError)?;
+        }
+        ALGO_ED25519_PREHASHED => {
+            let mut hasher = Blake2b512::new();
+            std::io::copy(&mut file, &mut hasher).map_err(Error::IoError)?;
+            data = hasher.finalize().to_vec();
+        }
+        _ => return Err(Error::UnsupportedAlgorithm),
+    }
+
+    let signature = Signature::from_bytes(signature.try_into().unwrap());
+    public_key
+        .key
+        .verify_strict(&data, &signature)
+        .map_err(|_| Error::BadSignature)?;
+
+    // The global signature binds the trusted comment to the file signature
+    let trusted_comment = match trusted_comment {
+        Some((comment, global_signature)) => {
+            let global_signature: &[u8; 64] = global_signature
+                .as_slice()
+                .try_into()
+                .map_err(|_| Error::MalformedSignature)?;
+
+            public_key
+                .key
+                .verify_strict(
+                    &[signature.to_bytes().as_slice(), &comment].concat(),
+                    &Signature::from_bytes(global_signature),
+                )
+                .map_err(|_| Error::BadSignature)?;
+
+            Some(String::from_utf8(comment).map_err(|_| Error::MalformedSignature)?)
+        }
+        None => None,
+    };
+
+    Ok(SignerIdentity::Minisign {
+        key_id: public_key
+            .key_id
+            .iter()
+            .rev()
+            .fold(String::new(), |s, byte| s + &format!("{byte:02X}")),
+        trusted_comment,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{testdata, TempFile};
+
+    #[test]
+    fn test_tampered_trusted_comment() {
+        let signature = std::fs::read_to_string(testdata("module.ko.minisig")).unwrap();
+
+        let tampered = TempFile::new("tampered.minisig", signature.replace("hashed", "hashes"));
+
+        let result = verify_file(&testdata("module.ko"), &tampered, &testdata("minisign.pub"));
+
+        assert!(matches!(result, Err(Error::BadSignature)));
+    }
+
+    #[test]
+    fn test_stripped_trusted_comment() {
+        let signature = std::fs::read_to_string(testdata("module.ko.minisig")).unwrap();
+
+        // Only the untrusted comment and the file signature are left, as in a signify signature
+        let stripped: String = signature
+            .lines()
+            .take(2)
+            .map(|line| line.to_owned() + "\n")
+            .collect();
+        let stripped = TempFile::new("stripped.minisig", stripped);
+
+        let result = verify_file(&testdata("module.ko"), &stripped, &testdata("minisign.pub"));
+
+        assert!(matches!(result, Err(Error::MalformedSignature)));
+    }
+}
@@ -0,0 +1,454 @@
+mod apk;
+mod context;
+mod crypto;
+mod deb;
+mod fsverity;
+mod ima;
+mod ipa;
+mod jar;
+mod macho;
+mod minisign;
+mod module;
+mod openpgp;
+mod pe;
+mod peer;
+mod pkcs7;
+mod plist;
+mod process;
+mod rpm;
+mod sigstore;
+mod sshsig;
+mod uefi;
+mod xar;
+mod zip;
+
+use super::Error;
+use crate::PeerCredentials;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::io::{BorrowedFd, OwnedFd};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+
+pub(crate) struct Verifier(SignatureKind);
+pub(crate) use context::Context;
+pub use fsverity::{FsVerityAlgorithm, FsVerityDigest};
+
+/// The keyring directory used when none is provided, as set up by `evmctl import`
+const DEFAULT_KEYRING: &str = "/etc/keys";
+
+enum SignatureKind {
+    /// File with a `security.ima` signature
+    Ima { file: PathBuf, keyring: PathBuf },
+    /// Open file with a `security.ima` signature
+    ImaOpenFile { file: File, keyring: PathBuf },
+    /// File held in memory, whose format is found from its content
+    Bytes { data: Vec<u8>, keyring: PathBuf },
+    /// Executable of a process with a `security.ima` signature, opened through `/proc`,
+    /// and a pidfd referring to the process when it was found through a socket
+    Process {
+        pid: i32,
+        exe: File,
+        keyring: PathBuf,
+        pidfd: Option<OwnedFd>,
+    },
+    /// Module with an appended PKCS#7 signature
+    KernelModule { module: PathBuf, key: PathBuf },
+    /// Detached PKCS#7 signature over the fs-verity digest
+    FsVerity {
+        digest: FsVerityDigest,
+        signature: PathBuf,
+        key: PathBuf,
+    },
+    /// Detached minisign or signify signature
+    Minisign {
+        file: PathBuf,
+        signature: PathBuf,
+        public_key: PathBuf,
+    },
+    /// Armored SSH signature made for a namespace
+    SshSig {
+        file: PathBuf,
+        signature: PathBuf,
+        allowed_signers: PathBuf,
+        namespace: String,
+    },
+    /// Detached OpenPGP signature
+    OpenPgp {
+        file: PathBuf,
+        signature: PathBuf,
+        keyring: PathBuf,
+    },
+    /// RPM package with OpenPGP header and payload signatures
+    Rpm { package: PathBuf, keyring: PathBuf },
+    /// Debian package with debsig or dpkg-sig signatures
+    Deb { package: PathBuf, keyring: PathBuf },
+    /// Java archive with JAR signatures
+    Jar { archive: PathBuf },
+    /// Android package with v1 (JAR), v2 or v3 signatures
+    Apk { package: PathBuf },
+    /// Sigstore bundle, verified offline against a trusted root
+    Sigstore {
+        file: PathBuf,
+        bundle: PathBuf,
+        trusted_root: PathBuf,
+    },
+    /// Flat installer package (xar) with a signed table of contents
+    Pkg { package: PathBuf, anchors: PathBuf },
+    /// iOS application archive with a signed application bundle
+    Ipa { archive: PathBuf, anchors: PathBuf },
+    /// EFI image with Authenticode signatures, checked against the Secure Boot databases
+    Uefi {
+        image: PathBuf,
+        db: PathBuf,
+        dbx: PathBuf,
+        sbat_level: Option<PathBuf>,
+    },
+}
+
+impl Verifier {
+    pub fn for_file<P: AsRef<Path>>(path: P) -> Self {
+        Self::for_file_with_keyring(path, DEFAULT_KEYRING)
+    }
+
+    pub fn for_file_with_keyring<P: AsRef<Path>, K: AsRef<Path>>(path: P, keyring: K) -> Self {
+        Verifier(SignatureKind::Ima {
+            file: path.as_ref().to_path_buf(),
+            keyring: keyring.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn for_fd(fd: BorrowedFd<'_>) -> Result<Self, Error> {
+        Self::for_fd_with_keyring(fd, DEFAULT_KEYRING)
+    }
+
+    /// Keep a duplicate of the file descriptor, the file is read through it
+    pub fn for_fd_with_keyring<K: AsRef<Path>>(
+        fd: BorrowedFd<'_>,
+        keyring: K,
+    ) -> Result<Self, Error> {
+        let file = File::from(fd.try_clone_to_owned().map_err(Error::IoError)?);
+
+        Ok(Verifier(SignatureKind::ImaOpenFile {
+            file,
+            keyring: keyring.as_ref().to_path_buf(),
+        }))
+    }
+
+    pub fn for_bytes(data: &[u8]) -> Self {
+        Self::for_bytes_with_keyring(data, DEFAULT_KEYRING)
+    }
+
+    pub fn for_bytes_with_keyring<K: AsRef<Path>>(data: &[u8], keyring: K) -> Self {
+        Verifier(SignatureKind::Bytes {
+            data: data.to_vec(),
+            keyring: keyring.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn for_reader<R: Read + Seek>(reader: R) -> Result<Self, Error> {
+        Self::for_reader_with_keyring(reader, DEFAULT_KEYRING)
+    }
+
+    /// Read the content from its start, the position of the reader is restored
+    pub fn for_reader_with_keyring<R: Read + Seek, K: AsRef<Path>>(
+        mut reader: R,
+        keyring: K,
+    ) -> Result<Self, Error> {
+        let mut data = Vec::new();
+        let position = reader.stream_position().map_err(Error::IoError)?;
+
+        reader
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| reader.read_to_end(&mut data))
+            .and_then(|_| reader.seek(SeekFrom::Start(position)))
+            .map_err(Error::IoError)?;
+
+        Ok(Verifier(SignatureKind::Bytes {
+            data,
+            keyring: keyring.as_ref().to_path_buf(),
+        }))
+    }
+
+    /// Open the executable of a process through `/proc/<pid>/exe`, which refers to the file that
+    /// was executed even if it was since renamed, replaced or deleted
+    pub fn for_pid(pid: i32) -> Result<Self, Error> {
+        let file = File::open(format!("/proc/{pid}/exe")).map_err(Error::IoError)?;
+
+        Ok(Verifier(SignatureKind::Process {
+            pid,
+            exe: file,
+            keyring: PathBuf::from(DEFAULT_KEYRING),
+            pidfd: None,
+        }))
+    }
+
+    /// Open the executable of the peer of a Unix domain socket. The pidfd of the peer is checked
+    /// after its files are opened, so they can't belong to another process that reused its PID.
+    pub fn for_unix_peer(stream: &UnixStream) -> Result<(Self, PeerCredentials), Error> {
+        let (credentials, peer_pidfd) = peer::credentials(stream)?;
+        let mut verifier = Self::for_pid(credentials.pid)?;
+
+        if let Some(peer_pidfd) = &peer_pidfd {
+            peer::check_alive(peer_pidfd)?;
+        }
+
+        if let SignatureKind::Process { pidfd, .. } = &mut verifier.0 {
+            *pidfd = peer_pidfd;
+        }
+
+        Ok((verifier, credentials))
+    }
+
+    pub fn for_kernel_module<P: AsRef<Path>, K: AsRef<Path>>(path: P, key: K) -> Self {
+        Verifier(SignatureKind::KernelModule {
+            module: path.as_ref().to_path_buf(),
+            key: key.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn for_fsverity<S: AsRef<Path>, K: AsRef<Path>>(
+        digest: FsVerityDigest,
+        signature: S,
+        key: K,
+    ) -> Self {
+        Verifier(SignatureKind::FsVerity {
+            digest,
+            signature: signature.as_ref().to_path_buf(),
+            key: key.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn for_openpgp<P: AsRef<Path>, S: AsRef<Path>, K: AsRef<Path>>(
+        path: P,
+        signature: S,
+        keyring: K,
+    ) -> Self {
+        Verifier(SignatureKind::OpenPgp {
+            file: path.as_ref().to_path_buf(),
+            signature: signature.as_ref().to_path_buf(),
+            keyring: keyring.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn for_minisign<P: AsRef<Path>, S: AsRef<Path>, K: AsRef<Path>>(
+        path: P,
+        signature: S,
+        public_key: K,
+    ) -> Self {
+        Verifier(SignatureKind::Minisign {
+            file: path.as_ref().to_path_buf(),
+            signature: signature.as_ref().to_path_buf(),
+            public_key: public_key.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn for_sshsig<P: AsRef<Path>, S: AsRef<Path>, A: AsRef<Path>>(
+        path: P,
+        signature: S,
+        allowed_signers: A,
+        namespace: &str,
+    ) -> Self {
+        Verifier(SignatureKind::SshSig {
+            file: path.as_ref().to_path_buf(),
+            signature: signature.as_ref().to_path_buf(),
+            allowed_signers: allowed_signers.as_ref().to_path_buf(),
+            namespace: namespace.to_string(),
+        })
+    }
+
+    pub fn for_rpm<P: AsRef<Path>, K: AsRef<Path>>(path: P, keyring: K) -> Self {
+        Verifier(SignatureKind::Rpm {
+            package: path.as_ref().to_path_buf(),
+            keyring: keyring.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn for_deb<P: AsRef<Path>, K: AsRef<Path>>(path: P, keyring: K) -> Self {
+        Verifier(SignatureKind::Deb {
+            package: path.as_ref().to_path_buf(),
+            keyring: keyring.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn for_jar<P: AsRef<Path>>(path: P) -> Self {
+        Verifier(SignatureKind::Jar {
+            archive: path.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn for_apk<P: AsRef<Path>>(path: P) -> Self {
+        Verifier(SignatureKind::Apk {
+            package: path.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn for_sigstore_bundle<P: AsRef<Path>, B: AsRef<Path>, T: AsRef<Path>>(
+        path: P,
+        bundle: B,
+        trusted_root: T,
+    ) -> Self {
+        Verifier(SignatureKind::Sigstore {
+            file: path.as_ref().to_path_buf(),
+            bundle: bundle.as_ref().to_path_buf(),
+            trusted_root: trusted_root.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn for_pkg<P: AsRef<Path>, A: AsRef<Path>>(path: P, anchors: A) -> Self {
+        Verifier(SignatureKind::Pkg {
+            package: path.as_ref().to_path_buf(),
+            anchors: anchors.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn for_ipa<P: AsRef<Path>, A: AsRef<Path>>(path: P, anchors: A) -> Self {
+        Verifier(SignatureKind::Ipa {
+            archive: path.as_ref().to_path_buf(),
+            anchors: anchors.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn for_uefi_image<P: AsRef<Path>, D: AsRef<Path>, X: AsRef<Path>>(
+        path: P,
+        db: D,
+        dbx: X,
+        sbat_level: Option<&Path>,
+    ) -> Self {
+        Verifier(SignatureKind::Uefi {
+            image: path.as_ref().to_path_buf(),
+            db: db.as_ref().to_path_buf(),
+            dbx: dbx.as_ref().to_path_buf(),
+            sbat_level: sbat_level.map(Path::to_path_buf),
+        })
+    }
+
+    pub fn verify(&self) -> Result<Context, Error> {
+        match &self.0 {
+            SignatureKind::Ima { file, keyring } => {
+                ima::verify_file(file, keyring).map(Context::new)
+            }
+            SignatureKind::ImaOpenFile { file, keyring } => {
+                ima::verify_open_file(file, keyring).map(Context::new)
+            }
+            SignatureKind::Bytes { data, keyring } => verify_bytes(data, keyring),
+            SignatureKind::Process {
+                pid,
+                exe,
+                keyring,
+                pidfd,
+            } => {
+                let cert = ima::verify_open_file(exe, keyring)?;
+                let info = process::inspect(*pid, exe, keyring)?;
+
+                if let Some(pidfd) = pidfd {
+                    peer::check_alive(pidfd)?;
+                }
+
+                Ok(Context::new(cert).with_process(info))
+            }
+            SignatureKind::KernelModule { module, key } => {
+                module::verify_module(module, key).map(Context::new)
+            }
+            SignatureKind::FsVerity {
+                digest,
+                signature,
+                key,
+            } => {
+                let signature = std::fs::read(signature).map_err(Error::IoError)?;
+                let trusted = crypto::load_certificates(key)?;
+
+                pkcs7::verify_detached(&signature, &digest.formatted(), &trusted).map(Context::new)
+            }
+            SignatureKind::Minisign {
+                file,
+                signature,
+                public_key,
+            } => minisign::verify_file(file, signature, public_key).map(Context::Key),
+            SignatureKind::SshSig {
+                file,
+                signature,
+                allowed_signers,
+                namespace,
+            } => sshsig::verify_file(file, signature, allowed_signers, namespace).map(Context::Key),
+            SignatureKind::OpenPgp {
+                file,
+                signature,
+                keyring,
+            } => openpgp::verify_detached(file, signature, keyring).map(Context::Key),
+            SignatureKind::Rpm { package, keyring } => {
+                rpm::verify_package(package, keyring).map(Context::Key)
+            }
+            SignatureKind::Deb { package, keyring } => {
+                deb::verify_package(package, keyring).map(Context::Key)
+            }
+            SignatureKind::Jar { archive } => {
+                let data = std::fs::read(archive).map_err(Error::IoError)?;
+
+                jar::verify_archive(&zip::Archive::parse(&data)?).map(jar_context)
+            }
+            SignatureKind::Apk { package } => apk::verify_apk(package).map(Context::new),
+            SignatureKind::Sigstore {
+                file,
+                bundle,
+                trusted_root,
+            } => sigstore::verify_bundle(file, bundle, trusted_root).map(Context::new),
+            SignatureKind::Pkg { package, anchors } => {
+                xar::verify_package(package, anchors).map(Context::new)
+            }
+            SignatureKind::Ipa { archive, anchors } => {
+                ipa::verify_ipa(archive, anchors).map(bundle_context)
+            }
+            SignatureKind::Uefi {
+                image,
+                db,
+                dbx,
+                sbat_level,
+            } => uefi::verify_image(image, db, dbx, sbat_level.as_deref()),
+        }
+    }
+}
+
+fn jar_context(signature: jar::JarSignature) -> Context {
+    Context::new(signature.cert).with_unsigned_entries(signature.unsigned_entries)
+}
+
+fn bundle_context(bundle: ipa::BundleSignature) -> Context {
+    Context::new(bundle.cert)
+        .with_unsigned_entries(bundle.unsealed)
+        .with_broken_nested_code(bundle.broken)
+}
+
+/// Verify a file held in memory the way the verifier of its format verifies a file, trusting
+/// the certificates in `keyring` for the formats that aren't self-signed
+fn verify_bytes(data: &[u8], keyring: &Path) -> Result<Context, Error> {
+    if let Ok(archive) = zip::Archive::parse(data) {
+        return if archive
+            .entries
+            .iter()
+            .any(|entry| entry.name.starts_with("Payload/"))
+        {
+            ipa::verify_ipa_archive(&archive, &crypto::load_keyring(keyring)?).map(bundle_context)
+        } else if archive.find("AndroidManifest.xml").is_some() {
+            apk::verify_apk_data(data).map(Context::new)
+        } else {
+            jar::verify_archive(&archive).map(jar_context)
+        };
+    }
+
+    let trusted = || crypto::load_keyring(keyring);
+
+    match data.get(..4) {
+        Some(b"xar!") => xar::verify_archive(data, &trusted()?).map(Context::new),
+        Some([b'M', b'Z', ..]) => uefi::verify_authenticode(data, trusted()?),
+        _ if macho::is_macho(data) => {
+            macho::verify(data, macho::BundleFiles::default(), &trusted()?)
+                .map(|signature| Context::new(signature.cert))
+        }
+        _ if module::has_signature(data) => {
+            module::verify_module_data(data, &trusted()?).map(Context::new)
+        }
+        // IMA signatures are in an extended attribute of the file, not in its content
+        _ => Err(Error::UnsupportedInput),
+    }
+}
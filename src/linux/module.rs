@@ -0,0 +1,94 @@
+use super::crypto::load_certificates;
+use super::pkcs7;
+use crate::Error;
+use std::convert::TryInto;
+use std::path::Path;
+use x509_cert::Certificate;
+
+const MODULE_SIG_STRING: &[u8] = b"~Module signature appended~\n";
+const MODULE_SIGNATURE_SIZE: usize = 12; // sizeof(struct module_signature)
+const PKEY_ID_PKCS7: u8 = 2;
+
+/// Whether `data` ends with an appended module signature
+pub(crate) fn has_signature(data: &[u8]) -> bool {
+    data.ends_with(MODULE_SIG_STRING)
+}
+
+/// Split a module into its contents and the appended PKCS#7 signature
+fn split_module(data: &[u8]) -> Result<(&[u8], &[u8]), Error> {
+    let data = data
+        .strip_suffix(MODULE_SIG_STRING)
+        .ok_or(Error::Unsigned)?;
+
+    let info_offset = data
+        .len()
+        .checked_sub(MODULE_SIGNATURE_SIZE)
+        .ok_or(Error::MalformedSignature)?;
+    let (data, info) = data.split_at(info_offset);
+
+    // struct module_signature { u8 algo, hash, id_type, signer_len, key_id_len, __pad[3]; __be32 sig_len; }
+    if info[2] != PKEY_ID_PKCS7 {
+        return Err(Error::UnsupportedAlgorithm);
+    }
+
+    let sig_len = u32::from_be_bytes(info[8..12].try_into().unwrap()) as usize;
+    let sig_offset = data
+        .len()
+        .checked_sub(sig_len)
+        .ok_or(Error::MalformedSignature)?;
+
+    Ok(data.split_at(sig_offset))
+}
+
+/// Verify the signature appended to the module at `path` against the certificates in `key`
+pub(crate) fn verify_module(path: &Path, key: &Path) -> Result<Certificate, Error> {
+    let data = std::fs::read(path).map_err(Error::IoError)?;
+
+    verify_module_data(&data, &load_certificates(key)?)
+}
+
+/// Verify the signature appended to a module against the `trusted` certificates
+pub(crate) fn verify_module_data(
+    data: &[u8],
+    trusted: &[Certificate],
+) -> Result<Certificate, Error> {
+    let (content, signature) = split_module(data)?;
+
+    pkcs7::verify_detached(signature, content, trusted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODULE: &[u8] = include_bytes!("testdata/module.ko");
+    const KEY: &[u8] = include_bytes!("testdata/signing_key.x509");
+
+    #[test]
+    fn test_split_module() {
+        let (content, signature) = split_module(MODULE).unwrap();
+        assert_eq!(content.len(), 3000);
+        assert_eq!(signature.len(), 375);
+
+        assert!(matches!(
+            split_module(&MODULE[..3000]),
+            Err(Error::Unsigned)
+        ));
+    }
+
+    #[test]
+    fn test_tampered_module() {
+        use der::Decode;
+
+        let trusted = vec![Certificate::from_der(KEY).unwrap()];
+        let (content, signature) = split_module(MODULE).unwrap();
+        assert!(pkcs7::verify_detached(signature, content, &trusted).is_ok());
+
+        let mut content = content.to_vec();
+        content[0] ^= 1;
+        assert!(matches!(
+            pkcs7::verify_detached(signature, &content, &trusted),
+            Err(Error::BadSignature)
+        ));
+    }
+}
@@ -0,0 +1,597 @@
+use super::crypto::{verify_rsa_key, HashAlgorithm};
+use crate::{hex, Error, SignerIdentity};
+use base64::Engine;
+use rsa::{BigUint, RsaPublicKey};
+use sha1::{Digest, Sha1};
+use std::convert::{TryFrom, TryInto};
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+// Packet tags (RFC 4880 section 4.3)
+const TAG_SIGNATURE: u8 = 2;
+const TAG_PUBLIC_KEY: u8 = 6;
+const TAG_USER_ID: u8 = 13;
+const TAG_PUBLIC_SUBKEY: u8 = 14;
+
+// Public key algorithms
+const ALGO_RSA: u8 = 1;
+const ALGO_RSA_SIGN_ONLY: u8 = 3;
+const ALGO_EDDSA_LEGACY: u8 = 22;
+const ALGO_ED25519: u8 = 27;
+
+// Signature subpackets
+const SUBPACKET_CREATION_TIME: u8 = 2;
+const SUBPACKET_ISSUER: u8 = 16;
+const SUBPACKET_ISSUER_FINGERPRINT: u8 = 33;
+
+// Signature types
+const BINARY_DOCUMENT: u8 = 0x00;
+const TEXT_DOCUMENT: u8 = 0x01;
+
+/// 1.3.6.1.4.1.11591.15.1, the curve OID of legacy `EdDSA` keys
+const OID_ED25519: &[u8] = &[0x2b, 0x06, 0x01, 0x04, 0x01, 0xda, 0x47, 0x0f, 0x01];
+
+const SIGNATURE_HEADER: &str = "-----BEGIN PGP SIGNATURE-----";
+
+/// A cursor over packet and subpacket bodies
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if len > self.0.len() {
+            return Err(Error::MalformedSignature);
+        }
+
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// A multiprecision integer: a bit count followed by the big-endian value
+    fn mpi(&mut self) -> Result<&'a [u8], Error> {
+        let bits = usize::from(self.u16()?);
+        self.take(bits.div_ceil(8))
+    }
+}
+
+/// Split binary OpenPGP data into its packets
+fn packets(data: &[u8]) -> Result<Vec<(u8, &[u8])>, Error> {
+    let mut reader = Reader(data);
+    let mut packets = Vec::new();
+
+    while !reader.0.is_empty() {
+        let header = reader.u8()?;
+
+        if header & 0x80 == 0 {
+            return Err(Error::MalformedSignature);
+        }
+
+        let (tag, len) = if header & 0x40 == 0 {
+            // Old format, the length type is in the low bits
+            let len = match header & 0x03 {
+                0 => usize::from(reader.u8()?),
+                1 => usize::from(reader.u16()?),
+                2 => reader.u32()? as usize,
+                _ => reader.0.len(), // Indeterminate, extends to the end of the data
+            };
+            ((header >> 2) & 0x0f, len)
+        } else {
+            let len = match reader.u8()? {
+                first @ 0..=191 => usize::from(first),
+                first @ 192..=223 => {
+                    (usize::from(first - 192) << 8) + usize::from(reader.u8()?) + 192
+                }
+                255 => reader.u32()? as usize,
+                _ => return Err(Error::MalformedSignature), // Partial lengths are only used for data packets
+            };
+            (header & 0x3f, len)
+        };
+
+        packets.push((tag, reader.take(len)?));
+    }
+
+    Ok(packets)
+}
+
+/// Decode ASCII armored data, which can hold several armored blocks, or return binary data as is
+pub(crate) fn dearmor(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return Ok(data.to_vec());
+    };
+
+    if !text.trim_start().starts_with("-----BEGIN PGP ") {
+        return Ok(data.to_vec());
+    }
+
+    let mut decoded = Vec::new();
+    let mut lines = text.lines().map(str::trim_end);
+
+    while lines.any(|line| line.starts_with("-----BEGIN PGP ")) {
+        // Armor headers end with an empty line
+        lines
+            .by_ref()
+            .take_while(|line| !line.is_empty())
+            .for_each(drop);
+
+        let mut body = String::new();
+        let mut checksum = None;
+
+        for line in lines.by_ref() {
+            if line.starts_with("-----END PGP ") {
+                break;
+            } else if let Some(crc) = line.strip_prefix('=') {
+                checksum = Some(crc.to_string());
+            } else {
+                body.push_str(line);
+            }
+        }
+
+        let block = base64::engine::general_purpose::STANDARD
+            .decode(body)
+            .map_err(|_| Error::MalformedSignature)?;
+
+        if let Some(checksum) = checksum {
+            let crc = base64::engine::general_purpose::STANDARD
+                .decode(checksum)
+                .map_err(|_| Error::MalformedSignature)?;
+
+            if crc.as_slice() != &crc24(&block).to_be_bytes()[1..] {
+                return Err(Error::MalformedSignature);
+            }
+        }
+
+        decoded.extend(block);
+    }
+
+    Ok(decoded)
+}
+
+/// The armor checksum (RFC 4880 section 6.1)
+fn crc24(data: &[u8]) -> u32 {
+    let mut crc = 0x00b7_04ce_u32;
+
+    for byte in data {
+        crc ^= u32::from(*byte) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x0100_0000 != 0 {
+                crc ^= 0x0186_4cfb;
+            }
+        }
+    }
+
+    crc & 0x00ff_ffff
+}
+
+enum KeyMaterial {
+    Rsa(RsaPublicKey),
+    Ed25519(ed25519_dalek::VerifyingKey),
+    Unsupported,
+}
+
+struct PublicKey {
+    fingerprint: [u8; 20],
+    material: KeyMaterial,
+}
+
+impl PublicKey {
+    fn parse(body: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader(body);
+
+        if reader.u8()? != 4 {
+            // Only version 4 keys are supported, but they must not stop a keyring from loading
+            return Ok(PublicKey {
+                fingerprint: [0; 20],
+                material: KeyMaterial::Unsupported,
+            });
+        }
+
+        let _created = reader.u32()?;
+
+        let material = match reader.u8()? {
+            ALGO_RSA | ALGO_RSA_SIGN_ONLY => {
+                let n = BigUint::from_bytes_be(reader.mpi()?);
+                let e = BigUint::from_bytes_be(reader.mpi()?);
+                RsaPublicKey::new(n, e).map_or(KeyMaterial::Unsupported, KeyMaterial::Rsa)
+            }
+            ALGO_EDDSA_LEGACY => {
+                let oid_len = usize::from(reader.u8()?);
+                let oid = reader.take(oid_len)?;
+                let point = reader.mpi()?;
+
+                match (oid, point) {
+                    (OID_ED25519, [0x40, key @ ..]) => ed25519_key(key),
+                    _ => KeyMaterial::Unsupported,
+                }
+            }
+            ALGO_ED25519 => ed25519_key(reader.take(32)?),
+            _ => KeyMaterial::Unsupported,
+        };
+
+        // The v4 fingerprint is the SHA-1 hash of the whole packet with an old format header
+        let mut hasher = Sha1::new();
+        hasher.update([0x99]);
+        hasher.update(
+            u16::try_from(body.len())
+                .map_err(|_| Error::InvalidKey)?
+                .to_be_bytes(),
+        );
+        hasher.update(body);
+
+        Ok(PublicKey {
+            fingerprint: hasher.finalize().into(),
+            material,
+        })
+    }
+
+    fn key_id(&self) -> &[u8] {
+        &self.fingerprint[12..]
+    }
+}
+
+fn ed25519_key(key: &[u8]) -> KeyMaterial {
+    key.try_into()
+        .ok()
+        .and_then(|key| ed25519_dalek::VerifyingKey::from_bytes(key).ok())
+        .map_or(KeyMaterial::Unsupported, KeyMaterial::Ed25519)
+}
+
+/// A transferable public key: the primary key with its subkeys and user IDs
+pub(crate) struct Cert {
+    keys: Vec<PublicKey>,
+    user_ids: Vec<String>,
+}
+
+/// Load the keys from a binary or ASCII armored keyring.
+/// The keyring is trusted as a whole, self signatures, expiration and revocation aren't evaluated.
+pub(crate) fn load_keyring(path: &Path) -> Result<Vec<Cert>, Error> {
+    let data = std::fs::read(path).map_err(Error::IoError)?;
+    let data = dearmor(&data).map_err(|_| Error::InvalidKey)?;
+
+    let mut certs: Vec<Cert> = Vec::new();
+
+    for (tag, body) in packets(&data).map_err(|_| Error::InvalidKey)? {
+        match (tag, certs.last_mut()) {
+            (TAG_PUBLIC_KEY, _) => certs.push(Cert {
+                keys: vec![PublicKey::parse(body).map_err(|_| Error::InvalidKey)?],
+                user_ids: Vec::new(),
+            }),
+            (TAG_PUBLIC_SUBKEY, Some(cert)) => {
+                cert.keys
+                    .push(PublicKey::parse(body).map_err(|_| Error::InvalidKey)?);
+            }
+            (TAG_USER_ID, Some(cert)) => {
+                cert.user_ids
+                    .push(String::from_utf8_lossy(body).into_owned());
+            }
+            _ => {}
+        }
+    }
+
+    if certs.is_empty() {
+        return Err(Error::InvalidKey);
+    }
+
+    Ok(certs)
+}
+
+/// A version 4 signature packet
+struct Signature<'a> {
+    sig_type: u8,
+    hash: HashAlgorithm,
+    hashed: &'a [u8], // The signature fields covered by the hash, up to the hashed subpackets
+    left16: &'a [u8],
+    created: u32,
+    issuer_fingerprint: Option<&'a [u8]>,
+    issuer: Option<&'a [u8]>,
+    values: Vec<&'a [u8]>,
+}
+
+impl<'a> Signature<'a> {
+    fn parse(body: &'a [u8]) -> Result<Self, Error> {
+        let mut reader = Reader(body);
+
+        if reader.u8()? != 4 {
+            return Err(Error::UnsupportedAlgorithm);
+        }
+
+        let sig_type = reader.u8()?;
+        let algorithm = reader.u8()?;
+        let hash = reader.u8()?;
+
+        let hashed_len = usize::from(reader.u16()?);
+        let hashed_subpackets = reader.take(hashed_len)?;
+        let hashed = &body[..6 + hashed_len];
+
+        let unhashed_len = usize::from(reader.u16()?);
+        let unhashed_subpackets = reader.take(unhashed_len)?;
+        let left16 = reader.take(2)?;
+
+        let mut signature = Signature {
+            sig_type,
+            hash: HashAlgorithm::from_openpgp(hash)?,
+            hashed,
+            left16,
+            created: 0,
+            issuer_fingerprint: None,
+            issuer: None,
+            values: Vec::new(),
+        };
+
+        // The creation time must be hashed, the issuer is commonly left out of the hashed area
+        let mut created = None;
+
+        for (hashed, subpackets) in [(true, hashed_subpackets), (false, unhashed_subpackets)] {
+            let mut reader = Reader(subpackets);
+
+            while !reader.0.is_empty() {
+                let len = match reader.u8()? {
+                    first @ 0..=191 => usize::from(first),
+                    first @ 192..=254 => {
+                        (usize::from(first - 192) << 8) + usize::from(reader.u8()?) + 192
+                    }
+                    255 => reader.u32()? as usize,
+                };
+
+                let mut subpacket = Reader(reader.take(len)?);
+
+                match subpacket.u8()? & 0x7f {
+                    SUBPACKET_CREATION_TIME if hashed => created = Some(subpacket.u32()?),
+                    SUBPACKET_ISSUER => signature.issuer = Some(subpacket.take(8)?),
+                    SUBPACKET_ISSUER_FINGERPRINT if subpacket.u8()? == 4 => {
+                        signature.issuer_fingerprint = Some(subpacket.take(20)?);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        // Every signature has a creation time (RFC 4880 section 5.2.3.4)
+        signature.created = created.ok_or(Error::MalformedSignature)?;
+
+        signature.values = match algorithm {
+            ALGO_RSA | ALGO_RSA_SIGN_ONLY => vec![reader.mpi()?],
+            ALGO_EDDSA_LEGACY => vec![reader.mpi()?, reader.mpi()?],
+            ALGO_ED25519 => vec![reader.take(64)?],
+            _ => return Err(Error::UnsupportedAlgorithm),
+        };
+
+        Ok(signature)
+    }
+
+    fn is_issued_by(&self, key: &PublicKey) -> bool {
+        match (self.issuer_fingerprint, self.issuer) {
+            (Some(fingerprint), _) => fingerprint == key.fingerprint,
+            (None, Some(issuer)) => issuer == key.key_id(),
+            (None, None) => false,
+        }
+    }
+
+    /// The trailer appended to the signed data before hashing (RFC 4880 section 5.2.4)
+    fn trailer(&self) -> Vec<u8> {
+        let mut trailer = self.hashed.to_vec();
+        trailer.extend_from_slice(&[4, 0xff]);
+        #[allow(clippy::cast_possible_truncation)]
+        trailer.extend_from_slice(&(self.hashed.len() as u32).to_be_bytes());
+        trailer
+    }
+
+    /// Verify the signature over the `digest` of the signed data and trailer with `key`
+    fn verify(&self, key: &PublicKey, digest: &[u8]) -> Result<(), Error> {
+        if !digest.starts_with(self.left16) {
+            return Err(Error::BadSignature);
+        }
+
+        match (&key.material, self.values.as_slice()) {
+            (KeyMaterial::Rsa(key), [value]) => {
+                // MPIs drop leading zeros, the signature must be as long as the modulus
+                let size = rsa::traits::PublicKeyParts::size(key);
+                let mut signature = vec![0; size.saturating_sub(value.len())];
+                signature.extend_from_slice(value);
+
+                verify_rsa_key(key, self.hash, digest, &signature)
+            }
+            (KeyMaterial::Ed25519(key), values) => {
+                let mut signature = [0; 64];
+
+                match values {
+                    [r, s] if r.len() <= 32 && s.len() <= 32 => {
+                        signature[32 - r.len()..32].copy_from_slice(r);
+                        signature[64 - s.len()..].copy_from_slice(s);
+                    }
+                    [value] if value.len() == 64 => signature.copy_from_slice(value),
+                    _ => return Err(Error::MalformedSignature),
+                }
+
+                // EdDSA signs the digest rather than the data
+                key.verify_strict(digest, &ed25519_dalek::Signature::from_bytes(&signature))
+                    .map_err(|_| Error::BadSignature)
+            }
+            (KeyMaterial::Rsa(_), _) => Err(Error::MalformedSignature),
+            (KeyMaterial::Unsupported, _) => Err(Error::UnsupportedAlgorithm),
+        }
+    }
+}
+
+/// Convert text to its canonical form with CRLF line endings, as signed by text signatures
+fn canonical_text(data: &[u8]) -> Vec<u8> {
+    let mut text = Vec::with_capacity(data.len());
+
+    for (i, byte) in data.iter().enumerate() {
+        if *byte == b'\n' && (i == 0 || data[i - 1] != b'\r') {
+            text.push(b'\r');
+        }
+        text.push(*byte);
+    }
+
+    text
+}
+
+/// Verify the data returned by `open` with the binary `signature` packets,
+/// made by one of the keys in `certs`. The data is opened once for each signature.
+pub(crate) fn verify_packets<R: Read>(
+    signature: &[u8],
+    certs: &[Cert],
+    mut open: impl FnMut() -> Result<R, Error>,
+) -> Result<SignerIdentity, Error> {
+    let mut result = Err(Error::Unsigned);
+
+    for (tag, body) in packets(signature)? {
+        if tag != TAG_SIGNATURE {
+            continue;
+        }
+
+        // Signatures of other versions or with unsupported algorithms may sit next to ours
+        let signature = match Signature::parse(body) {
+            Ok(signature) => signature,
+            Err(err) => {
+                result = result.or(Err(err));
+                continue;
+            }
+        };
+
+        // Only signatures over a document, SHA-1 is too weak to sign one
+        if signature.sig_type != BINARY_DOCUMENT && signature.sig_type != TEXT_DOCUMENT {
+            result = result.or(Err(Error::MalformedSignature));
+            continue;
+        }
+        if signature.hash == HashAlgorithm::Sha1 {
+            result = result.or(Err(Error::UnsupportedAlgorithm));
+            continue;
+        }
+
+        let Some((cert, key)) = certs.iter().find_map(|cert| {
+            cert.keys
+                .iter()
+                .find(|key| signature.is_issued_by(key))
+                .map(|key| (cert, key))
+        }) else {
+            result = result.or(Err(Error::UnknownSigner));
+            continue;
+        };
+
+        let mut data = open()?;
+        let trailer = signature.trailer();
+
+        let digest = if signature.sig_type == TEXT_DOCUMENT {
+            let mut text = Vec::new();
+            data.read_to_end(&mut text).map_err(Error::IoError)?;
+
+            let mut text = canonical_text(&text);
+            text.extend(trailer);
+            signature.hash.digest(&text)
+        } else {
+            signature
+                .hash
+                .digest_reader(&mut data.chain(trailer.as_slice()))
+                .map_err(Error::IoError)?
+        };
+
+        result = signature
+            .verify(key, &digest)
+            .map(|()| SignerIdentity::OpenPgp {
+                fingerprint: hex(&key.fingerprint),
+                user_ids: cert.user_ids.clone(),
+                created: SystemTime::UNIX_EPOCH + Duration::from_secs(signature.created.into()),
+            });
+
+        if result.is_ok() {
+            break;
+        }
+    }
+
+    result
+}
+
+/// Verify the file at `path` with the detached binary or ASCII armored `signature`,
+/// made by one of the keys in `keyring`
+pub(crate) fn verify_detached(
+    path: &Path,
+    signature: &Path,
+    keyring: &Path,
+) -> Result<SignerIdentity, Error> {
+    let signature = dearmor(&std::fs::read(signature).map_err(Error::IoError)?)?;
+    let certs = load_keyring(keyring)?;
+
+    verify_packets(&signature, &certs, || {
+        std::fs::File::open(path).map_err(Error::IoError)
+    })
+}
+
+/// Verify a cleartext signed message (RFC 4880 section 7), made by one of the keys in `certs`.
+/// Returns the signer and the signed text, with LF line endings.
+pub(crate) fn verify_cleartext(
+    message: &str,
+    certs: &[Cert],
+) -> Result<(SignerIdentity, String), Error> {
+    // The signature starts on a line of its own, a dash escaped header is part of the text
+    let start = std::iter::once(0)
+        .chain(message.match_indices('\n').map(|(i, _)| i + 1))
+        .find(|&i| message[i..].starts_with(SIGNATURE_HEADER))
+        .ok_or(Error::Unsigned)?;
+    let (text, signature) = message.split_at(start);
+
+    let mut lines = text
+        .lines()
+        .skip_while(|line| !line.starts_with("-----BEGIN PGP SIGNED MESSAGE-----"));
+    if lines.next().is_none() {
+        return Err(Error::MalformedSignature);
+    }
+
+    // Skip the armor headers, then undo the dash escaping; trailing whitespace isn't signed
+    let text = lines
+        .skip_while(|line| !line.trim_end().is_empty())
+        .skip(1)
+        .map(|line| {
+            line.strip_prefix("- ")
+                .unwrap_or(line)
+                .trim_end_matches([' ', '\t', '\r'])
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let signature = dearmor(signature.as_bytes())?;
+    let identity = verify_packets(&signature, certs, || Ok(text.as_bytes()))?;
+
+    Ok((identity, text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc24() {
+        assert_eq!(crc24(b""), 0x00b7_04ce);
+        assert_eq!(crc24(b"123456789"), 0x0021_cf02);
+    }
+
+    #[test]
+    fn test_cleartext_dash_escaped() {
+        let message = "-----BEGIN PGP SIGNED MESSAGE-----\nHash: SHA256\n\n\
+            - -----BEGIN PGP SIGNATURE-----\nnot a signature\n";
+
+        assert!(matches!(
+            verify_cleartext(message, &[]),
+            Err(Error::Unsigned)
+        ));
+    }
+
+    #[test]
+    fn test_canonical_text() {
+        assert_eq!(canonical_text(b"a\nb\r\nc\n"), b"a\r\nb\r\nc\r\n");
+    }
+}
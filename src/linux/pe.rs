@@ -0,0 +1,188 @@
+use super::crypto::HashAlgorithm;
+use crate::Error;
+use std::convert::TryInto;
+use std::ops::Range;
+
+const PE_SIGNATURE: &[u8] = b"PE\0\0";
+const PE32_MAGIC: u16 = 0x10b;
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+const CERTIFICATE_TABLE: usize = 4;
+const WIN_CERT_TYPE_PKCS_SIGNED_DATA: u16 = 0x0002;
+
+fn le16(data: &[u8], offset: usize) -> Result<u16, Error> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(Error::MalformedSignature)
+}
+
+fn le32(data: &[u8], offset: usize) -> Result<u32, Error> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .ok_or(Error::MalformedSignature)
+}
+
+/// A section of the image, with the range of its raw data in the file
+pub(crate) struct Section {
+    pub name: String,
+    pub raw: Range<usize>,
+}
+
+/// A PE/COFF image held in memory, e.g. an EFI application
+pub(crate) struct Image<'a> {
+    data: &'a [u8],
+    checksum_offset: usize,
+    certificate_table_entry: usize,
+    size_of_headers: usize,
+    certificate_table: Option<Range<usize>>,
+    pub sections: Vec<Section>,
+}
+
+impl<'a> Image<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        if data.get(..2) != Some(b"MZ") {
+            return Err(Error::MalformedSignature);
+        }
+
+        let pe = le32(data, 0x3c)? as usize;
+        if data.get(pe..pe + 4) != Some(PE_SIGNATURE) {
+            return Err(Error::MalformedSignature);
+        }
+
+        let coff = pe + 4;
+        let section_count = usize::from(le16(data, coff + 2)?);
+        let optional_header = coff + 20;
+        let optional_header_size = usize::from(le16(data, coff + 16)?);
+
+        // The data directories follow the fields that differ between PE32 and PE32+
+        let directories = match le16(data, optional_header)? {
+            PE32_MAGIC => optional_header + 96,
+            PE32_PLUS_MAGIC => optional_header + 112,
+            _ => return Err(Error::MalformedSignature),
+        };
+        let directory_count = le32(data, directories - 4)? as usize;
+        let certificate_table_entry = directories + CERTIFICATE_TABLE * 8;
+
+        let certificate_table = if directory_count > CERTIFICATE_TABLE {
+            // The certificate table is a file offset, unlike the other directories
+            let offset = le32(data, certificate_table_entry)? as usize;
+            let size = le32(data, certificate_table_entry + 4)? as usize;
+            if size == 0 {
+                None
+            } else if offset.checked_add(size) == Some(data.len()) {
+                Some(offset..data.len())
+            } else {
+                // The certificate table is always at the end of the file, which isn't hashed
+                return Err(Error::MalformedSignature);
+            }
+        } else {
+            None
+        };
+
+        let mut sections = Vec::with_capacity(section_count);
+        let section_headers = optional_header + optional_header_size;
+
+        for index in 0..section_count {
+            let header = section_headers + index * 40;
+            let name = data
+                .get(header..header + 8)
+                .ok_or(Error::MalformedSignature)?;
+            let size = le32(data, header + 16)? as usize;
+            let offset = le32(data, header + 20)? as usize;
+
+            if offset.saturating_add(size) > data.len() {
+                return Err(Error::MalformedSignature);
+            }
+
+            sections.push(Section {
+                name: String::from_utf8_lossy(name)
+                    .trim_end_matches('\0')
+                    .to_string(),
+                raw: offset..offset + size,
+            });
+        }
+
+        Ok(Image {
+            data,
+            checksum_offset: optional_header + 64,
+            certificate_table_entry,
+            size_of_headers: le32(data, optional_header + 60)? as usize,
+            certificate_table,
+            sections,
+        })
+    }
+
+    /// The raw data of the first section named `name`
+    pub fn section(&self, name: &str) -> Option<&'a [u8]> {
+        self.sections
+            .iter()
+            .find(|section| section.name == name)
+            .map(|section| &self.data[section.raw.clone()])
+    }
+
+    /// Compute the Authenticode digest of the image: the headers without the checksum and the
+    /// certificate table entry, the sections in file order and any data after them, except for
+    /// the certificate table
+    pub fn authenticode_digest(&self, hash: HashAlgorithm) -> Result<Vec<u8>, Error> {
+        let data = self.data;
+        let end = self
+            .certificate_table
+            .as_ref()
+            .map_or(data.len(), |table| table.start);
+
+        if self.size_of_headers > end || self.certificate_table_entry + 8 > self.size_of_headers {
+            return Err(Error::MalformedSignature);
+        }
+
+        let mut contents = [
+            &data[..self.checksum_offset],
+            &data[self.checksum_offset + 4..self.certificate_table_entry],
+            &data[self.certificate_table_entry + 8..self.size_of_headers],
+        ]
+        .concat();
+
+        let mut sections: Vec<&Section> = self
+            .sections
+            .iter()
+            .filter(|section| !section.raw.is_empty())
+            .collect();
+        sections.sort_by_key(|section| section.raw.start);
+
+        let mut hashed = self.size_of_headers;
+        for section in sections {
+            contents.extend_from_slice(&data[section.raw.clone()]);
+            hashed += section.raw.len();
+        }
+
+        if hashed < end {
+            contents.extend_from_slice(&data[hashed..end]);
+        }
+
+        Ok(hash.digest(&contents))
+    }
+
+    /// The PKCS#7 signatures of the certificate table, empty for an unsigned image
+    pub fn signatures(&self) -> Result<Vec<&'a [u8]>, Error> {
+        let Some(table) = &self.certificate_table else {
+            return Ok(Vec::new());
+        };
+
+        let mut signatures = Vec::new();
+        let mut offset = table.start;
+
+        // Each WIN_CERTIFICATE is 8 byte aligned: length, revision, type and the certificate
+        while offset + 8 <= table.end {
+            let length = le32(self.data, offset)? as usize;
+            if length < 8 || offset + length > table.end {
+                return Err(Error::MalformedSignature);
+            }
+
+            if le16(self.data, offset + 6)? == WIN_CERT_TYPE_PKCS_SIGNED_DATA {
+                signatures.push(&self.data[offset + 8..offset + length]);
+            }
+
+            offset += (length + 7) & !7;
+        }
+
+        Ok(signatures)
+    }
+}
@@ -0,0 +1,121 @@
+use crate::{Error, PeerCredentials};
+use std::convert::TryFrom;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+
+/// Read a socket option of `stream` into a `T`
+fn socket_option<T>(stream: &UnixStream, option: libc::c_int) -> std::io::Result<T> {
+    let mut value = std::mem::MaybeUninit::<T>::zeroed();
+    #[allow(clippy::cast_possible_truncation)]
+    let mut len = std::mem::size_of::<T>() as libc::socklen_t;
+
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            option,
+            value.as_mut_ptr().cast(),
+            std::ptr::addr_of_mut!(len),
+        )
+    };
+
+    if result != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(unsafe { value.assume_init() })
+}
+
+/// Open a pidfd for the peer of `stream`. `SO_PEERPIDFD` (Linux 6.5) refers to the process that
+/// connected, `pidfd_open` on older kernels to the process with its PID, which may have been reused.
+/// Returns `None` when neither is supported.
+fn peer_pidfd(stream: &UnixStream, pid: libc::pid_t) -> Result<Option<OwnedFd>, Error> {
+    let fd = match socket_option::<RawFd>(stream, libc::SO_PEERPIDFD) {
+        Ok(fd) => fd,
+        Err(err) if err.raw_os_error() == Some(libc::ENOPROTOOPT) => {
+            let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+            match RawFd::try_from(fd) {
+                Ok(fd) if fd >= 0 => fd,
+                _ => {
+                    let err = std::io::Error::last_os_error();
+                    if err.raw_os_error() == Some(libc::ENOSYS) {
+                        return Ok(None);
+                    }
+                    return Err(Error::IoError(err));
+                }
+            }
+        }
+        Err(err) => return Err(Error::IoError(err)),
+    };
+
+    Ok(Some(unsafe { OwnedFd::from_raw_fd(fd) }))
+}
+
+/// The credentials of the peer of `stream`, as of when it connected, and a pidfd referring to it
+pub(crate) fn credentials(
+    stream: &UnixStream,
+) -> Result<(PeerCredentials, Option<OwnedFd>), Error> {
+    let cred = socket_option::<libc::ucred>(stream, libc::SO_PEERCRED).map_err(Error::IoError)?;
+
+    // The peer is in another PID namespace, or gone
+    if cred.pid <= 0 {
+        return Err(Error::IoError(std::io::Error::from_raw_os_error(
+            libc::ESRCH,
+        )));
+    }
+
+    let pidfd = peer_pidfd(stream, cred.pid)?;
+
+    Ok((
+        PeerCredentials {
+            pid: cred.pid,
+            uid: cred.uid,
+            gid: cred.gid,
+        },
+        pidfd,
+    ))
+}
+
+/// Check that the process of `pidfd` is still running. As long as it is, its PID can't have been
+/// reused, so whatever was opened through `/proc/<pid>` before belongs to it.
+/// A pidfd becomes readable when its process exits, which unlike signaling it needs no permission.
+pub(crate) fn check_alive(pidfd: &OwnedFd) -> Result<(), Error> {
+    let mut poll_fd = libc::pollfd {
+        fd: pidfd.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+
+    if unsafe { libc::poll(std::ptr::addr_of_mut!(poll_fd), 1, 0) } < 0 {
+        return Err(Error::IoError(std::io::Error::last_os_error()));
+    }
+
+    if poll_fd.revents & libc::POLLIN != 0 {
+        return Err(Error::IoError(std::io::Error::from_raw_os_error(
+            libc::ESRCH,
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_alive() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, child.id(), 0) };
+        let pidfd = unsafe { OwnedFd::from_raw_fd(RawFd::try_from(fd).unwrap()) };
+
+        assert!(check_alive(&pidfd).is_ok());
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(check_alive(&pidfd).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use super::crypto::{verify_signature, HashAlgorithm};
+use crate::Error;
+use cms::cert::CertificateChoices;
+use cms::content_info::ContentInfo;
+use cms::signed_data::{SignedData, SignerIdentifier, SignerInfo};
+use der::asn1::{ObjectIdentifier, OctetStringRef};
+use der::{Decode, Encode};
+use x509_cert::ext::pkix::SubjectKeyIdentifier;
+use x509_cert::Certificate;
+
+const ID_SIGNED_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.7.2");
+const ID_MESSAGE_DIGEST: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.113549.1.9.4");
+
+fn signed_data(signature: &[u8]) -> Result<SignedData, Error> {
+    let content_info = ContentInfo::from_der(signature).map_err(|_| Error::MalformedSignature)?;
+
+    if content_info.content_type != ID_SIGNED_DATA {
+        return Err(Error::MalformedSignature);
+    }
+
+    content_info
+        .content
+        .decode_as()
+        .map_err(|_| Error::MalformedSignature)
+}
+
+/// The certificates embedded in a PKCS#7 `SignedData`, for formats where the signer is self-asserted
+pub(crate) fn certificates(signature: &[u8]) -> Result<Vec<Certificate>, Error> {
+    let signed_data = signed_data(signature)?;
+
+    Ok(signed_data
+        .certificates
+        .iter()
+        .flat_map(|certs| certs.0.iter())
+        .filter_map(|cert| match cert {
+            CertificateChoices::Certificate(cert) => Some(cert.clone()),
+            CertificateChoices::Other(_) => None,
+        })
+        .collect())
+}
+
+/// Verify a detached PKCS#7 `SignedData` over `content` against the `trusted` certificates.
+/// Returns the certificate of the first signer with a valid signature.
+pub(crate) fn verify_detached(
+    signature: &[u8],
+    content: &[u8],
+    trusted: &[Certificate],
+) -> Result<Certificate, Error> {
+    let signed_data = signed_data(signature)?;
+    let mut result = Err(Error::Unsigned);
+
+    for signer in signed_data.signer_infos.0.iter() {
+        let Some(cert) = trusted.iter().find(|cert| is_signer(cert, &signer.sid)) else {
+            result = result.or(Err(Error::UnknownSigner));
+            continue;
+        };
+
+        result = verify_signer(signer, content, cert).map(|()| cert.clone());
+        if result.is_ok() {
+            break;
+        }
+    }
+
+    result
+}
+
+/// Verify a PKCS#7 `SignedData` with embedded content of `content_type`, as used by Authenticode,
+/// against its own certificates. The content is signed without its outer tag and length.
+/// Returns the certificate of the first signer with a valid signature and the content.
+pub(crate) fn verify_embedded(
+    signature: &[u8],
+    content_type: ObjectIdentifier,
+) -> Result<(Certificate, Vec<u8>), Error> {
+    let signed_data = signed_data(signature)?;
+    let encapsulated = &signed_data.encap_content_info;
+
+    if encapsulated.econtent_type != content_type {
+        return Err(Error::MalformedSignature);
+    }
+
+    let content = encapsulated
+        .econtent
+        .as_ref()
+        .ok_or(Error::MalformedSignature)?
+        .value()
+        .to_vec();
+
+    verify_detached(signature, &content, &certificates(signature)?).map(|cert| (cert, content))
+}
+
+fn is_signer(cert: &Certificate, sid: &SignerIdentifier) -> bool {
+    match sid {
+        SignerIdentifier::IssuerAndSerialNumber(id) => {
+            cert.tbs_certificate.issuer == id.issuer
+                && cert.tbs_certificate.serial_number == id.serial_number
+        }
+        SignerIdentifier::SubjectKeyIdentifier(id) => cert
+            .tbs_certificate
+            .get::<SubjectKeyIdentifier>()
+            .ok()
+            .flatten()
+            .is_some_and(|(_, skid)| skid == *id),
+    }
+}
+
+fn verify_signer(signer: &SignerInfo, content: &[u8], cert: &Certificate) -> Result<(), Error> {
+    let hash = HashAlgorithm::from_oid(signer.digest_alg.oid)?;
+    let mut digest = hash.digest(content);
+
+    if let Some(attrs) = &signer.signed_attrs {
+        // With signed attributes the signature covers the attributes, which carry the content digest
+        let message_digest = attrs
+            .iter()
+            .find(|attr| attr.oid == ID_MESSAGE_DIGEST)
+            .and_then(|attr| attr.values.iter().next())
+            .and_then(|value| value.decode_as::<OctetStringRef<'_>>().ok())
+            .ok_or(Error::MalformedSignature)?;
+
+        if message_digest.as_bytes() != digest.as_slice() {
+            return Err(Error::BadSignature);
+        }
+
+        digest = hash.digest(&attrs.to_der().map_err(|_| Error::MalformedSignature)?);
+    }
+
+    verify_signature(cert, hash, &digest, signer.signature.as_bytes())
+}
@@ -0,0 +1,253 @@
+use crate::Error;
+use base64::Engine;
+use std::cell::Cell;
+use std::convert::TryFrom;
+
+const BPLIST_MAGIC: &[u8] = b"bplist00";
+const TRAILER_SIZE: usize = 32;
+
+/// The deepest nesting of dictionaries followed in a binary property list, whose
+/// object references can form cycles
+const MAX_DEPTH: usize = 32;
+
+/// The most objects decoded from a binary property list, where shared objects are decoded
+/// every time they are referenced
+const MAX_OBJECTS: usize = 1 << 16;
+
+/// A property list value. Arrays, numbers and dates aren't used by code signing and aren't
+/// decoded.
+pub(crate) enum Value {
+    Dict(Vec<(String, Value)>),
+    String(String),
+    Data(Vec<u8>),
+    Bool(bool),
+    Other,
+}
+
+impl Value {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_dict()?
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value)
+    }
+
+    pub fn as_dict(&self) -> Option<&[(String, Value)]> {
+        match self {
+            Value::Dict(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_data(&self) -> Option<&[u8]> {
+        match self {
+            Value::Data(data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// Parse an XML or binary (`bplist00`) property list
+pub(crate) fn parse(data: &[u8]) -> Result<Value, Error> {
+    if data.starts_with(BPLIST_MAGIC) {
+        return parse_binary(data);
+    }
+
+    let text = std::str::from_utf8(data).map_err(|_| Error::MalformedSignature)?;
+    // Property lists carry a document type declaration
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..roxmltree::ParsingOptions::default()
+    };
+    let document = roxmltree::Document::parse_with_options(text, options)
+        .map_err(|_| Error::MalformedSignature)?;
+    let root = document
+        .root_element()
+        .children()
+        .find(roxmltree::Node::is_element)
+        .ok_or(Error::MalformedSignature)?;
+
+    parse_xml(root)
+}
+
+fn parse_xml(node: roxmltree::Node<'_, '_>) -> Result<Value, Error> {
+    let text = || node.text().unwrap_or_default().to_string();
+
+    Ok(match node.tag_name().name() {
+        "dict" => {
+            let mut entries = Vec::new();
+            let mut children = node.children().filter(roxmltree::Node::is_element);
+
+            while let Some(key) = children.next() {
+                let value = children.next().ok_or(Error::MalformedSignature)?;
+                if !key.has_tag_name("key") {
+                    return Err(Error::MalformedSignature);
+                }
+                entries.push((
+                    key.text().unwrap_or_default().to_string(),
+                    parse_xml(value)?,
+                ));
+            }
+            Value::Dict(entries)
+        }
+        "string" => Value::String(text()),
+        "data" => {
+            let encoded: String = text().split_whitespace().collect();
+            Value::Data(
+                base64::engine::general_purpose::STANDARD
+                    .decode(encoded)
+                    .map_err(|_| Error::MalformedSignature)?,
+            )
+        }
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => Value::Other,
+    })
+}
+
+/// Read a big endian unsigned integer of `size` bytes at `offset`
+fn be_uint(data: &[u8], offset: usize, size: usize) -> Result<usize, Error> {
+    let bytes = data
+        .get(offset..offset.saturating_add(size))
+        .filter(|bytes| bytes.len() <= 8)
+        .ok_or(Error::MalformedSignature)?;
+    let value = bytes
+        .iter()
+        .fold(0_u64, |value, byte| value << 8 | u64::from(*byte));
+
+    usize::try_from(value).map_err(|_| Error::MalformedSignature)
+}
+
+struct BinaryPlist<'a> {
+    data: &'a [u8],
+    offset_size: usize,
+    ref_size: usize,
+    object_count: usize,
+    offset_table: usize,
+    decoded: Cell<usize>,
+}
+
+fn parse_binary(data: &[u8]) -> Result<Value, Error> {
+    let trailer = data
+        .len()
+        .checked_sub(TRAILER_SIZE)
+        .filter(|&trailer| trailer >= BPLIST_MAGIC.len())
+        .ok_or(Error::MalformedSignature)?;
+    let plist = BinaryPlist {
+        data,
+        offset_size: usize::from(data[trailer + 6]),
+        ref_size: usize::from(data[trailer + 7]),
+        object_count: be_uint(data, trailer + 8, 8)?,
+        offset_table: be_uint(data, trailer + 24, 8)?,
+        decoded: Cell::new(0),
+    };
+
+    plist.object(be_uint(data, trailer + 16, 8)?, 0)
+}
+
+impl BinaryPlist<'_> {
+    fn object(&self, index: usize, depth: usize) -> Result<Value, Error> {
+        self.decoded.set(self.decoded.get() + 1);
+        if index >= self.object_count || depth > MAX_DEPTH || self.decoded.get() > MAX_OBJECTS {
+            return Err(Error::MalformedSignature);
+        }
+
+        let offset = be_uint(
+            self.data,
+            self.offset_table
+                .saturating_add(index.saturating_mul(self.offset_size)),
+            self.offset_size,
+        )?;
+        let marker = *self.data.get(offset).ok_or(Error::MalformedSignature)?;
+
+        Ok(match marker >> 4 {
+            0x0 => match marker {
+                0x08 => Value::Bool(false),
+                0x09 => Value::Bool(true),
+                _ => Value::Other,
+            },
+            0x4 => {
+                let (start, len) = self.length(offset)?;
+                Value::Data(self.bytes(start, len)?.to_vec())
+            }
+            0x5 => {
+                let (start, len) = self.length(offset)?;
+                Value::String(
+                    String::from_utf8(self.bytes(start, len)?.to_vec())
+                        .map_err(|_| Error::MalformedSignature)?,
+                )
+            }
+            0x6 => {
+                let (start, len) = self.length(offset)?;
+                let units: Vec<u16> = self
+                    .bytes(start, len.saturating_mul(2))?
+                    .chunks(2)
+                    .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                    .collect();
+                Value::String(String::from_utf16(&units).map_err(|_| Error::MalformedSignature)?)
+            }
+            0xD => {
+                let (start, len) = self.length(offset)?;
+                let mut entries = Vec::new();
+
+                for i in 0..len {
+                    let Value::String(key) = self.object(self.reference(start, i)?, depth + 1)?
+                    else {
+                        return Err(Error::MalformedSignature);
+                    };
+                    let value =
+                        self.object(self.reference(start, len.saturating_add(i))?, depth + 1)?;
+                    entries.push((key, value));
+                }
+                Value::Dict(entries)
+            }
+            _ => Value::Other,
+        })
+    }
+
+    /// The start and length of the object at `offset`. Lengths of 15 and more follow the
+    /// marker as an integer object.
+    fn length(&self, offset: usize) -> Result<(usize, usize), Error> {
+        let info = usize::from(self.data[offset] & 0xf);
+        if info != 0xf {
+            return Ok((offset + 1, info));
+        }
+
+        let marker = *self.data.get(offset + 1).ok_or(Error::MalformedSignature)?;
+        if marker >> 4 != 0x1 {
+            return Err(Error::MalformedSignature);
+        }
+        let size = 1_usize << (marker & 0xf);
+
+        Ok((offset + 2 + size, be_uint(self.data, offset + 2, size)?))
+    }
+
+    fn bytes(&self, start: usize, len: usize) -> Result<&[u8], Error> {
+        self.data
+            .get(start..start.saturating_add(len))
+            .ok_or(Error::MalformedSignature)
+    }
+
+    /// The object index of the `i`th reference of a collection starting at `start`
+    fn reference(&self, start: usize, i: usize) -> Result<usize, Error> {
+        be_uint(
+            self.data,
+            start.saturating_add(i.saturating_mul(self.ref_size)),
+            self.ref_size,
+        )
+    }
+}
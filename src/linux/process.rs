@@ -0,0 +1,245 @@
+use super::ima;
+use crate::{Error, ProcessInfo};
+use std::ffi::CString;
+use std::fs::File;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
+
+const DELETED_SUFFIX: &str = " (deleted)";
+
+/// A file mapping of `/proc/<pid>/maps`
+struct Mapping {
+    range: String,
+    device: libc::dev_t,
+    inode: u64,
+    path: PathBuf,
+}
+
+/// Parse the executable file mappings of `/proc/<pid>/maps`:
+/// address range, permissions, offset, device (major:minor in hex), inode and path
+fn executable_mappings(maps: &str) -> Vec<Mapping> {
+    maps.lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(6, ' ');
+            let range = fields.next()?;
+            let permissions = fields.next()?;
+            let (_offset, device, inode) = (fields.next()?, fields.next()?, fields.next()?);
+            let path = fields.next()?.trim_start();
+
+            if !permissions.contains('x') || !path.starts_with('/') {
+                return None;
+            }
+
+            let (major, minor) = device.split_once(':')?;
+            Some(Mapping {
+                range: range.to_string(),
+                device: libc::makedev(
+                    u32::from_str_radix(major, 16).ok()?,
+                    u32::from_str_radix(minor, 16).ok()?,
+                ),
+                inode: inode.parse().ok()?,
+                path: PathBuf::from(path.strip_suffix(DELETED_SUFFIX).unwrap_or(path)),
+            })
+        })
+        .collect()
+}
+
+/// Open `path` as the process sees it, beneath its root directory (`/proc/<pid>/root`), which is
+/// the root of its container. Absolute symlinks are resolved in that root rather than the host's.
+#[allow(clippy::cast_sign_loss, clippy::cast_possible_truncation)]
+fn open_in_root(pid: i32, path: &Path) -> std::io::Result<File> {
+    let root = format!("/proc/{pid}/root");
+    let relative = path.strip_prefix("/").unwrap_or(path);
+    let root_dir = File::open(&root)?;
+    let relative_path = CString::new(relative.as_os_str().as_bytes())?;
+
+    let mut how: libc::open_how = unsafe { std::mem::zeroed() };
+    how.flags = (libc::O_RDONLY | libc::O_CLOEXEC) as u64;
+    how.resolve = libc::RESOLVE_IN_ROOT;
+
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_openat2,
+            root_dir.as_raw_fd(),
+            relative_path.as_ptr(),
+            std::ptr::addr_of!(how),
+            std::mem::size_of::<libc::open_how>(),
+        )
+    };
+
+    if fd >= 0 {
+        return Ok(unsafe { File::from_raw_fd(fd as i32) });
+    }
+
+    // openat2 was added in Linux 5.6, seccomp filters that don't know it may deny it with EPERM
+    let err = std::io::Error::last_os_error();
+    if !matches!(err.raw_os_error(), Some(libc::ENOSYS | libc::EPERM)) {
+        return Err(err);
+    }
+
+    File::open(Path::new(&root).join(relative))
+}
+
+/// Open the file of a mapping. `map_files` refers to the mapped file itself but needs
+/// `CAP_CHECKPOINT_RESTORE`, otherwise the path must still refer to the mapped file.
+fn open_mapping(pid: i32, mapping: &Mapping) -> Result<File, Error> {
+    if let Ok(file) = File::open(format!("/proc/{pid}/map_files/{}", mapping.range)) {
+        return Ok(file);
+    }
+
+    let file = open_in_root(pid, &mapping.path).map_err(Error::IoError)?;
+    let metadata = file.metadata().map_err(Error::IoError)?;
+
+    if metadata.dev() != mapping.device || metadata.ino() != mapping.inode {
+        return Err(Error::IoError(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "the mapped file was replaced",
+        )));
+    }
+
+    Ok(file)
+}
+
+/// The cgroup of a process from `/proc/<pid>/cgroup` (`hierarchy:controllers:path` lines):
+/// the unified (v2) hierarchy path unless only a v1 hierarchy places it below the root
+fn cgroup(contents: &str) -> Option<String> {
+    let paths: Vec<(&str, &str)> = contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, ':');
+            let hierarchy = fields.next()?;
+            fields.next()?;
+            Some((hierarchy, fields.next()?))
+        })
+        .collect();
+
+    paths
+        .iter()
+        .filter(|(_, path)| *path != "/")
+        .min_by_key(|(hierarchy, _)| *hierarchy != "0")
+        .or_else(|| paths.first())
+        .map(|(_, path)| (*path).to_string())
+}
+
+/// The container ID in a cgroup path, the 64 hex digits container runtimes name cgroups with,
+/// e.g. `/system.slice/docker-<id>.scope` or `/kubepods/besteffort/pod<uid>/<id>`
+fn container_id(cgroup: &str) -> Option<String> {
+    cgroup.rsplit('/').find_map(|component| {
+        component
+            .split(|c: char| !c.is_ascii_hexdigit())
+            .find(|id| id.len() == 64)
+            .map(str::to_string)
+    })
+}
+
+/// Inspect a process whose executable was opened as `exe`: where it was executed from, whether
+/// that path still refers to it, and which of the other executable mappings fail verification
+/// with the keys in `keyring`
+pub(crate) fn inspect(pid: i32, exe: &File, keyring: &Path) -> Result<ProcessInfo, Error> {
+    let executable = std::fs::read_link(format!("/proc/{pid}/exe")).map_err(Error::IoError)?;
+    let metadata = exe.metadata().map_err(Error::IoError)?;
+    let deleted = metadata.nlink() == 0;
+
+    let executable = match executable
+        .to_str()
+        .and_then(|path| path.strip_suffix(DELETED_SUFFIX).filter(|_| deleted))
+    {
+        Some(path) => PathBuf::from(path),
+        None => executable,
+    };
+
+    // The path is in the mount namespace of the process
+    let matches_path = open_in_root(pid, &executable)
+        .and_then(|file| file.metadata())
+        .is_ok_and(|current| current.dev() == metadata.dev() && current.ino() == metadata.ino());
+
+    let maps = std::fs::read_to_string(format!("/proc/{pid}/maps")).map_err(Error::IoError)?;
+    let mut mappings = executable_mappings(&maps);
+
+    // A shared object is usually mapped several times
+    mappings.retain(|mapping| mapping.device != metadata.dev() || mapping.inode != metadata.ino());
+    mappings.sort_by_key(|mapping| (mapping.device, mapping.inode));
+    mappings.dedup_by_key(|mapping| (mapping.device, mapping.inode));
+
+    let unverified_objects = mappings
+        .into_iter()
+        .filter_map(|mapping| {
+            open_mapping(pid, &mapping)
+                .and_then(|file| ima::verify_open_file(&file, keyring))
+                .err()
+                .map(|err| (mapping.path, err))
+        })
+        .collect();
+
+    let cgroup = std::fs::read_to_string(format!("/proc/{pid}/cgroup"))
+        .ok()
+        .and_then(|contents| cgroup(&contents));
+
+    Ok(ProcessInfo {
+        executable,
+        deleted,
+        matches_path,
+        unverified_objects,
+        container_id: cgroup.as_deref().and_then(container_id),
+        cgroup,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_executable_mappings() {
+        let maps = "55d0c8a00000-55d0c8a02000 r--p 00000000 fd:01 1316 /usr/bin/cat\n\
+            55d0c8a02000-55d0c8a07000 r-xp 00002000 fd:01 1316 /usr/bin/cat\n\
+            7f1e2c000000-7f1e2c200000 r-xp 00028000 103:02 2760 /usr/lib/libc.so.6 (deleted)\n\
+            7f1e2c400000-7f1e2c401000 r-xp 00000000 00:00 0 \n\
+            7ffd5a5f0000-7ffd5a5f2000 r-xp 00000000 00:00 0                          [vdso]\n";
+        let mappings = executable_mappings(maps);
+
+        assert_eq!(mappings.len(), 2);
+        assert_eq!(mappings[0].range, "55d0c8a02000-55d0c8a07000");
+        assert_eq!(mappings[0].device, libc::makedev(0xfd, 1));
+        assert_eq!(mappings[1].inode, 2760);
+        assert_eq!(mappings[1].path, Path::new("/usr/lib/libc.so.6"));
+    }
+
+    #[test]
+    fn test_container_id() {
+        let id = "4c5f6b4d2e0c9a1b8f7e6d5c4b3a29180716253443526170819a0b1c2d3e4f50";
+        let cgroups = format!(
+            "12:memory:/docker/{id}\n1:name=systemd:/docker/{id}\n0::/system.slice/docker-{id}.scope\n"
+        );
+
+        let path = cgroup(&cgroups).unwrap();
+        assert_eq!(path, format!("/system.slice/docker-{id}.scope"));
+        assert_eq!(container_id(&path).as_deref(), Some(id));
+
+        // A v1 hierarchy is used when the process is at the root of the unified one
+        assert_eq!(
+            cgroup("4:memory:/user.slice\n0::/\n").as_deref(),
+            Some("/user.slice")
+        );
+        assert_eq!(
+            container_id("/user.slice/user-1000.slice/session-2.scope"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_inspect_self() {
+        use std::convert::TryFrom;
+
+        let pid = i32::try_from(std::process::id()).unwrap();
+        let exe = File::open("/proc/self/exe").unwrap();
+        let info = inspect(pid, &exe, Path::new("/etc/keys")).unwrap();
+
+        assert_eq!(info.executable, std::env::current_exe().unwrap());
+        assert!(!info.deleted);
+        assert!(info.matches_path);
+        assert!(info.cgroup.is_some());
+    }
+}
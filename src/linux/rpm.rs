@@ -0,0 +1,224 @@
+use super::crypto::HashAlgorithm;
+use super::openpgp::{load_keyring, verify_packets};
+use crate::{hex, Error, SignerIdentity};
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+const LEAD_SIZE: usize = 96;
+const LEAD_MAGIC: &[u8] = &[0xed, 0xab, 0xee, 0xdb];
+const HEADER_MAGIC: &[u8] = &[0x8e, 0xad, 0xe8, 0x01];
+const HEADER_MAX_SIZE: usize = 256 * 1024 * 1024; // The limit enforced by rpm itself
+
+// Signature header tags
+const SIGTAG_DSA: u32 = 267; // OpenPGP signature of the header, for non RSA keys
+const SIGTAG_RSA: u32 = 268; // OpenPGP signature of the header, for RSA keys
+const SIGTAG_SHA256: u32 = 273; // Hex SHA-256 digest of the header
+const SIGTAG_PGP: u32 = 1002; // OpenPGP signature of the header and payload, for RSA keys
+const SIGTAG_GPG: u32 = 1005; // OpenPGP signature of the header and payload, for non RSA keys
+
+// Main header tags
+const RPMTAG_PAYLOADDIGEST: u32 = 5092;
+const RPMTAG_PAYLOADDIGESTALGO: u32 = 5093;
+
+// Header entry types
+const TYPE_INT32: u32 = 4;
+const TYPE_STRING: u32 = 6;
+const TYPE_BIN: u32 = 7;
+const TYPE_STRING_ARRAY: u32 = 8;
+
+fn be32(data: &[u8]) -> u32 {
+    u32::from_be_bytes(data[..4].try_into().unwrap())
+}
+
+/// An RPM header structure: the index entries and the data store
+struct Header {
+    blob: Vec<u8>, // The header as stored in the package, starting with the magic
+    index: Vec<(u32, u32, usize, usize)>, // Tag, type, data offset and count
+}
+
+impl Header {
+    fn read<R: Read>(reader: &mut R) -> Result<Self, Error> {
+        let mut blob = vec![0; 16];
+        reader
+            .read_exact(&mut blob)
+            .map_err(|_| Error::MalformedSignature)?;
+
+        // magic, 4 reserved bytes, number of index entries and size of the data store
+        if !blob.starts_with(HEADER_MAGIC) {
+            return Err(Error::MalformedSignature);
+        }
+
+        let entries = be32(&blob[8..]) as usize;
+        let size = be32(&blob[12..]) as usize;
+        let index_size = entries.checked_mul(16).ok_or(Error::MalformedSignature)?;
+
+        if index_size.saturating_add(size) > HEADER_MAX_SIZE {
+            return Err(Error::MalformedSignature);
+        }
+
+        blob.resize(16 + index_size + size, 0);
+        reader
+            .read_exact(&mut blob[16..])
+            .map_err(|_| Error::MalformedSignature)?;
+
+        let index = blob[16..16 + index_size]
+            .chunks_exact(16)
+            .map(|entry| {
+                (
+                    be32(entry),
+                    be32(&entry[4..]),
+                    be32(&entry[8..]) as usize,
+                    be32(&entry[12..]) as usize,
+                )
+            })
+            .collect();
+
+        Ok(Header { blob, index })
+    }
+
+    fn store(&self) -> &[u8] {
+        &self.blob[16 + self.index.len() * 16..]
+    }
+
+    fn find(&self, tag: u32, kinds: &[u32]) -> Result<Option<(&[u8], usize)>, Error> {
+        let Some(&(_, kind, offset, count)) = self.index.iter().find(|entry| entry.0 == tag) else {
+            return Ok(None);
+        };
+
+        if !kinds.contains(&kind) {
+            return Err(Error::MalformedSignature);
+        }
+
+        let data = self
+            .store()
+            .get(offset..)
+            .ok_or(Error::MalformedSignature)?;
+        Ok(Some((data, count)))
+    }
+
+    fn binary(&self, tag: u32) -> Result<Option<&[u8]>, Error> {
+        self.find(tag, &[TYPE_BIN])?
+            .map(|(data, count)| data.get(..count).ok_or(Error::MalformedSignature))
+            .transpose()
+    }
+
+    /// A string, or the first string of an array
+    fn string(&self, tag: u32) -> Result<Option<&str>, Error> {
+        self.find(tag, &[TYPE_STRING, TYPE_STRING_ARRAY])?
+            .map(|(data, _)| {
+                let end = data.iter().position(|&c| c == 0);
+                end.and_then(|end| std::str::from_utf8(&data[..end]).ok())
+                    .ok_or(Error::MalformedSignature)
+            })
+            .transpose()
+    }
+
+    fn int32(&self, tag: u32) -> Result<Option<u32>, Error> {
+        self.find(tag, &[TYPE_INT32])?
+            .map(|(data, _)| data.get(..4).map(be32).ok_or(Error::MalformedSignature))
+            .transpose()
+    }
+}
+
+/// Verify the RPM package at `path`: the OpenPGP signatures of the header and of the header and
+/// payload, made by one of the keys in `keyring`, and the SHA-256 digests of the header and payload
+pub(crate) fn verify_package(path: &Path, keyring: &Path) -> Result<SignerIdentity, Error> {
+    let mut file = File::open(path).map_err(Error::IoError)?;
+
+    let mut lead = [0; LEAD_SIZE];
+    file.read_exact(&mut lead)
+        .map_err(|_| Error::MalformedSignature)?;
+    if !lead.starts_with(LEAD_MAGIC) {
+        return Err(Error::MalformedSignature);
+    }
+
+    // The signature header is padded to a multiple of 8 bytes
+    let signature = Header::read(&mut file)?;
+    let padding = (8 - signature.blob.len() % 8) % 8;
+    file.read_exact(&mut [0; 8][..padding])
+        .map_err(|_| Error::MalformedSignature)?;
+
+    let header_offset = file.stream_position().map_err(Error::IoError)?;
+    let header = Header::read(&mut file)?;
+
+    let header_signature = match signature.binary(SIGTAG_RSA)? {
+        Some(signature) => Some(signature),
+        None => signature.binary(SIGTAG_DSA)?,
+    };
+    let package_signature = match signature.binary(SIGTAG_PGP)? {
+        Some(signature) => Some(signature),
+        None => signature.binary(SIGTAG_GPG)?,
+    };
+
+    if header_signature.is_none() && package_signature.is_none() {
+        return Err(Error::Unsigned);
+    }
+
+    let header_digest = hex(&HashAlgorithm::Sha256.digest(&header.blob));
+    if let Some(digest) = signature.string(SIGTAG_SHA256)? {
+        if !digest.eq_ignore_ascii_case(&header_digest) {
+            return Err(Error::BadSignature);
+        }
+    }
+
+    // The payload digest is part of the header, which makes a header signature cover the payload
+    let payload_digest = header.string(RPMTAG_PAYLOADDIGEST)?;
+    if let Some(digest) = payload_digest {
+        let hash = match header.int32(RPMTAG_PAYLOADDIGESTALGO)? {
+            Some(algorithm) => HashAlgorithm::from_openpgp(
+                algorithm
+                    .try_into()
+                    .map_err(|_| Error::UnsupportedAlgorithm)?,
+            )?,
+            None => return Err(Error::MalformedSignature),
+        };
+
+        let payload = hash.digest_reader(&mut file).map_err(Error::IoError)?;
+        if !digest.eq_ignore_ascii_case(&hex(&payload)) {
+            return Err(Error::BadSignature);
+        }
+    } else if package_signature.is_none() {
+        // Only an MD5 digest of the header and payload protects the payload
+        return Err(Error::UnsupportedAlgorithm);
+    }
+
+    let certs = load_keyring(keyring)?;
+    let mut identity = None;
+
+    if let Some(signature) = package_signature {
+        identity = Some(verify_packets(signature, &certs, || {
+            let mut file = File::open(path).map_err(Error::IoError)?;
+            file.seek(SeekFrom::Start(header_offset))
+                .map_err(Error::IoError)?;
+            Ok(file)
+        })?);
+    }
+
+    if let Some(signature) = header_signature {
+        identity = Some(verify_packets(signature, &certs, || {
+            Ok(header.blob.as_slice())
+        })?);
+    }
+
+    identity.ok_or(Error::Unsigned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{testdata, TempFile};
+
+    #[test]
+    fn test_tampered_payload() {
+        let mut package = std::fs::read(testdata("hello-1.0-1.noarch.rpm")).unwrap();
+        let last = package.len() - 1;
+        package[last] ^= 1;
+
+        let tampered = TempFile::new("tampered.rpm", package);
+        let result = verify_package(&tampered, &testdata("keyring.asc"));
+
+        assert!(matches!(result, Err(Error::BadSignature)));
+    }
+}
@@ -0,0 +1,658 @@
+use super::crypto::{verify_certificate, verify_signature, verify_spki, HashAlgorithm};
+use crate::{hex, Error};
+use base64::Engine;
+use der::asn1::{ObjectIdentifier, OctetString};
+use der::{Decode, DecodePem, Encode};
+use serde_json::Value;
+use std::convert::TryInto;
+use std::path::Path;
+use std::str::FromStr;
+use x509_cert::ext::pkix::{BasicConstraints, ExtendedKeyUsage};
+use x509_cert::spki::SubjectPublicKeyInfoOwned;
+use x509_cert::Certificate;
+
+const ID_KP_CODE_SIGNING: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.5.5.7.3.3");
+const CT_PRECERT_SCTS: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.11129.2.4.2");
+
+/// Look up a nested field of a JSON document
+fn field<'a>(value: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, key| value.get(key))
+}
+
+/// Decode a base64 encoded bytes field
+fn bytes_field(value: &Value, path: &[&str]) -> Result<Vec<u8>, Error> {
+    field(value, path)
+        .and_then(Value::as_str)
+        .and_then(|s| base64::engine::general_purpose::STANDARD.decode(s).ok())
+        .ok_or(Error::MalformedSignature)
+}
+
+/// Read an integer field, which protobuf JSON encodes as a string for 64 bit values
+fn int_field(value: &Value, path: &[&str]) -> Result<u64, Error> {
+    field(value, path)
+        .and_then(|value| match value {
+            Value::String(s) => s.parse().ok(),
+            value => value.as_u64(),
+        })
+        .ok_or(Error::MalformedSignature)
+}
+
+/// Parse a protobuf JSON timestamp, e.g. "2021-01-12T11:53:27.000Z", into seconds since the
+/// epoch. Fractional seconds are dropped.
+fn parse_timestamp(timestamp: &str) -> Option<u64> {
+    let timestamp = match timestamp.split_once('.') {
+        Some((seconds, fraction))
+            if fraction
+                .strip_suffix('Z')?
+                .bytes()
+                .all(|c| c.is_ascii_digit()) =>
+        {
+            format!("{seconds}Z")
+        }
+        Some(_) => return None,
+        None => timestamp.to_string(),
+    };
+
+    der::DateTime::from_str(&timestamp)
+        .ok()
+        .map(|time| time.unix_duration().as_secs())
+}
+
+/// The period a key or certificate authority of the trusted root is used for (`validFor`).
+/// Without an end it is still in use.
+#[derive(Default)]
+struct ValidFor {
+    start: u64,
+    end: Option<u64>,
+}
+
+impl ValidFor {
+    fn parse(value: Option<&Value>) -> Result<Self, Error> {
+        let Some(value) = value else {
+            return Ok(ValidFor::default());
+        };
+
+        let time = |name: &str| {
+            field(value, &[name])
+                .map(|time| {
+                    time.as_str()
+                        .and_then(parse_timestamp)
+                        .ok_or(Error::InvalidKey)
+                })
+                .transpose()
+        };
+
+        Ok(ValidFor {
+            start: time("start")?.unwrap_or_default(),
+            end: time("end")?,
+        })
+    }
+
+    fn contains(&self, time: u64) -> bool {
+        self.start <= time && self.end.is_none_or(|end| time <= end)
+    }
+}
+
+/// A transparency log (Rekor) or certificate transparency log from the trusted root
+struct TransparencyLog {
+    key_id: Vec<u8>,
+    key: SubjectPublicKeyInfoOwned,
+    valid_for: ValidFor,
+    origin: String, // The host of the log's base URL, which starts the origin of its checkpoints
+}
+
+/// A Fulcio certificate authority from the trusted root
+struct CertificateAuthority {
+    chain: Vec<Certificate>, // Starts with the issuing certificate
+    valid_for: ValidFor,
+}
+
+/// The trust anchors of a Sigstore instance (`trusted_root.json`)
+struct TrustedRoot {
+    tlogs: Vec<TransparencyLog>,
+    ctlogs: Vec<TransparencyLog>,
+    certificate_authorities: Vec<CertificateAuthority>,
+}
+
+impl TrustedRoot {
+    fn load(path: &Path) -> Result<Self, Error> {
+        let data = std::fs::read(path).map_err(Error::IoError)?;
+        let root: Value = serde_json::from_slice(&data).map_err(|_| Error::InvalidKey)?;
+
+        let logs = |name: &str| -> Result<Vec<TransparencyLog>, Error> {
+            field(&root, &[name])
+                .and_then(Value::as_array)
+                .map_or(&[][..], Vec::as_slice)
+                .iter()
+                .map(|log| {
+                    let key = bytes_field(log, &["publicKey", "rawBytes"])?;
+                    Ok(TransparencyLog {
+                        key_id: bytes_field(log, &["logId", "keyId"])?,
+                        key: SubjectPublicKeyInfoOwned::from_der(&key)
+                            .map_err(|_| Error::InvalidKey)?,
+                        valid_for: ValidFor::parse(field(log, &["publicKey", "validFor"]))?,
+                        origin: field(log, &["baseUrl"])
+                            .and_then(Value::as_str)
+                            .map(|url| url.split("://").last().unwrap_or(url))
+                            .map(|url| url.trim_end_matches('/').to_string())
+                            .ok_or(Error::InvalidKey)?,
+                    })
+                })
+                .collect::<Result<_, Error>>()
+                .map_err(|_| Error::InvalidKey)
+        };
+
+        let certificate_authorities = field(&root, &["certificateAuthorities"])
+            .and_then(Value::as_array)
+            .map_or(&[][..], Vec::as_slice)
+            .iter()
+            .map(|authority| {
+                let chain = field(authority, &["certChain", "certificates"])
+                    .and_then(Value::as_array)
+                    .ok_or(Error::InvalidKey)?
+                    .iter()
+                    .map(|cert| {
+                        let der = bytes_field(cert, &["rawBytes"])?;
+                        Certificate::from_der(&der).map_err(|_| Error::InvalidKey)
+                    })
+                    .collect::<Result<Vec<_>, Error>>()
+                    .map_err(|_| Error::InvalidKey)?;
+
+                Ok(CertificateAuthority {
+                    chain,
+                    valid_for: ValidFor::parse(field(authority, &["validFor"]))?,
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(TrustedRoot {
+            tlogs: logs("tlogs")?,
+            ctlogs: logs("ctlogs")?,
+            certificate_authorities,
+        })
+    }
+}
+
+fn is_valid_at(cert: &Certificate, time: u64) -> bool {
+    let validity = &cert.tbs_certificate.validity;
+    validity.not_before.to_unix_duration().as_secs() <= time
+        && time <= validity.not_after.to_unix_duration().as_secs()
+}
+
+fn is_ca(cert: &Certificate) -> bool {
+    matches!(
+        cert.tbs_certificate.get::<BasicConstraints>(),
+        Ok(Some((_, constraints))) if constraints.ca
+    )
+}
+
+/// Chain the leaf certificate to one of the trusted certificate authorities in use at `time`,
+/// returning the certificate that issued the leaf
+fn verify_chain<'a>(
+    leaf: &Certificate,
+    root: &'a TrustedRoot,
+    time: u64,
+) -> Result<&'a Certificate, Error> {
+    let code_signing = matches!(
+        leaf.tbs_certificate.get::<ExtendedKeyUsage>(),
+        Ok(Some((_, usage))) if usage.0.contains(&ID_KP_CODE_SIGNING)
+    );
+
+    if !code_signing || !is_valid_at(leaf, time) {
+        return Err(Error::BadSignature);
+    }
+
+    let mut result = Err(Error::UnknownSigner);
+
+    for authority in &root.certificate_authorities {
+        let chain = &authority.chain;
+        let Some(issuer) = chain.first().filter(|_| authority.valid_for.contains(time)) else {
+            continue;
+        };
+
+        if issuer.tbs_certificate.subject != leaf.tbs_certificate.issuer {
+            continue;
+        }
+
+        result = std::iter::once(leaf)
+            .chain(chain.iter())
+            .zip(chain.iter())
+            .try_for_each(|(cert, issuer)| {
+                if !is_ca(issuer) || !is_valid_at(issuer, time) {
+                    return Err(Error::BadSignature);
+                }
+                verify_certificate(cert, issuer)
+            })
+            .map(|()| issuer);
+
+        if result.is_ok() {
+            break;
+        }
+    }
+
+    result
+}
+
+/// Verify that at least one embedded signed certificate timestamp comes from a trusted CT log,
+/// made while the log key was in use
+fn verify_scts(
+    leaf: &Certificate,
+    issuer: &Certificate,
+    ctlogs: &[TransparencyLog],
+) -> Result<(), Error> {
+    let extensions = leaf
+        .tbs_certificate
+        .extensions
+        .as_deref()
+        .unwrap_or_default();
+
+    let sct_list = extensions
+        .iter()
+        .find(|ext| ext.extn_id == CT_PRECERT_SCTS)
+        .ok_or(Error::Unsigned)?;
+    let sct_list = OctetString::from_der(sct_list.extn_value.as_bytes())
+        .map_err(|_| Error::MalformedSignature)?;
+
+    // The SCTs sign the precertificate, which is the certificate without the SCT list
+    let mut precert = leaf.tbs_certificate.clone();
+    precert.extensions = Some(
+        extensions
+            .iter()
+            .filter(|ext| ext.extn_id != CT_PRECERT_SCTS)
+            .cloned()
+            .collect(),
+    );
+    let precert = precert.to_der().map_err(|_| Error::MalformedSignature)?;
+
+    let issuer_key = issuer
+        .tbs_certificate
+        .subject_public_key_info
+        .to_der()
+        .map_err(|_| Error::MalformedSignature)?;
+    let issuer_key_hash = HashAlgorithm::Sha256.digest(&issuer_key);
+
+    // TLS encoded lists, each element has a 16 bit length prefix
+    let tls_vec = |data: &[u8]| -> Result<Vec<Vec<u8>>, Error> {
+        let mut items = Vec::new();
+        let mut data = data;
+
+        while data.len() >= 2 {
+            let len = usize::from(u16::from_be_bytes([data[0], data[1]]));
+            let item = data.get(2..2 + len).ok_or(Error::MalformedSignature)?;
+            items.push(item.to_vec());
+            data = &data[2 + len..];
+        }
+
+        Ok(items)
+    };
+
+    let [list] = tls_vec(sct_list.as_bytes())?
+        .try_into()
+        .map_err(|_| Error::MalformedSignature)?;
+    let mut result = Err(Error::UnknownSigner);
+
+    for sct in tls_vec(&list)? {
+        // struct { version; log_id[32]; timestamp; extensions<0..2^16-1>; digitally-signed struct }
+        if sct.len() < 43 || sct[0] != 0 {
+            result = Err(Error::MalformedSignature);
+            continue;
+        }
+
+        let (log_id, timestamp) = (&sct[1..33], &sct[33..41]);
+        let ext_len = usize::from(u16::from_be_bytes([sct[41], sct[42]]));
+        let sct_extensions = sct.get(43..43 + ext_len).ok_or(Error::MalformedSignature)?;
+        let signed = sct.get(43 + ext_len..).ok_or(Error::MalformedSignature)?;
+
+        let [hash, _signature_algorithm, ..] = *signed else {
+            result = Err(Error::MalformedSignature);
+            continue;
+        };
+        let hash = match hash {
+            4 => HashAlgorithm::Sha256,
+            5 => HashAlgorithm::Sha384,
+            6 => HashAlgorithm::Sha512,
+            _ => {
+                result = Err(Error::UnsupportedAlgorithm);
+                continue;
+            }
+        };
+        let [signature] = tls_vec(&signed[2..])?
+            .try_into()
+            .map_err(|_| Error::MalformedSignature)?;
+
+        // The timestamp is in milliseconds
+        let time = u64::from_be_bytes(timestamp.try_into().unwrap()) / 1000;
+        let Some(log) = ctlogs
+            .iter()
+            .find(|log| log.key_id == log_id && log.valid_for.contains(time))
+        else {
+            continue;
+        };
+
+        // version, signature_type, timestamp, entry_type (precert), issuer_key_hash, tbs, extensions
+        let mut data = vec![0, 0];
+        data.extend_from_slice(timestamp);
+        data.extend_from_slice(&[0, 1]);
+        data.extend_from_slice(&issuer_key_hash);
+        #[allow(clippy::cast_possible_truncation)]
+        data.extend_from_slice(&(precert.len() as u32).to_be_bytes()[1..]);
+        data.extend_from_slice(&precert);
+        #[allow(clippy::cast_possible_truncation)]
+        data.extend_from_slice(&(sct_extensions.len() as u16).to_be_bytes());
+        data.extend_from_slice(sct_extensions);
+
+        result = verify_spki(&log.key, hash, &hash.digest(&data), &signature);
+        if result.is_ok() {
+            break;
+        }
+    }
+
+    result
+}
+
+/// Compute the root hash of a Merkle tree from an inclusion proof (RFC 9162 section 2.1.3.2)
+fn inclusion_proof_root(
+    index: u64,
+    tree_size: u64,
+    leaf: Vec<u8>,
+    hashes: &[Vec<u8>],
+) -> Option<Vec<u8>> {
+    if index >= tree_size {
+        return None;
+    }
+
+    let node =
+        |left: &[u8], right: &[u8]| HashAlgorithm::Sha256.digest(&[&[1], left, right].concat());
+    let (mut fn_, mut sn) = (index, tree_size - 1);
+    let mut root = leaf;
+
+    for hash in hashes {
+        if sn == 0 {
+            return None;
+        }
+
+        if fn_ & 1 == 1 || fn_ == sn {
+            root = node(hash, &root);
+            while fn_ & 1 == 0 && fn_ != 0 {
+                fn_ >>= 1;
+                sn >>= 1;
+            }
+        } else {
+            root = node(&root, hash);
+        }
+
+        fn_ >>= 1;
+        sn >>= 1;
+    }
+
+    (sn == 0).then_some(root)
+}
+
+/// Verify the signed checkpoint (a signed note) of `log` commits to `tree_size` and `root_hash`
+fn verify_checkpoint(
+    envelope: &str,
+    log: &TransparencyLog,
+    tree_size: u64,
+    root_hash: &[u8],
+) -> Result<(), Error> {
+    let (note, signatures) = envelope
+        .split_once("\n\n")
+        .ok_or(Error::MalformedSignature)?;
+    let note = format!("{note}\n");
+
+    let signed = signatures
+        .lines()
+        .filter_map(|line| line.strip_prefix("\u{2014} "))
+        .filter_map(|line| line.rsplit(' ').next())
+        .filter_map(|signature| {
+            base64::engine::general_purpose::STANDARD
+                .decode(signature)
+                .ok()
+        })
+        .filter(|signature| signature.len() > 4)
+        .any(|signature| {
+            let digest = HashAlgorithm::Sha256.digest(note.as_bytes());
+            verify_spki(&log.key, HashAlgorithm::Sha256, &digest, &signature[4..]).is_ok()
+        });
+
+    if !signed {
+        return Err(Error::BadSignature);
+    }
+
+    // origin, tree size, base64 root hash, optional extension lines. Rekor appends the ID of
+    // the tree to its origin.
+    let mut lines = note.lines();
+    let origin = lines.next().unwrap_or_default();
+    if origin != log.origin && !origin.starts_with(&format!("{} - ", log.origin)) {
+        return Err(Error::BadSignature);
+    }
+
+    let size = lines.next().and_then(|size| size.parse::<u64>().ok());
+    let root = lines
+        .next()
+        .and_then(|root| base64::engine::general_purpose::STANDARD.decode(root).ok());
+
+    if size != Some(tree_size) || root.as_deref() != Some(root_hash) {
+        return Err(Error::BadSignature);
+    }
+
+    Ok(())
+}
+
+/// Verify a Rekor entry: its signed entry timestamp, by a log key in use at its integrated time,
+/// and its inclusion proof. Returns the integrated time and the canonicalized body of the entry.
+fn verify_tlog_entry(entry: &Value, tlogs: &[TransparencyLog]) -> Result<(u64, Vec<u8>), Error> {
+    let key_id = bytes_field(entry, &["logId", "keyId"])?;
+    let integrated_time = int_field(entry, &["integratedTime"])?;
+    let log = tlogs
+        .iter()
+        .find(|log| log.key_id == key_id && log.valid_for.contains(integrated_time))
+        .ok_or(Error::UnknownSigner)?;
+
+    let body = bytes_field(entry, &["canonicalizedBody"])?;
+    let log_index = int_field(entry, &["logIndex"])?;
+
+    // The signed entry timestamp is a signature over the canonical JSON of the entry
+    let set = bytes_field(entry, &["inclusionPromise", "signedEntryTimestamp"])?;
+    let payload = format!(
+        r#"{{"body":"{}","integratedTime":{},"logID":"{}","logIndex":{}}}"#,
+        base64::engine::general_purpose::STANDARD.encode(&body),
+        integrated_time,
+        hex(&key_id),
+        log_index
+    );
+
+    let digest = HashAlgorithm::Sha256.digest(payload.as_bytes());
+    verify_spki(&log.key, HashAlgorithm::Sha256, &digest, &set)?;
+
+    let proof = field(entry, &["inclusionProof"]).ok_or(Error::MalformedSignature)?;
+    let tree_size = int_field(proof, &["treeSize"])?;
+    let root_hash = bytes_field(proof, &["rootHash"])?;
+    let hashes = field(proof, &["hashes"])
+        .and_then(Value::as_array)
+        .ok_or(Error::MalformedSignature)?
+        .iter()
+        .map(|hash| {
+            hash.as_str()
+                .and_then(|hash| base64::engine::general_purpose::STANDARD.decode(hash).ok())
+                .ok_or(Error::MalformedSignature)
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let leaf = HashAlgorithm::Sha256.digest(&[&[0], body.as_slice()].concat());
+    let root = inclusion_proof_root(int_field(proof, &["logIndex"])?, tree_size, leaf, &hashes);
+
+    if root.as_deref() != Some(root_hash.as_slice()) {
+        return Err(Error::BadSignature);
+    }
+
+    let envelope = field(proof, &["checkpoint", "envelope"])
+        .and_then(Value::as_str)
+        .ok_or(Error::MalformedSignature)?;
+    verify_checkpoint(envelope, log, tree_size, &root_hash)?;
+
+    Ok((integrated_time, body))
+}
+
+/// Check that a `hashedrekord` entry records this artifact digest, signature and certificate
+fn verify_entry_body(
+    body: &[u8],
+    digest: &[u8],
+    signature: &[u8],
+    leaf: &Certificate,
+) -> Result<(), Error> {
+    let body: Value = serde_json::from_slice(body).map_err(|_| Error::MalformedSignature)?;
+
+    if field(&body, &["kind"]).and_then(Value::as_str) != Some("hashedrekord") {
+        return Err(Error::UnsupportedAlgorithm);
+    }
+
+    let hash = field(&body, &["spec", "data", "hash"]).ok_or(Error::MalformedSignature)?;
+    let recorded_digest = field(hash, &["value"]).and_then(Value::as_str);
+    let recorded_signature = bytes_field(&body, &["spec", "signature", "content"])?;
+    let recorded_cert = bytes_field(&body, &["spec", "signature", "publicKey", "content"])?;
+
+    if field(hash, &["algorithm"]).and_then(Value::as_str) != Some("sha256")
+        || recorded_digest != Some(hex(digest).as_str())
+        || recorded_signature != signature
+        || Certificate::from_pem(&recorded_cert).ok().as_ref() != Some(leaf)
+    {
+        return Err(Error::BadSignature);
+    }
+
+    Ok(())
+}
+
+/// Verify the file at `path` with a Sigstore bundle, offline, against the trust anchors in `trusted_root`
+pub(crate) fn verify_bundle(
+    path: &Path,
+    bundle: &Path,
+    trusted_root: &Path,
+) -> Result<Certificate, Error> {
+    let root = TrustedRoot::load(trusted_root)?;
+    let data = std::fs::read(bundle).map_err(Error::IoError)?;
+    let bundle: Value = serde_json::from_slice(&data).map_err(|_| Error::MalformedSignature)?;
+
+    // v0.3 bundles hold a single certificate, earlier versions a chain starting with the leaf
+    let material = field(&bundle, &["verificationMaterial"]).ok_or(Error::MalformedSignature)?;
+    let leaf = bytes_field(material, &["certificate", "rawBytes"]).or_else(|_| {
+        field(material, &["x509CertificateChain", "certificates"])
+            .and_then(Value::as_array)
+            .and_then(|certs| certs.first())
+            .ok_or(Error::MalformedSignature)
+            .and_then(|cert| bytes_field(cert, &["rawBytes"]))
+    })?;
+    let leaf = Certificate::from_der(&leaf).map_err(|_| Error::MalformedSignature)?;
+
+    let message = field(&bundle, &["messageSignature"]).ok_or(Error::UnsupportedAlgorithm)?; // DSSE envelopes aren't supported
+    if field(message, &["messageDigest", "algorithm"]).and_then(Value::as_str) != Some("SHA2_256") {
+        return Err(Error::UnsupportedAlgorithm);
+    }
+    let signature = bytes_field(message, &["signature"])?;
+
+    let mut file = std::fs::File::open(path).map_err(Error::IoError)?;
+    let digest = HashAlgorithm::Sha256
+        .digest_reader(&mut file)
+        .map_err(Error::IoError)?;
+
+    if bytes_field(message, &["messageDigest", "digest"])? != digest {
+        return Err(Error::BadSignature);
+    }
+
+    verify_signature(&leaf, HashAlgorithm::Sha256, &digest, &signature)?;
+
+    let entries = field(material, &["tlogEntries"])
+        .and_then(Value::as_array)
+        .filter(|entries| !entries.is_empty())
+        .ok_or(Error::Unsigned)?;
+
+    for entry in entries {
+        let (integrated_time, body) = verify_tlog_entry(entry, &root.tlogs)?;
+        verify_entry_body(&body, &digest, &signature, &leaf)?;
+
+        // Fulcio certificates are short lived, they must have been valid when the entry was logged
+        let issuer = verify_chain(&leaf, &root, integrated_time)?;
+        verify_scts(&leaf, issuer, &root.ctlogs)?;
+    }
+
+    Ok(leaf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{testdata, TempFile};
+
+    #[test]
+    fn test_inclusion_proof_root() {
+        let leaf = |i: u8| HashAlgorithm::Sha256.digest(&[0, i]);
+        let node = |l: &[u8], r: &[u8]| HashAlgorithm::Sha256.digest(&[&[1], l, r].concat());
+
+        // A tree of 3 leaves: ((0, 1), 2)
+        let root = node(&node(&leaf(0), &leaf(1)), &leaf(2));
+
+        assert_eq!(
+            inclusion_proof_root(2, 3, leaf(2), &[node(&leaf(0), &leaf(1))]),
+            Some(root.clone())
+        );
+        assert_eq!(
+            inclusion_proof_root(1, 3, leaf(1), &[leaf(0), leaf(2)]),
+            Some(root)
+        );
+        assert_eq!(inclusion_proof_root(3, 3, leaf(2), &[]), None);
+    }
+
+    #[test]
+    fn test_valid_for() {
+        assert_eq!(
+            parse_timestamp("2021-01-12T11:53:27.000Z"),
+            Some(1_610_452_407)
+        );
+        assert_eq!(parse_timestamp("2021-01-12T11:53:27Z"), Some(1_610_452_407));
+        assert_eq!(parse_timestamp("2021-01-12T11:53:27.000+01:00"), None);
+
+        let trusted_root: Value =
+            serde_json::from_slice(&std::fs::read(testdata("trusted_root.json")).unwrap()).unwrap();
+
+        // The entry was logged in 2026, after each of these keys was retired
+        let expired = ["tlogs", "ctlogs", "certificateAuthorities"].map(|name| {
+            let mut root = trusted_root.clone();
+            let entry = &mut root[name][0];
+            let valid_for = match name {
+                "certificateAuthorities" => &mut entry["validFor"],
+                _ => &mut entry["publicKey"]["validFor"],
+            };
+            valid_for["end"] = Value::from("2025-01-01T00:00:00.000Z");
+            TempFile::new("trusted_root.json", root.to_string())
+        });
+
+        for root in &expired {
+            assert!(matches!(
+                verify_bundle(
+                    &testdata("module.ko"),
+                    &testdata("module.ko.sigstore.json"),
+                    root
+                ),
+                Err(Error::UnknownSigner)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_origin() {
+        let mut root: Value =
+            serde_json::from_slice(&std::fs::read(testdata("trusted_root.json")).unwrap()).unwrap();
+
+        // The checkpoint of the entry names the origin of another log
+        root["tlogs"][0]["baseUrl"] = Value::from("https://rekor.other.example.com");
+        let other_origin = TempFile::new("trusted_root.json", root.to_string());
+        assert!(matches!(
+            verify_bundle(
+                &testdata("module.ko"),
+                &testdata("module.ko.sigstore.json"),
+                &other_origin
+            ),
+            Err(Error::BadSignature)
+        ));
+    }
+}
@@ -0,0 +1,264 @@
+use super::crypto::HashAlgorithm;
+use crate::{Error, SignerIdentity};
+use signature::Verifier;
+use ssh_key::{HashAlg, PublicKey, SshSig};
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+const MAGIC_PREAMBLE: &[u8] = b"SSHSIG";
+
+/// An entry of an `allowed_signers` file (see `ssh-keygen(1)`)
+struct AllowedSigner {
+    principals: Vec<String>,
+    namespaces: Option<String>,
+    valid_after: Option<SystemTime>,
+    valid_before: Option<SystemTime>,
+    key: PublicKey,
+}
+
+/// Split the next field off `line`, which ends at whitespace outside of double quotes
+fn next_field(line: &str) -> (&str, &str) {
+    let mut quoted = false;
+
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => return (&line[..i], line[i..].trim_start()),
+            _ => {}
+        }
+    }
+
+    (line, "")
+}
+
+/// Split a comma separated list, keeping commas inside double quotes
+fn split_list(list: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut quoted = false;
+    let mut start = 0;
+
+    for (i, c) in list.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                items.push(&list[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    items.push(&list[start..]);
+    items
+}
+
+/// Match `s` against a wildcard pattern, where `*` matches any sequence and `?` any character
+fn match_pattern(pattern: &[u8], s: &[u8]) -> bool {
+    match (pattern.split_first(), s.split_first()) {
+        (Some((b'*', rest)), _) => {
+            match_pattern(rest, s) || (!s.is_empty() && match_pattern(pattern, &s[1..]))
+        }
+        (Some((b'?', rest)), Some((_, s))) => match_pattern(rest, s),
+        (Some((p, rest)), Some((c, s))) => p == c && match_pattern(rest, s),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Match `s` against a comma separated list of patterns, a negated (`!`) match always fails
+fn match_pattern_list(list: &str, s: &str) -> bool {
+    let mut matched = false;
+
+    for pattern in list.split(',') {
+        match pattern.strip_prefix('!') {
+            Some(negated) if match_pattern(negated.as_bytes(), s.as_bytes()) => return false,
+            Some(_) => {}
+            None => matched |= match_pattern(pattern.as_bytes(), s.as_bytes()),
+        }
+    }
+
+    matched
+}
+
+/// Parse a YYYYMMDD[HHMM[SS]][Z] timestamp, which is always treated as UTC
+fn parse_time(time: &str) -> Option<SystemTime> {
+    let time = time.strip_suffix('Z').unwrap_or(time);
+
+    if !time.bytes().all(|c| c.is_ascii_digit()) || ![8, 12, 14].contains(&time.len()) {
+        return None;
+    }
+
+    let field = |range: std::ops::Range<usize>| time.get(range).map_or(Ok(0), str::parse::<u64>);
+    let (year, month, day) = (field(0..4).ok()?, field(4..6).ok()?, field(6..8).ok()?);
+    let (hour, minute, second) = (field(8..10).ok()?, field(10..12).ok()?, field(12..14).ok()?);
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || year < 1970 {
+        return None;
+    }
+
+    // Days since the epoch from a civil date, counting years from March
+    let (year, month) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let days =
+        year * 365 + year / 4 - year / 100 + year / 400 + (153 * month + 2) / 5 + day - 1 - 719_468;
+
+    let seconds = days * 86_400 + hour * 3_600 + minute * 60 + second;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+impl AllowedSigner {
+    /// Parse a line: the principals, optional options and the public key
+    fn parse(line: &str) -> Option<Self> {
+        let (principals, rest) = next_field(line);
+
+        let (options, key) = if let Ok(key) = PublicKey::from_openssh(rest) {
+            ("", key)
+        } else {
+            let (options, rest) = next_field(rest);
+            (options, PublicKey::from_openssh(rest).ok()?)
+        };
+
+        let mut signer = AllowedSigner {
+            principals: principals
+                .trim_matches('"')
+                .split(',')
+                .map(str::to_string)
+                .collect(),
+            namespaces: None,
+            valid_after: None,
+            valid_before: None,
+            key,
+        };
+
+        for option in split_list(options)
+            .into_iter()
+            .filter(|option| !option.is_empty())
+        {
+            let (name, value) = option.split_once('=').unwrap_or((option, ""));
+            let value = value.trim_matches('"');
+
+            match name.to_ascii_lowercase().as_str() {
+                "namespaces" => signer.namespaces = Some(value.to_string()),
+                "valid-after" => signer.valid_after = Some(parse_time(value)?),
+                "valid-before" => signer.valid_before = Some(parse_time(value)?),
+                _ => return None, // Certificate authorities aren't supported
+            }
+        }
+
+        Some(signer)
+    }
+
+    fn permits(&self, signature: &SshSig, now: SystemTime) -> bool {
+        self.key.key_data() == signature.public_key()
+            && self
+                .namespaces
+                .as_ref()
+                .is_none_or(|namespaces| match_pattern_list(namespaces, signature.namespace()))
+            && self.valid_after.is_none_or(|after| after <= now)
+            && self.valid_before.is_none_or(|before| now <= before)
+    }
+}
+
+/// Append an SSH wire format string
+fn put_string(data: &mut Vec<u8>, value: &[u8]) {
+    #[allow(clippy::cast_possible_truncation)]
+    data.extend_from_slice(&(value.len() as u32).to_be_bytes());
+    data.extend_from_slice(value);
+}
+
+/// Verify `path` with an armored SSH signature made for `namespace`,
+/// by a key listed for that namespace in the `allowed_signers` file
+pub(crate) fn verify_file(
+    path: &Path,
+    signature: &Path,
+    allowed_signers: &Path,
+    namespace: &str,
+) -> Result<SignerIdentity, Error> {
+    let signature = std::fs::read(signature).map_err(Error::IoError)?;
+    let signature = SshSig::from_pem(signature).map_err(|_| Error::MalformedSignature)?;
+
+    // A signature made for a different purpose must not be accepted
+    if signature.namespace() != namespace {
+        return Err(Error::BadSignature);
+    }
+
+    let allowed_signers = std::fs::read_to_string(allowed_signers).map_err(Error::IoError)?;
+    let now = SystemTime::now();
+
+    // The key may be listed on several lines, as `ssh-keygen -Y find-principals` does every
+    // principal listed for it is reported, the signature doesn't name one
+    let mut principals: Vec<String> = Vec::new();
+    for signer in allowed_signers
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(AllowedSigner::parse)
+        .filter(|signer| signer.permits(&signature, now))
+    {
+        for principal in signer.principals {
+            if !principals.contains(&principal) {
+                principals.push(principal);
+            }
+        }
+    }
+
+    if principals.is_empty() {
+        return Err(Error::UnknownSigner);
+    }
+
+    let hash = match signature.hash_alg() {
+        HashAlg::Sha256 => HashAlgorithm::Sha256,
+        HashAlg::Sha512 => HashAlgorithm::Sha512,
+        _ => return Err(Error::UnsupportedAlgorithm),
+    };
+
+    let mut file = std::fs::File::open(path).map_err(Error::IoError)?;
+    let digest = hash.digest_reader(&mut file).map_err(Error::IoError)?;
+
+    // The signed data (PROTOCOL.sshsig), built here so the file doesn't have to be held in memory
+    let mut signed_data = MAGIC_PREAMBLE.to_vec();
+    put_string(&mut signed_data, namespace.as_bytes());
+    put_string(&mut signed_data, signature.reserved());
+    put_string(&mut signed_data, signature.hash_alg().as_str().as_bytes());
+    put_string(&mut signed_data, &digest);
+
+    signature
+        .public_key()
+        .verify(&signed_data, signature.signature())
+        .map_err(|_| Error::BadSignature)?;
+
+    Ok(SignerIdentity::Ssh {
+        principals,
+        fingerprint: signature
+            .public_key()
+            .fingerprint(HashAlg::Sha256)
+            .to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_pattern_list() {
+        assert!(match_pattern_list("file,git", "git"));
+        assert!(match_pattern_list("*@example.com", "alice@example.com"));
+        assert!(match_pattern_list("release-?", "release-1"));
+        assert!(!match_pattern_list("*,!git", "git"));
+        assert!(!match_pattern_list("file", "files"));
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("19700101"), Some(SystemTime::UNIX_EPOCH));
+        assert_eq!(
+            parse_time("20261018171503Z"),
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_792_343_703))
+        );
+        assert_eq!(parse_time("2026101"), None);
+    }
+}
@@ -0,0 +1,329 @@
+use super::crypto::{verify_certificate, HashAlgorithm};
+use super::pe::Image;
+use super::pkcs7;
+use super::Context;
+use crate::{hex, Error, SignerIdentity};
+use der::asn1::{AnyRef, ObjectIdentifier, OctetStringRef};
+use der::{Decode, Reader, SliceReader};
+use std::convert::TryInto;
+use std::path::Path;
+use x509_cert::spki::AlgorithmIdentifierRef;
+use x509_cert::Certificate;
+
+const SPC_INDIRECT_DATA: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.6.1.4.1.311.2.1.4");
+
+/// `EFI_CERT_X509_GUID` and `EFI_CERT_SHA256_GUID`, in their mixed endian encoding
+const EFI_CERT_X509: [u8; 16] = [
+    0xa1, 0x59, 0xc0, 0xa5, 0xe4, 0x94, 0xa7, 0x4a, 0x87, 0xb5, 0xab, 0x15, 0x5c, 0x2b, 0xf0, 0x72,
+];
+const EFI_CERT_SHA256: [u8; 16] = [
+    0x26, 0x16, 0xc4, 0xc1, 0x4c, 0x50, 0x92, 0x40, 0xac, 0xa9, 0x41, 0xf9, 0x36, 0x93, 0x43, 0x28,
+];
+
+/// The longest certificate chain followed from a signer to the signature database
+const MAX_CHAIN_LENGTH: usize = 8;
+
+/// A signature database (`db` or `dbx`): the certificates and image digests it lists
+#[derive(Default)]
+struct SignatureDatabase {
+    certificates: Vec<Certificate>,
+    sha256: Vec<Vec<u8>>,
+}
+
+impl SignatureDatabase {
+    /// Parse a sequence of `EFI_SIGNATURE_LIST`. Entries of other types (e.g. `EFI_CERT_X509_SHA256`)
+    /// are skipped.
+    fn parse(mut data: &[u8]) -> Result<Self, Error> {
+        let mut database = SignatureDatabase::default();
+
+        while !data.is_empty() {
+            // Type, list size, header size, entry size, header and the entries
+            let header = data.get(..28).ok_or(Error::MalformedSignature)?;
+            let le32 = |offset: usize| {
+                u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap()) as usize
+            };
+            let (list_size, header_size, entry_size) = (le32(16), le32(20), le32(24));
+
+            let list = data
+                .get(28..list_size)
+                .and_then(|list| list.get(header_size..))
+                .ok_or(Error::MalformedSignature)?;
+            if entry_size <= 16 || list.len() % entry_size != 0 {
+                return Err(Error::MalformedSignature);
+            }
+
+            // Each entry starts with its owner GUID
+            for entry in list.chunks(entry_size).map(|entry| &entry[16..]) {
+                match header[..16].try_into().unwrap() {
+                    EFI_CERT_X509 => database
+                        .certificates
+                        .push(Certificate::from_der(entry).map_err(|_| Error::MalformedSignature)?),
+                    EFI_CERT_SHA256 => database.sha256.push(entry.to_vec()),
+                    _ => {}
+                }
+            }
+
+            data = &data[list_size..];
+        }
+
+        Ok(database)
+    }
+
+    /// Load a signature database from an `.esl` file, or from an efivarfs variable
+    /// (e.g. `/sys/firmware/efi/efivars/db-d719b2cb-3d3a-4596-a3bc-dad00e67656f`), which starts
+    /// with the variable attributes
+    fn load(path: &Path) -> Result<Self, Error> {
+        let data = std::fs::read(path).map_err(Error::IoError)?;
+
+        Self::parse(&data).or_else(|_| Self::parse(data.get(4..).unwrap_or_default()))
+    }
+
+    /// Whether the database lists `cert`, or a certificate that issued it. Revoking a certificate
+    /// in `dbx` also revokes what it issued.
+    fn trusts(&self, cert: &Certificate) -> bool {
+        self.certificates.iter().any(|trusted| {
+            trusted == cert
+                || (trusted.tbs_certificate.subject == cert.tbs_certificate.issuer
+                    && verify_certificate(cert, trusted).is_ok())
+        })
+    }
+}
+
+/// The component name and generation of each line of an SBAT CSV, as found in the `.sbat`
+/// section or in the `SbatLevel` variable
+fn sbat_generations(data: &[u8]) -> Vec<(String, u32)> {
+    let data = data.split(|&c| c == 0).next().unwrap_or_default();
+
+    String::from_utf8_lossy(data)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(',');
+            let component = fields.next()?.trim();
+            let generation = fields.next()?.trim().parse().ok()?;
+            Some((component.to_string(), generation))
+        })
+        .collect()
+}
+
+/// Check the SBAT section of an image against an SBAT level (e.g. `SbatLevelRT`): shim refuses
+/// images with a component generation lower than the level, and images without SBAT data
+fn verify_sbat(image: &Image<'_>, level: &Path) -> Result<(), Error> {
+    let level = std::fs::read(level).map_err(Error::IoError)?;
+
+    // The efivarfs variable starts with its attributes
+    let level = if level.starts_with(b"sbat,") {
+        &level[..]
+    } else {
+        level.get(4..).unwrap_or_default()
+    };
+
+    let generations = sbat_generations(image.section(".sbat").ok_or(Error::Revoked)?);
+    if generations.is_empty() {
+        return Err(Error::Revoked);
+    }
+
+    for (component, minimum) in sbat_generations(level) {
+        if generations
+            .iter()
+            .any(|(name, generation)| *name == component && *generation < minimum)
+        {
+            return Err(Error::Revoked);
+        }
+    }
+
+    Ok(())
+}
+
+/// The digest an Authenticode `SpcIndirectDataContent` (without its tag and length) signs
+fn signed_digest(content: &[u8]) -> Result<(HashAlgorithm, Vec<u8>), Error> {
+    let mut reader = SliceReader::new(content).map_err(|_| Error::MalformedSignature)?;
+
+    let (algorithm, digest) = reader
+        .decode::<AnyRef<'_>>()
+        .and_then(|_| {
+            reader.sequence(|digest_info| {
+                Ok((
+                    AlgorithmIdentifierRef::decode(digest_info)?.oid,
+                    OctetStringRef::decode(digest_info)?.as_bytes().to_vec(),
+                ))
+            })
+        })
+        .map_err(|_| Error::MalformedSignature)?;
+
+    Ok((HashAlgorithm::from_oid(algorithm)?, digest))
+}
+
+/// Follow the issuers of `signer` through the certificates of its signature
+fn chain(signer: Certificate, certificates: &[Certificate]) -> Vec<Certificate> {
+    let mut chain = vec![signer];
+
+    while chain.len() < MAX_CHAIN_LENGTH {
+        let cert = chain.last().unwrap();
+        let Some(issuer) = certificates.iter().find(|issuer| {
+            *issuer != cert
+                && issuer.tbs_certificate.subject == cert.tbs_certificate.issuer
+                && verify_certificate(cert, issuer).is_ok()
+        }) else {
+            break;
+        };
+
+        if chain.contains(issuer) {
+            break;
+        }
+        chain.push(issuer.clone());
+    }
+
+    chain
+}
+
+/// Verify an EFI image the way firmware does with Secure Boot enabled: the image is refused
+/// when its digest, any certificate of a signature chain or the issuer of one is in `dbx`, and
+/// allowed when a signature chains to a certificate in `db` or its SHA-256 digest is in `db`.
+/// Certificate validity periods are ignored, as firmware has no trusted time.
+/// With an SBAT level, the `.sbat` section is checked as shim does.
+/// Returns the signer allowed by `db`, or the image digest when only the digest is.
+pub(crate) fn verify_image(
+    path: &Path,
+    db: &Path,
+    dbx: &Path,
+    sbat_level: Option<&Path>,
+) -> Result<Context, Error> {
+    let data = std::fs::read(path).map_err(Error::IoError)?;
+
+    verify_image_data(
+        &data,
+        &SignatureDatabase::load(db)?,
+        &SignatureDatabase::load(dbx)?,
+        sbat_level,
+    )
+}
+
+/// Verify the Authenticode signatures of a PE image against the `trusted` certificates, as
+/// `verify_image` does with the certificates as `db` and an empty `dbx`
+pub(crate) fn verify_authenticode(
+    data: &[u8],
+    trusted: Vec<Certificate>,
+) -> Result<Context, Error> {
+    let db = SignatureDatabase {
+        certificates: trusted,
+        sha256: Vec::new(),
+    };
+
+    verify_image_data(data, &db, &SignatureDatabase::default(), None)
+}
+
+fn verify_image_data(
+    data: &[u8],
+    db: &SignatureDatabase,
+    dbx: &SignatureDatabase,
+    sbat_level: Option<&Path>,
+) -> Result<Context, Error> {
+    let image = Image::parse(data)?;
+
+    let sha256 = image.authenticode_digest(HashAlgorithm::Sha256)?;
+    if dbx.sha256.contains(&sha256) {
+        return Err(Error::Revoked);
+    }
+
+    let mut result = Err(Error::Unsigned);
+
+    for signature in image.signatures()? {
+        let (signer, content) = pkcs7::verify_embedded(signature, SPC_INDIRECT_DATA)?;
+        let (hash, digest) = signed_digest(&content)?;
+
+        if image.authenticode_digest(hash)? != digest {
+            return Err(Error::BadSignature);
+        }
+
+        let mut chain = chain(signer, &pkcs7::certificates(signature)?);
+        if chain.iter().any(|cert| dbx.trusts(cert)) {
+            return Err(Error::Revoked);
+        }
+
+        if result.is_err() {
+            result = if chain.iter().any(|cert| db.trusts(cert)) {
+                Ok(Context::new(chain.swap_remove(0)))
+            } else {
+                Err(Error::UnknownSigner)
+            };
+        }
+    }
+
+    if result.is_err() && db.sha256.contains(&sha256) {
+        result = Ok(Context::Key(SignerIdentity::ImageDigest {
+            sha256: hex(&sha256),
+        }));
+    }
+
+    if let (Ok(_), Some(level)) = (&result, sbat_level) {
+        verify_sbat(&image, level)?;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{testdata, TempFile};
+
+    #[test]
+    fn test_sbat_generations() {
+        let section =
+            b"sbat,1,SBAT Version,sbat,1,https://github.com/rhboot/shim/blob/main/SBAT.md\n\
+            shim,4,UEFI shim,shim,1,https://github.com/rhboot/shim\n\0\0\0";
+        let level = b"sbat,1,2024010900\nshim,4\ngrub,3\n";
+
+        assert_eq!(
+            sbat_generations(section),
+            [("sbat".to_string(), 1), ("shim".to_string(), 4)]
+        );
+        assert_eq!(sbat_generations(level).len(), 3);
+    }
+
+    #[test]
+    fn test_revoked() {
+        let image = testdata("bootx64.efi");
+        let db = testdata("db.esl");
+
+        // A dbx listing the image digest
+        let data = std::fs::read(&image).unwrap();
+        let digest = Image::parse(&data)
+            .unwrap()
+            .authenticode_digest(HashAlgorithm::Sha256)
+            .unwrap();
+        let mut list = EFI_CERT_SHA256.to_vec();
+        for value in [76u32, 0, 48] {
+            list.extend_from_slice(&value.to_le_bytes());
+        }
+        list.extend_from_slice(&[0; 16]);
+        list.extend_from_slice(&digest);
+        let dbx = TempFile::new("dbx.esl", list);
+
+        // An SBAT level above the image's "example,2"
+        let level = TempFile::new("sbat-level.csv", b"sbat,1,2026101800\nexample,3\n");
+
+        let revoked = verify_image(&image, &db, &dbx, None);
+        // The signature doesn't include the CA that issued its certificate
+        let issuer = verify_image(&image, &db, &db, None);
+        let sbat = verify_image(&image, &db, &testdata("dbx.esl"), Some(&level));
+
+        assert!(matches!(revoked, Err(Error::Revoked)));
+        assert!(matches!(issuer, Err(Error::Revoked)));
+        assert!(matches!(sbat, Err(Error::Revoked)));
+    }
+
+    #[test]
+    fn test_authenticode() {
+        let db = testdata("db.esl");
+        let data = std::fs::read(testdata("bootx64.efi")).unwrap();
+        let empty = TempFile::new("empty.esl", b"");
+
+        let image = verify_image(&testdata("bootx64.efi"), &db, &empty, None).unwrap();
+        let ctx =
+            verify_authenticode(&data, SignatureDatabase::load(&db).unwrap().certificates).unwrap();
+        assert_eq!(ctx.sha256_thumbprint(), image.sha256_thumbprint());
+
+        assert!(verify_authenticode(&data, Vec::new()).is_err());
+    }
+}
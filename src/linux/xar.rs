@@ -0,0 +1,218 @@
+use super::crypto::{load_keyring, verify_chain, verify_signature, HashAlgorithm};
+use crate::{hex, Error};
+use base64::Engine;
+use der::Decode;
+use flate2::read::ZlibDecoder;
+use std::convert::{TryFrom, TryInto};
+use std::io::Read;
+use std::path::Path;
+use x509_cert::Certificate;
+
+const XAR_MAGIC: &[u8] = b"xar!";
+const HEADER_SIZE: usize = 28;
+
+/// The checksum algorithm named by a `style` attribute. `none` and `md5` aren't supported.
+fn hash_algorithm(style: Option<&str>) -> Result<HashAlgorithm, Error> {
+    match style.map(str::to_ascii_lowercase).as_deref() {
+        Some("sha1") => Ok(HashAlgorithm::Sha1),
+        Some("sha256") => Ok(HashAlgorithm::Sha256),
+        Some("sha384") => Ok(HashAlgorithm::Sha384),
+        Some("sha512") => Ok(HashAlgorithm::Sha512),
+        _ => Err(Error::UnsupportedAlgorithm),
+    }
+}
+
+fn be64(data: &[u8]) -> u64 {
+    u64::from_be_bytes(data.try_into().unwrap())
+}
+
+/// The text of the child element `name` of `node`
+fn child_text<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|child| child.has_tag_name(name))
+        .and_then(|child| child.text())
+        .map(str::trim)
+}
+
+/// The heap range described by the `offset` and `size` children of `node`
+fn heap_range<'a>(heap: &'a [u8], node: roxmltree::Node<'_, '_>) -> Result<&'a [u8], Error> {
+    let field = |name| {
+        child_text(node, name)
+            .and_then(|value| value.parse::<usize>().ok())
+            .ok_or(Error::MalformedSignature)
+    };
+    let offset = field("offset")?;
+    let end = offset
+        .checked_add(field("size")?)
+        .ok_or(Error::MalformedSignature)?;
+
+    heap.get(offset..end).ok_or(Error::MalformedSignature)
+}
+
+/// Check the checksum element `name` of a file's `data` element over `content`
+fn verify_checksum(data: roxmltree::Node<'_, '_>, name: &str, content: &[u8]) -> Result<(), Error> {
+    let checksum = data
+        .children()
+        .find(|child| child.has_tag_name(name))
+        .ok_or(Error::MalformedSignature)?;
+    let hash = hash_algorithm(checksum.attribute("style"))?;
+
+    let expected = checksum.text().map(str::trim).unwrap_or_default();
+    let digest = hex(&hash.digest(content));
+
+    if !digest.eq_ignore_ascii_case(expected) {
+        return Err(Error::BadSignature);
+    }
+
+    Ok(())
+}
+
+/// Check the archived checksum of a file in the heap, and its extracted checksum when it is
+/// stored or zlib compressed (`application/x-gzip` in xar). The extracted length is bounded by
+/// the `length` element.
+fn verify_file(data: roxmltree::Node<'_, '_>, heap: &[u8]) -> Result<(), Error> {
+    let archived = heap_range(heap, data)?;
+    verify_checksum(data, "archived-checksum", archived)?;
+
+    let encoding = data
+        .children()
+        .find(|child| child.has_tag_name("encoding"))
+        .and_then(|encoding| encoding.attribute("style"));
+    let length = child_text(data, "length")
+        .and_then(|value| value.parse::<u64>().ok())
+        .ok_or(Error::MalformedSignature)?;
+
+    let extracted = match encoding {
+        None | Some("application/octet-stream") => archived.to_vec(),
+        Some("application/x-gzip") => {
+            let mut extracted = Vec::new();
+            ZlibDecoder::new(archived)
+                .take(length)
+                .read_to_end(&mut extracted)
+                .map_err(|_| Error::MalformedSignature)?;
+            extracted
+        }
+        // The archived checksum is signed, so other encodings (bzip2, lzma) are still covered
+        Some(_) => return Ok(()),
+    };
+
+    if u64::try_from(extracted.len()).ok() != Some(length) {
+        return Err(Error::BadSignature);
+    }
+
+    verify_checksum(data, "extracted-checksum", &extracted)
+}
+
+/// Verify a xar archive: the RSA signature over the table of contents checksum, the chain of its
+/// signing certificate to one of the `trusted` certificates, and the checksum of every file in
+/// the heap. Returns the signing certificate.
+pub(crate) fn verify_archive(data: &[u8], trusted: &[Certificate]) -> Result<Certificate, Error> {
+    let header = data.get(..HEADER_SIZE).ok_or(Error::MalformedSignature)?;
+    if &header[..4] != XAR_MAGIC {
+        return Err(Error::MalformedSignature);
+    }
+
+    let header_size = usize::from(u16::from_be_bytes([header[4], header[5]]));
+    let toc_start = header_size.max(HEADER_SIZE);
+    let heap_start = usize::try_from(be64(&header[8..16]))
+        .ok()
+        .and_then(|size| toc_start.checked_add(size))
+        .ok_or(Error::MalformedSignature)?;
+    let toc_compressed = data
+        .get(toc_start..heap_start)
+        .ok_or(Error::MalformedSignature)?;
+    let heap = &data[heap_start..];
+
+    let mut toc = String::new();
+    ZlibDecoder::new(toc_compressed)
+        .take(be64(&header[16..24]))
+        .read_to_string(&mut toc)
+        .map_err(|_| Error::MalformedSignature)?;
+    let document = roxmltree::Document::parse(&toc).map_err(|_| Error::MalformedSignature)?;
+    let toc = document
+        .root_element()
+        .children()
+        .find(|node| node.has_tag_name("toc"))
+        .ok_or(Error::MalformedSignature)?;
+
+    let checksum = toc
+        .children()
+        .find(|node| node.has_tag_name("checksum"))
+        .ok_or(Error::Unsigned)?;
+    let hash = hash_algorithm(checksum.attribute("style"))?;
+    let digest = hash.digest(toc_compressed);
+    if heap_range(heap, checksum)? != digest.as_slice() {
+        return Err(Error::BadSignature);
+    }
+
+    let signature = toc
+        .children()
+        .find(|node| node.has_tag_name("signature"))
+        .ok_or(Error::Unsigned)?;
+    if signature.attribute("style") != Some("RSA") {
+        return Err(Error::UnsupportedAlgorithm);
+    }
+
+    let certificates = signature
+        .descendants()
+        .filter(|node| node.has_tag_name("X509Certificate"))
+        .map(|node| {
+            let der: String = node.text().unwrap_or_default().split_whitespace().collect();
+            base64::engine::general_purpose::STANDARD
+                .decode(der)
+                .ok()
+                .and_then(|der| Certificate::from_der(&der).ok())
+                .ok_or(Error::MalformedSignature)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let leaf = certificates.first().ok_or(Error::LeafCertNotFound)?;
+
+    // The checksum itself is signed, as the digest of the signature
+    verify_signature(leaf, hash, &digest, heap_range(heap, signature)?)?;
+    verify_chain(leaf, &certificates, trusted)?;
+
+    for data in toc
+        .descendants()
+        .filter(|node| node.has_tag_name("file"))
+        .filter_map(|file| file.children().find(|child| child.has_tag_name("data")))
+    {
+        verify_file(data, heap)?;
+    }
+
+    Ok(leaf.clone())
+}
+
+/// Verify the flat installer package (`.pkg`) at `path` against the certificates in `anchors`
+pub(crate) fn verify_package(path: &Path, anchors: &Path) -> Result<Certificate, Error> {
+    let data = std::fs::read(path).map_err(Error::IoError)?;
+    let trusted = load_keyring(anchors)?;
+
+    verify_archive(&data, &trusted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::testdata;
+
+    #[test]
+    fn test_tampered_package() {
+        let data = std::fs::read(testdata("installer.pkg")).unwrap();
+        let trusted = load_keyring(&testdata("pkg_root.pem")).unwrap();
+
+        assert!(verify_archive(&data, &trusted).is_ok());
+
+        // The last byte is in the compressed payload
+        let mut payload = data.clone();
+        *payload.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            verify_archive(&payload, &trusted),
+            Err(Error::BadSignature)
+        ));
+
+        // The table of contents starts after the header
+        let mut toc = data;
+        toc[HEADER_SIZE + 8] ^= 1;
+        assert!(verify_archive(&toc, &trusted).is_err());
+    }
+}
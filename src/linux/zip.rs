@@ -0,0 +1,211 @@
+use crate::Error;
+use flate2::read::DeflateDecoder;
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::io::Read;
+
+const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const EOCD_SIZE: usize = 22;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+fn le16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap())
+}
+
+fn le32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// An entry of the central directory
+pub(crate) struct Entry {
+    pub name: String,
+    method: u16,
+    compressed_size: usize,
+    uncompressed_size: usize,
+    local_header_offset: usize,
+}
+
+/// A ZIP archive held in memory. ZIP64 archives and archives with duplicate names aren't supported.
+pub(crate) struct Archive<'a> {
+    data: &'a [u8],
+    pub eocd_offset: usize,
+    pub central_directory_offset: usize,
+    pub entries: Vec<Entry>,
+}
+
+impl<'a> Archive<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < EOCD_SIZE {
+            return Err(Error::MalformedSignature);
+        }
+
+        // The end of central directory record is followed by a comment of up to 64K
+        let search_start = data.len().saturating_sub(EOCD_SIZE + usize::from(u16::MAX));
+        let eocd_offset = (search_start..=data.len().saturating_sub(EOCD_SIZE))
+            .rev()
+            .find(|&offset| {
+                le32(data, offset) == EOCD_SIGNATURE
+                    && offset + EOCD_SIZE + usize::from(le16(data, offset + 20)) == data.len()
+            })
+            .ok_or(Error::MalformedSignature)?;
+
+        let entry_count = usize::from(le16(data, eocd_offset + 10));
+        let central_directory_size = le32(data, eocd_offset + 12) as usize;
+        let central_directory_offset = le32(data, eocd_offset + 16) as usize;
+
+        if central_directory_offset.saturating_add(central_directory_size) != eocd_offset {
+            return Err(Error::MalformedSignature);
+        }
+
+        // Each central directory header takes at least 46 bytes
+        let mut entries = Vec::with_capacity(entry_count.min(central_directory_size / 46));
+        let mut offset = central_directory_offset;
+
+        for _ in 0..entry_count {
+            let header = data
+                .get(offset..offset + 46)
+                .filter(|header| le32(header, 0) == CENTRAL_HEADER_SIGNATURE)
+                .ok_or(Error::MalformedSignature)?;
+
+            let name_len = usize::from(le16(header, 28));
+            let extra_len = usize::from(le16(header, 30));
+            let comment_len = usize::from(le16(header, 32));
+            let name = data
+                .get(offset + 46..offset + 46 + name_len)
+                .ok_or(Error::MalformedSignature)?;
+
+            entries.push(Entry {
+                name: String::from_utf8(name.to_vec()).map_err(|_| Error::MalformedSignature)?,
+                method: le16(header, 10),
+                compressed_size: le32(header, 20) as usize,
+                uncompressed_size: le32(header, 24) as usize,
+                local_header_offset: le32(header, 42) as usize,
+            });
+
+            offset += 46 + name_len + extra_len + comment_len;
+        }
+
+        // Readers disagree on which of the entries with the same name they extract
+        let mut names = HashSet::new();
+        if !entries
+            .iter()
+            .all(|entry| names.insert(entry.name.as_str()))
+        {
+            return Err(Error::MalformedSignature);
+        }
+
+        Ok(Archive {
+            data,
+            eocd_offset,
+            central_directory_offset,
+            entries,
+        })
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    /// Read the uncompressed contents of an entry
+    pub fn read(&self, entry: &Entry) -> Result<Vec<u8>, Error> {
+        let offset = entry.local_header_offset;
+        let header = self
+            .data
+            .get(offset..offset + 30)
+            .filter(|header| le32(header, 0) == LOCAL_HEADER_SIGNATURE)
+            .ok_or(Error::MalformedSignature)?;
+
+        // The local header sizes may be left out, the central directory ones are used
+        let start = offset + 30 + usize::from(le16(header, 26)) + usize::from(le16(header, 28));
+        let compressed = self
+            .data
+            .get(start..start.saturating_add(entry.compressed_size))
+            .ok_or(Error::MalformedSignature)?;
+
+        // The sizes come from the archive, the allocation is bounded by the archive length
+        let mut contents = Vec::with_capacity(entry.uncompressed_size.min(self.data.len()));
+
+        match entry.method {
+            METHOD_STORED => contents.extend_from_slice(compressed),
+            METHOD_DEFLATED => {
+                DeflateDecoder::new(compressed)
+                    .take(entry.uncompressed_size as u64)
+                    .read_to_end(&mut contents)
+                    .map_err(|_| Error::MalformedSignature)?;
+            }
+            _ => return Err(Error::UnsupportedAlgorithm),
+        }
+
+        if contents.len() != entry.uncompressed_size {
+            return Err(Error::MalformedSignature);
+        }
+
+        Ok(contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    /// An archive of empty stored entries
+    fn archive(names: &[&str]) -> Vec<u8> {
+        let (mut data, mut central_directory) = (Vec::new(), Vec::new());
+
+        for name in names {
+            let mut local = LOCAL_HEADER_SIGNATURE.to_le_bytes().to_vec();
+            local.extend_from_slice(&[0; 22]);
+            local.extend_from_slice(&u16::try_from(name.len()).unwrap().to_le_bytes());
+            local.extend_from_slice(&[0; 2]);
+
+            let mut header = CENTRAL_HEADER_SIGNATURE.to_le_bytes().to_vec();
+            header.extend_from_slice(&[0; 24]);
+            header.extend_from_slice(&u16::try_from(name.len()).unwrap().to_le_bytes());
+            header.extend_from_slice(&[0; 12]);
+            header.extend_from_slice(&u32::try_from(data.len()).unwrap().to_le_bytes());
+            header.extend_from_slice(name.as_bytes());
+            central_directory.extend_from_slice(&header);
+
+            data.extend_from_slice(&local);
+            data.extend_from_slice(name.as_bytes());
+        }
+
+        let offset = u32::try_from(data.len()).unwrap();
+        data.extend_from_slice(&central_directory);
+        data.extend_from_slice(&EOCD_SIGNATURE.to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        for _ in 0..2 {
+            data.extend_from_slice(&u16::try_from(names.len()).unwrap().to_le_bytes());
+        }
+        data.extend_from_slice(
+            &u32::try_from(central_directory.len())
+                .unwrap()
+                .to_le_bytes(),
+        );
+        data.extend_from_slice(&offset.to_le_bytes());
+        data.extend_from_slice(&[0; 2]);
+        data
+    }
+
+    #[test]
+    fn test_duplicate_names() {
+        let data = archive(&["META-INF/MANIFEST.MF", "classes.dex"]);
+        let parsed = Archive::parse(&data).unwrap();
+        assert_eq!(parsed.entries.len(), 2);
+        assert!(parsed
+            .read(parsed.find("classes.dex").unwrap())
+            .unwrap()
+            .is_empty());
+
+        let data = archive(&["classes.dex", "META-INF/MANIFEST.MF", "classes.dex"]);
+        assert!(matches!(
+            Archive::parse(&data),
+            Err(Error::MalformedSignature)
+        ));
+    }
+}
@@ -0,0 +1,593 @@
+use crate::Error;
+use std::collections::BTreeMap;
+use std::convert::{TryFrom, TryInto};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+const FAT_MAGIC: u32 = 0xcafe_babe;
+const FAT_MAGIC_64: u32 = 0xcafe_babf;
+const MH_MAGIC: u32 = 0xfeed_face;
+const MH_MAGIC_64: u32 = 0xfeed_facf;
+const LC_CODE_SIGNATURE: u32 = 0x1d;
+
+#[cfg(target_arch = "aarch64")]
+const CPU_TYPE_HOST: u32 = 0x0100_000c; // CPU_TYPE_ARM64
+#[cfg(not(target_arch = "aarch64"))]
+const CPU_TYPE_HOST: u32 = 0x0100_0007; // CPU_TYPE_X86_64
+
+const CSMAGIC_EMBEDDED_SIGNATURE: u32 = 0xfade_0cc0;
+const CSMAGIC_CODEDIRECTORY: u32 = 0xfade_0c02;
+const CSMAGIC_EMBEDDED_LAUNCH_CONSTRAINT: u32 = 0xfade_8181;
+
+const CSSLOT_CODEDIRECTORY: u32 = 0;
+const CSSLOT_ALTERNATE_CODEDIRECTORIES: u32 = 0x1000;
+const CSSLOT_ALTERNATE_CODEDIRECTORY_LIMIT: u32 = 5;
+const CSSLOT_LAUNCH_CONSTRAINT_SELF: u32 = 8;
+const CSSLOT_LAUNCH_CONSTRAINT_PARENT: u32 = 9;
+const CSSLOT_LAUNCH_CONSTRAINT_RESPONSIBLE: u32 = 10;
+const CSSLOT_LIBRARY_CONSTRAINT: u32 = 11;
+
+const CS_HASHTYPE_SHA1: u8 = 1;
+const CS_HASHTYPE_SHA256: u8 = 2;
+const CS_HASHTYPE_SHA256_TRUNCATED: u8 = 3;
+const CS_HASHTYPE_SHA384: u8 = 4;
+
+///
+/// A value decoded from the DER (or property list) representation Apple uses for
+/// entitlements and constraints
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValue {
+    Boolean(bool),
+    Integer(i64),
+    String(String),
+    Data(Vec<u8>),
+    Date(std::time::SystemTime),
+    Array(Vec<PropertyValue>),
+    Dictionary(BTreeMap<String, PropertyValue>),
+}
+
+///
+/// A launch or library constraint, a lightweight code requirement
+///
+/// # Fields
+///
+/// `category`: the constraint category (`ccat`), if the constraint is one of Apple's predefined
+/// categories
+///
+/// `compatibility_version`: the version of the constraint format (`comp`)
+///
+/// `version`: the version of the constraint itself (`vers`)
+///
+/// `requirements`: the facts the process must match (`reqs`), such as `team-identifier`
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constraint {
+    pub category: Option<i64>,
+    pub compatibility_version: i64,
+    pub version: i64,
+    pub requirements: BTreeMap<String, PropertyValue>,
+}
+
+impl Constraint {
+    fn from_value(value: PropertyValue) -> Result<Self, Error> {
+        let PropertyValue::Dictionary(mut dict) = value else {
+            return Err(Error::MalformedSignature);
+        };
+
+        let mut integer = |key: &str| match dict.remove(key) {
+            Some(PropertyValue::Integer(value)) => Ok(Some(value)),
+            Some(_) => Err(Error::MalformedSignature),
+            None => Ok(None),
+        };
+
+        let category = integer("ccat")?;
+        let compatibility_version = integer("comp")?.ok_or(Error::MalformedSignature)?;
+        let version = integer("vers")?.ok_or(Error::MalformedSignature)?;
+
+        let requirements = match dict.remove("reqs") {
+            Some(PropertyValue::Dictionary(requirements)) => requirements,
+            Some(_) => return Err(Error::MalformedSignature),
+            None => BTreeMap::new(),
+        };
+
+        Ok(Constraint {
+            category,
+            compatibility_version,
+            version,
+            requirements,
+        })
+    }
+}
+
+///
+/// The launch and library constraints embedded in a code signature
+///
+/// # Fields
+///
+/// `self_constraint`: constraints on the process itself
+///
+/// `parent_constraint`: constraints on the parent process
+///
+/// `responsible_constraint`: constraints on the responsible process
+///
+/// `library_constraint`: constraints on the libraries the process may load
+///
+#[derive(Debug, Default, PartialEq)]
+pub struct LaunchConstraints {
+    pub self_constraint: Option<Constraint>,
+    pub parent_constraint: Option<Constraint>,
+    pub responsible_constraint: Option<Constraint>,
+    pub library_constraint: Option<Constraint>,
+}
+
+impl LaunchConstraints {
+    /// Read the constraint blobs from the signature of the Mach-O file at `path`.
+    /// For universal binaries the slice for the host architecture is used.
+    ///
+    /// The file is read again after its signature was verified, so it may have been replaced in
+    /// between. The blobs are only trusted when their hashes are in the special slots of the
+    /// code directory whose hash is `cdhash`, the one that was verified.
+    pub(crate) fn from_file(path: &Path, cdhash: &[u8]) -> Result<Self, Error> {
+        let mut file = std::fs::File::open(path).map_err(Error::IoError)?;
+
+        let Some(superblob) = read_code_signature(&mut file)? else {
+            return Err(Error::BadSignature);
+        };
+
+        Self::from_superblob(&superblob, cdhash)
+    }
+
+    fn from_superblob(superblob: &[u8], cdhash: &[u8]) -> Result<Self, Error> {
+        let entries = superblob_entries(superblob)?;
+
+        let code_directory = entries
+            .iter()
+            .filter(|(slot, _)| {
+                *slot == CSSLOT_CODEDIRECTORY
+                    || (CSSLOT_ALTERNATE_CODEDIRECTORIES
+                        ..CSSLOT_ALTERNATE_CODEDIRECTORIES + CSSLOT_ALTERNATE_CODEDIRECTORY_LIMIT)
+                        .contains(slot)
+            })
+            .map(|(_, blob)| *blob)
+            .find(|blob| {
+                be_u32(blob, 0).ok() == Some(CSMAGIC_CODEDIRECTORY)
+                    && blob
+                        .get(37)
+                        .and_then(|hash_type| code_hash(*hash_type, blob).ok())
+                        .is_some_and(|hash| hash.get(..cdhash.len()) == Some(cdhash))
+            })
+            .ok_or(Error::BadSignature)?;
+
+        let mut constraints = LaunchConstraints::default();
+
+        for index in CSSLOT_LAUNCH_CONSTRAINT_SELF..=CSSLOT_LIBRARY_CONSTRAINT {
+            let constraint = match index {
+                CSSLOT_LAUNCH_CONSTRAINT_SELF => &mut constraints.self_constraint,
+                CSSLOT_LAUNCH_CONSTRAINT_PARENT => &mut constraints.parent_constraint,
+                CSSLOT_LAUNCH_CONSTRAINT_RESPONSIBLE => &mut constraints.responsible_constraint,
+                _ => &mut constraints.library_constraint,
+            };
+
+            let blob = entries
+                .iter()
+                .find(|(slot, _)| *slot == index)
+                .map(|(_, blob)| *blob);
+            let expected = special_slot_hash(code_directory, index)?;
+
+            let Some(blob) = blob else {
+                // A constraint listed in the code directory was stripped from the signature
+                if expected.iter().any(|byte| *byte != 0) {
+                    return Err(Error::BadSignature);
+                }
+                continue;
+            };
+
+            let hash = code_hash(code_directory[37], blob)?;
+            if expected.is_empty() || hash.get(..expected.len()) != Some(expected) {
+                return Err(Error::BadSignature);
+            }
+
+            if be_u32(blob, 0)? != CSMAGIC_EMBEDDED_LAUNCH_CONSTRAINT {
+                return Err(Error::MalformedSignature);
+            }
+
+            let value = decode_der(blob.get(8..).ok_or(Error::MalformedSignature)?)?;
+            *constraint = Some(Constraint::from_value(value)?);
+        }
+
+        Ok(constraints)
+    }
+}
+
+/// Hash `data` with a code directory hash type
+fn code_hash(hash_type: u8, data: &[u8]) -> Result<Vec<u8>, Error> {
+    use sha2::Digest;
+
+    Ok(match hash_type {
+        CS_HASHTYPE_SHA1 => sha1::Sha1::digest(data).to_vec(),
+        CS_HASHTYPE_SHA256 => sha2::Sha256::digest(data).to_vec(),
+        CS_HASHTYPE_SHA256_TRUNCATED => sha2::Sha256::digest(data)[..20].to_vec(),
+        CS_HASHTYPE_SHA384 => sha2::Sha384::digest(data).to_vec(),
+        _ => return Err(Error::UnsupportedAlgorithm),
+    })
+}
+
+/// The hash of special slot `index` of a code directory, empty if the code directory has fewer
+/// special slots. Special slots are stored before the code slots, in reverse order.
+fn special_slot_hash(code_directory: &[u8], index: u32) -> Result<&[u8], Error> {
+    let hash_offset = be_u32(code_directory, 16)? as usize;
+    let special_slots = be_u32(code_directory, 24)?;
+    let hash_size = usize::from(*code_directory.get(36).ok_or(Error::MalformedSignature)?);
+
+    if index > special_slots {
+        return Ok(&[]);
+    }
+
+    let start = hash_offset
+        .checked_sub(index as usize * hash_size)
+        .ok_or(Error::MalformedSignature)?;
+    code_directory
+        .get(start..start + hash_size)
+        .ok_or(Error::MalformedSignature)
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Result<u32, Error> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes(b.try_into().unwrap()))
+        .ok_or(Error::MalformedSignature)
+}
+
+fn le_u32(data: &[u8], offset: usize) -> Result<u32, Error> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or(Error::MalformedSignature)
+}
+
+/// Read `len` bytes at `offset`. The length comes from the file's headers, so it is checked
+/// against the file size before anything is allocated.
+fn read_at(file: &mut std::fs::File, offset: u64, len: usize) -> Result<Vec<u8>, Error> {
+    let file_len = file.metadata().map_err(Error::IoError)?.len();
+    let end = u64::try_from(len)
+        .ok()
+        .and_then(|len| offset.checked_add(len))
+        .ok_or(Error::MalformedSignature)?;
+    if end > file_len {
+        return Err(Error::MalformedSignature);
+    }
+
+    let mut buf = vec![0; len];
+    file.seek(SeekFrom::Start(offset)).map_err(Error::IoError)?;
+    file.read_exact(&mut buf).map_err(Error::IoError)?;
+    Ok(buf)
+}
+
+/// Find the offset of the Mach-O image for the host architecture
+fn slice_offset(file: &mut std::fs::File) -> Result<u64, Error> {
+    let header = read_at(file, 0, 8)?;
+
+    let (entry_size, offset_64) = match be_u32(&header, 0)? {
+        FAT_MAGIC => (20, false),   // struct fat_arch
+        FAT_MAGIC_64 => (32, true), // struct fat_arch_64
+        _ => return Ok(0),          // Not a universal binary
+    };
+
+    let count = be_u32(&header, 4)? as usize;
+    let archs = read_at(
+        file,
+        8,
+        count
+            .checked_mul(entry_size)
+            .ok_or(Error::MalformedSignature)?,
+    )?;
+
+    for arch in archs.chunks(entry_size) {
+        if be_u32(arch, 0)? != CPU_TYPE_HOST {
+            continue;
+        }
+
+        return if offset_64 {
+            Ok(u64::from(be_u32(arch, 8)?) << 32 | u64::from(be_u32(arch, 12)?))
+        } else {
+            Ok(u64::from(be_u32(arch, 8)?))
+        };
+    }
+
+    Err(Error::MalformedSignature)
+}
+
+/// Read the code signature `SuperBlob` referenced by `LC_CODE_SIGNATURE`, if there is one
+fn read_code_signature(file: &mut std::fs::File) -> Result<Option<Vec<u8>>, Error> {
+    let base = slice_offset(file)?;
+    let header = read_at(file, base, 28)?;
+
+    let header_size = match le_u32(&header, 0)? {
+        MH_MAGIC => 28,
+        MH_MAGIC_64 => 32,
+        _ => return Err(Error::MalformedSignature),
+    };
+
+    let size_of_cmds = le_u32(&header, 20)? as usize;
+    let cmds_offset = base
+        .checked_add(header_size)
+        .ok_or(Error::MalformedSignature)?;
+    let cmds = read_at(file, cmds_offset, size_of_cmds)?;
+
+    let mut offset = 0;
+    while offset < cmds.len() {
+        let cmd = le_u32(&cmds, offset)?;
+        let cmd_size = le_u32(&cmds, offset + 4)? as usize;
+
+        if cmd == LC_CODE_SIGNATURE {
+            // struct linkedit_data_command
+            let data_off = le_u32(&cmds, offset + 8)?;
+            let data_size = le_u32(&cmds, offset + 12)?;
+            let data_offset = base
+                .checked_add(u64::from(data_off))
+                .ok_or(Error::MalformedSignature)?;
+            return read_at(file, data_offset, data_size as usize).map(Some);
+        }
+
+        if cmd_size == 0 {
+            return Err(Error::MalformedSignature);
+        }
+        offset += cmd_size;
+    }
+
+    Ok(None)
+}
+
+/// Split a `SuperBlob` into its (slot, blob) entries
+fn superblob_entries(superblob: &[u8]) -> Result<Vec<(u32, &[u8])>, Error> {
+    if be_u32(superblob, 0)? != CSMAGIC_EMBEDDED_SIGNATURE {
+        return Err(Error::MalformedSignature);
+    }
+
+    let count = be_u32(superblob, 8)? as usize;
+    let mut entries = Vec::with_capacity(count);
+
+    for i in 0..count {
+        let slot = be_u32(superblob, 12 + i * 8)?;
+        let offset = be_u32(superblob, 16 + i * 8)? as usize;
+        let length = be_u32(superblob, offset + 4)? as usize;
+
+        let blob = superblob
+            .get(offset..offset + length)
+            .ok_or(Error::MalformedSignature)?;
+        entries.push((slot, blob));
+    }
+
+    Ok(entries)
+}
+
+/// Split the next DER element off `data`, returning its tag and contents
+fn der_next<'a>(data: &mut &'a [u8]) -> Result<(u8, &'a [u8]), Error> {
+    let tag = *data.first().ok_or(Error::MalformedSignature)?;
+    let first = *data.get(1).ok_or(Error::MalformedSignature)?;
+
+    let (len, header) = if first & 0x80 == 0 {
+        (usize::from(first), 2)
+    } else {
+        let octets = usize::from(first & 0x7f);
+        if octets == 0 || octets > 4 {
+            return Err(Error::MalformedSignature);
+        }
+
+        let len = data
+            .get(2..2 + octets)
+            .ok_or(Error::MalformedSignature)?
+            .iter()
+            .fold(0, |len, byte| len << 8 | usize::from(*byte));
+        (len, 2 + octets)
+    };
+
+    let contents = data
+        .get(header..header + len)
+        .ok_or(Error::MalformedSignature)?;
+    *data = &data[header + len..];
+
+    Ok((tag, contents))
+}
+
+fn der_children(mut data: &[u8]) -> Result<Vec<(u8, &[u8])>, Error> {
+    let mut children = Vec::new();
+    while !data.is_empty() {
+        children.push(der_next(&mut data)?);
+    }
+    Ok(children)
+}
+
+fn der_value(tag: u8, contents: &[u8]) -> Result<PropertyValue, Error> {
+    Ok(match tag {
+        0x01 => PropertyValue::Boolean(contents.iter().any(|b| *b != 0)),
+        0x02 => {
+            if contents.is_empty() || contents.len() > 8 {
+                return Err(Error::MalformedSignature);
+            }
+            // Sign extend the big endian two's complement value
+            let init = if contents[0] & 0x80 == 0 { 0 } else { -1 };
+            PropertyValue::Integer(
+                contents
+                    .iter()
+                    .fold(init, |val: i64, byte| val << 8 | i64::from(*byte)),
+            )
+        }
+        0x04 => PropertyValue::Data(contents.to_vec()),
+        0x0c => PropertyValue::String(
+            String::from_utf8(contents.to_vec()).map_err(|_| Error::MalformedSignature)?,
+        ),
+        0x30 => PropertyValue::Array(
+            der_children(contents)?
+                .into_iter()
+                .map(|(tag, contents)| der_value(tag, contents))
+                .collect::<Result<_, _>>()?,
+        ),
+        0xb0 => {
+            // [CONTEXT 16] holds a set of key/value sequences
+            let mut dict = BTreeMap::new();
+            for (tag, pair) in der_children(contents)? {
+                match (tag, der_children(pair)?.as_slice()) {
+                    (0x30, [(0x0c, key), (tag, value)]) => {
+                        let key = String::from_utf8(key.to_vec())
+                            .map_err(|_| Error::MalformedSignature)?;
+                        dict.insert(key, der_value(*tag, value)?);
+                    }
+                    _ => return Err(Error::MalformedSignature),
+                }
+            }
+            PropertyValue::Dictionary(dict)
+        }
+        _ => return Err(Error::MalformedSignature),
+    })
+}
+
+/// Decode a `CoreEntitlements` DER blob: `[APPLICATION 16] { INTEGER version, [CONTEXT 16] dict }`
+fn decode_der(mut data: &[u8]) -> Result<PropertyValue, Error> {
+    match der_next(&mut data)? {
+        (0x70, contents) => der_children(contents)?
+            .into_iter()
+            .find(|(tag, _)| *tag == 0xb0)
+            .ok_or(Error::MalformedSignature)
+            .and_then(|(tag, contents)| der_value(tag, contents)),
+        (tag, contents) => der_value(tag, contents),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // { "ccat": 0, "comp": 1, "reqs": { "team-identifier": "ABC" }, "vers": 1 }
+    const CONSTRAINT_DER: [u8; 74] = [
+        0x70, 0x48, 0x02, 0x01, 0x01, 0xb0, 0x43, 0x30, 0x09, 0x0c, 0x04, 0x63, 0x63, 0x61, 0x74,
+        0x02, 0x01, 0x00, 0x30, 0x09, 0x0c, 0x04, 0x63, 0x6f, 0x6d, 0x70, 0x02, 0x01, 0x01, 0x30,
+        0x20, 0x0c, 0x04, 0x72, 0x65, 0x71, 0x73, 0xb0, 0x18, 0x30, 0x16, 0x0c, 0x0f, 0x74, 0x65,
+        0x61, 0x6d, 0x2d, 0x69, 0x64, 0x65, 0x6e, 0x74, 0x69, 0x66, 0x69, 0x65, 0x72, 0x0c, 0x03,
+        0x41, 0x42, 0x43, 0x30, 0x09, 0x0c, 0x04, 0x76, 0x65, 0x72, 0x73, 0x02, 0x01, 0x01,
+    ];
+
+    #[test]
+    fn test_decode_der() {
+        let mut reqs = BTreeMap::new();
+        reqs.insert(
+            "team-identifier".to_string(),
+            PropertyValue::String("ABC".to_string()),
+        );
+
+        let mut expected = BTreeMap::new();
+        expected.insert("ccat".to_string(), PropertyValue::Integer(0));
+        expected.insert("comp".to_string(), PropertyValue::Integer(1));
+        expected.insert("reqs".to_string(), PropertyValue::Dictionary(reqs));
+        expected.insert("vers".to_string(), PropertyValue::Integer(1));
+
+        assert_eq!(
+            decode_der(&CONSTRAINT_DER).unwrap(),
+            PropertyValue::Dictionary(expected)
+        );
+    }
+
+    /// A SHA-256 code directory with 11 special slots holding `special_slots` (slot 1 first)
+    fn code_directory(special_slots: &[[u8; 32]; 11]) -> Vec<u8> {
+        let hash_offset = 44 + 11 * 32;
+        let mut cd = Vec::new();
+        for field in [
+            CSMAGIC_CODEDIRECTORY,
+            hash_offset,
+            0x0002_0001,
+            0,
+            hash_offset,
+            0,
+            11,
+            0,
+            0,
+        ] {
+            cd.extend_from_slice(&field.to_be_bytes());
+        }
+        cd.extend_from_slice(&[32, CS_HASHTYPE_SHA256, 0, 12, 0, 0, 0, 0]);
+        for hash in special_slots.iter().rev() {
+            cd.extend_from_slice(hash);
+        }
+        cd
+    }
+
+    fn superblob(blobs: &[(u32, &[u8])]) -> Vec<u8> {
+        let header_len = 12 + 8 * blobs.len();
+        let len = header_len + blobs.iter().map(|(_, blob)| blob.len()).sum::<usize>();
+
+        let u32 = |value: usize| u32::try_from(value).unwrap().to_be_bytes();
+
+        let mut superblob = CSMAGIC_EMBEDDED_SIGNATURE.to_be_bytes().to_vec();
+        superblob.extend_from_slice(&u32(len));
+        superblob.extend_from_slice(&u32(blobs.len()));
+        let mut offset = header_len;
+        for (slot, blob) in blobs {
+            superblob.extend_from_slice(&slot.to_be_bytes());
+            superblob.extend_from_slice(&u32(offset));
+            offset += blob.len();
+        }
+        for (_, blob) in blobs {
+            superblob.extend_from_slice(blob);
+        }
+        superblob
+    }
+
+    #[test]
+    fn test_constraint_slots() {
+        let mut constraint = CSMAGIC_EMBEDDED_LAUNCH_CONSTRAINT.to_be_bytes().to_vec();
+        let len = u32::try_from(8 + CONSTRAINT_DER.len()).unwrap();
+        constraint.extend_from_slice(&len.to_be_bytes());
+        constraint.extend_from_slice(&CONSTRAINT_DER);
+
+        let mut special_slots = [[0; 32]; 11];
+        special_slots[CSSLOT_LAUNCH_CONSTRAINT_PARENT as usize - 1]
+            .copy_from_slice(&code_hash(CS_HASHTYPE_SHA256, &constraint).unwrap());
+        let cd = code_directory(&special_slots);
+        let cdhash = &code_hash(CS_HASHTYPE_SHA256, &cd).unwrap()[..20];
+
+        let signed = superblob(&[
+            (CSSLOT_CODEDIRECTORY, &cd),
+            (CSSLOT_LAUNCH_CONSTRAINT_PARENT, &constraint),
+        ]);
+        let constraints = LaunchConstraints::from_superblob(&signed, cdhash).unwrap();
+
+        let mut requirements = BTreeMap::new();
+        requirements.insert(
+            "team-identifier".to_string(),
+            PropertyValue::String("ABC".to_string()),
+        );
+        assert_eq!(
+            constraints,
+            LaunchConstraints {
+                parent_constraint: Some(Constraint {
+                    category: Some(0),
+                    compatibility_version: 1,
+                    version: 1,
+                    requirements,
+                }),
+                ..LaunchConstraints::default()
+            }
+        );
+
+        // Another code directory than the verified one
+        assert!(matches!(
+            LaunchConstraints::from_superblob(&signed, &[0; 20]),
+            Err(Error::BadSignature)
+        ));
+
+        // The constraint doesn't match its hash, or was stripped
+        let mut tampered = constraint.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        for blobs in [
+            &[
+                (CSSLOT_CODEDIRECTORY, &cd[..]),
+                (CSSLOT_LAUNCH_CONSTRAINT_PARENT, &tampered[..]),
+            ][..],
+            &[(CSSLOT_CODEDIRECTORY, &cd[..])],
+        ] {
+            assert!(matches!(
+                LaunchConstraints::from_superblob(&superblob(blobs), cdhash),
+                Err(Error::BadSignature)
+            ));
+        }
+    }
+}
@@ -1,9 +1,15 @@
+use super::profile::property_value;
 use super::sec_sys::*;
-use crate::Name;
+use super::{LaunchConstraints, PropertyValue, ProvisioningProfile};
+use crate::{hex, Error, Name};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 
 pub(crate) struct Context {
     cert: SecCertificate,
     dict: CFDictionary<CFString, CFDictionary<CFString, CFType>>,
+    info: CFDictionary,
+    path: Option<PathBuf>,
 }
 
 enum SecProperty {
@@ -59,7 +65,7 @@ impl Into<CFString> for SecOID {
 }
 
 impl Context {
-    pub fn new(cert: SecCertificateRef) -> Self {
+    pub fn new(cert: SecCertificateRef, info: CFDictionary, path: Option<PathBuf>) -> Self {
         Context {
             cert: unsafe { SecCertificate::wrap_under_get_rule(cert) },
             dict: unsafe {
@@ -69,6 +75,17 @@ impl Context {
                     None,
                 ))
             },
+            info,
+            path,
+        }
+    }
+
+    fn get_info(&self, key: CFStringRef) -> Option<CFType> {
+        unsafe {
+            let key = CFString::wrap_under_get_rule(key);
+            self.info
+                .find(key.as_CFTypeRef())
+                .map(|value| CFType::wrap_under_get_rule(*value))
         }
     }
 
@@ -162,11 +179,7 @@ impl Context {
         };
 
         use sha1::Digest;
-        let hash = sha1::Sha1::digest(cert_data.bytes());
-
-        hash.as_slice()
-            .iter()
-            .fold(String::new(), |s, byte| s + &format!("{:02x}", byte))
+        hex(&sha1::Sha1::digest(cert_data.bytes()))
     }
 
     pub fn sha256_thumbprint(&self) -> String {
@@ -175,10 +188,39 @@ impl Context {
         };
 
         use sha2::Digest;
-        let hash = sha2::Sha256::digest(cert_data.bytes());
+        hex(&sha2::Sha256::digest(cert_data.bytes()))
+    }
+
+    pub fn has_extension(&self, oid: &str) -> bool {
+        self.dict.find(CFString::new(oid)).is_some()
+    }
+
+    pub fn launch_constraints(&self) -> Result<LaunchConstraints, Error> {
+        let path = unsafe { self.get_info(kSecCodeInfoMainExecutable) }
+            .and_then(|url| url.downcast::<CFURL>())
+            .and_then(|url| url.to_path())
+            .ok_or(Error::InvalidPath)?;
+
+        // The cdhash of the verified code directory, which lists the hashes of the constraints
+        let cdhash = unsafe { self.get_info(kSecCodeInfoUnique) }
+            .and_then(|unique| unique.downcast::<CFData>())
+            .ok_or(Error::MalformedSignature)?;
+
+        LaunchConstraints::from_file(&path, cdhash.bytes())
+    }
+
+    pub fn entitlements(&self) -> BTreeMap<String, PropertyValue> {
+        match unsafe { self.get_info(kSecCodeInfoEntitlementsDict) }
+            .as_ref()
+            .and_then(property_value)
+        {
+            Some(PropertyValue::Dictionary(entitlements)) => entitlements,
+            _ => BTreeMap::new(),
+        }
+    }
 
-        hash.as_slice()
-            .iter()
-            .fold(String::new(), |s, byte| s + &format!("{:02x}", byte))
+    pub fn provisioning_profile(&self) -> Result<Option<ProvisioningProfile>, Error> {
+        let path = self.path.as_ref().ok_or(Error::InvalidPath)?;
+        ProvisioningProfile::from_bundle(path)
     }
 }
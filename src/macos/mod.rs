@@ -1,12 +1,16 @@
+mod constraints;
 mod context;
+mod profile;
 #[allow(non_upper_case_globals)]
 mod sec_sys;
 
-use super::Error;
+use super::{hex, AdHocSignature, Error};
 use sec_sys::*;
 
 pub(crate) struct Verifier(SecCodeKind);
+pub use constraints::{Constraint, LaunchConstraints, PropertyValue};
 pub(crate) use context::Context;
+pub use profile::ProvisioningProfile;
 
 #[derive(Debug)]
 enum SecCodeKind {
@@ -41,7 +45,9 @@ impl Verifier {
         }
     }
 
-    /// Retrieve the code object for the file at the target location
+    /// Retrieve the code object for the file at the target location.
+    /// UDIF disk images are handled by the Security framework itself, it reads the signature
+    /// referenced by the "koly" trailer and verifies it over the image.
     pub fn for_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error> {
         let mut sec: SecStaticCodeRef = std::ptr::null_mut();
         let url = CFURL::from_path(path.as_ref(), false).ok_or(Error::InvalidPath)?;
@@ -61,8 +67,15 @@ impl Verifier {
     }
 
     pub fn verify(&self) -> Result<Context, Error> {
-        self.check_validity("anchor trusted")?; // This is the most generic verification
         let sec_info = self.get_code_singing_info()?;
+
+        if let Some(ad_hoc) = get_ad_hoc_signature(&sec_info) {
+            // There is no certificate to evaluate, only the integrity of the code can be checked
+            self.check_validity(None)?;
+            return Err(Error::AdHocSigned(ad_hoc));
+        }
+
+        self.check_validity(Some("anchor trusted"))?; // This is the most generic verification
         let cert_key = unsafe { CFString::wrap_under_get_rule(kSecCodeInfoCertificates) };
 
         let certs_ref = sec_info
@@ -72,7 +85,30 @@ impl Verifier {
         let certs = unsafe { CFArray::<SecCertificate>::wrap_under_get_rule(*certs_ref as _) };
         let leaf_cert = certs.get(0).ok_or(Error::LeafCertNotFound)?;
 
-        Ok(Context::new(leaf_cert.as_concrete_TypeRef()))
+        Ok(Context::new(
+            leaf_cert.as_concrete_TypeRef(),
+            sec_info,
+            self.get_path(),
+        ))
+    }
+
+    /// Retrieve the on disk location of the code, for bundles this is the bundle directory
+    fn get_path(&self) -> Option<std::path::PathBuf> {
+        let mut url: CFURLRef = std::ptr::null();
+
+        let sec = match &self.0 {
+            SecCodeKind::Static(sec) => sec.as_concrete_TypeRef(),
+            SecCodeKind::Dynamic(sec) => sec.as_CFTypeRef() as _,
+        };
+
+        unsafe {
+            match SecCodeCopyPath(sec, SecCSFlags::kSecCSDefaultFlags, Some(&mut url)) {
+                sec_sys::errSecSuccess if !url.is_null() => {
+                    CFURL::wrap_under_create_rule(url).to_path()
+                }
+                _ => None,
+            }
+        }
     }
 
     /// Retreive a dictionary of various pieces of information from a code signature.
@@ -87,7 +123,7 @@ impl Verifier {
         unsafe {
             match SecCodeCopySigningInformation(
                 sec,
-                SecCSFlags::kSecCSSigningInformation,
+                SecCSFlags::kSecCSSigningAndRequirementInformation,
                 Some(&mut dict),
             ) {
                 sec_sys::errSecSuccess if !dict.is_null() => {
@@ -98,37 +134,45 @@ impl Verifier {
         }
     }
 
-    fn check_validity(&self, requirement: &str) -> Result<(), Error> {
+    /// Check the integrity of the code, and that it satisfies `requirement` if one is given
+    fn check_validity(&self, requirement: Option<&str>) -> Result<(), Error> {
         let mut req: SecRequirementRef = std::ptr::null_mut();
         let mut err: CFErrorRef = std::ptr::null_mut();
 
         // Generate a new requirement object using the Apple [Code Signing Requirement Language](https://developer.apple.com/library/archive/documentation/Security/Conceptual/CodeSigningGuide/RequirementLang/RequirementLang.html#//apple_ref/doc/uid/TP40005929-CH5-SW1)
-        let req = unsafe {
-            match SecRequirementCreateWithStringAndErrors(
-                CFString::new(requirement).as_concrete_TypeRef(),
-                SecCSFlags::kSecCSDefaultFlags,
-                Some(&mut err),
-                Some(&mut req),
-            ) {
-                sec_sys::errSecSuccess if !req.is_null() => {
-                    SecRequirement::wrap_under_create_rule(req)
-                }
-                status => {
-                    if !err.is_null() {
-                        return Err(err.into());
-                    } else {
-                        return Err(Error::OsError(status));
+        let req = match requirement {
+            Some(requirement) => unsafe {
+                match SecRequirementCreateWithStringAndErrors(
+                    CFString::new(requirement).as_concrete_TypeRef(),
+                    SecCSFlags::kSecCSDefaultFlags,
+                    Some(&mut err),
+                    Some(&mut req),
+                ) {
+                    sec_sys::errSecSuccess if !req.is_null() => {
+                        Some(SecRequirement::wrap_under_create_rule(req))
+                    }
+                    status => {
+                        if !err.is_null() {
+                            return Err(err.into());
+                        } else {
+                            return Err(Error::OsError(status));
+                        }
                     }
                 }
-            }
+            },
+            None => None,
         };
 
+        let req = req
+            .as_ref()
+            .map_or(std::ptr::null(), TCFType::as_concrete_TypeRef);
+
         let status = match &self.0 {
             SecCodeKind::Static(sec) => unsafe {
                 SecStaticCodeCheckValidityWithErrors(
                     sec.as_concrete_TypeRef(),
                     SecCSFlags::kSecCSDefaultFlags,
-                    req.as_concrete_TypeRef(),
+                    req,
                     Some(&mut err),
                 )
             },
@@ -136,7 +180,7 @@ impl Verifier {
                 SecCodeCheckValidityWithErrors(
                     sec.as_concrete_TypeRef(),
                     SecCSFlags::kSecCSDefaultFlags,
-                    req.as_concrete_TypeRef(),
+                    req,
                     Some(&mut err),
                 )
             },
@@ -156,6 +200,28 @@ impl Verifier {
     }
 }
 
+/// Extract the cdhash and flags if the signing information describes an ad-hoc signature
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn get_ad_hoc_signature(sec_info: &CFDictionary) -> Option<AdHocSignature> {
+    let flags_key = unsafe { CFString::wrap_under_get_rule(kSecCodeInfoFlags) };
+    let unique_key = unsafe { CFString::wrap_under_get_rule(kSecCodeInfoUnique) };
+
+    let flags_ref = sec_info.find(flags_key.as_CFTypeRef())?;
+    let flags = unsafe { CFNumber::wrap_under_get_rule(*flags_ref as _) }.to_i64()? as u32;
+
+    if flags & kSecCodeSignatureAdhoc == 0 {
+        return None;
+    }
+
+    let cdhash = sec_info
+        .find(unique_key.as_CFTypeRef())
+        .map(|unique| unsafe { CFData::wrap_under_get_rule(*unique as _) })
+        .map(|unique| hex(unique.bytes()))
+        .unwrap_or_default();
+
+    Some(AdHocSignature { cdhash, flags })
+}
+
 impl From<CFErrorRef> for Error {
     fn from(err: CFErrorRef) -> Self {
         if err.is_null() {
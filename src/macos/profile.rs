@@ -0,0 +1,431 @@
+use super::sec_sys::*;
+use super::PropertyValue;
+use crate::Error;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+const CF_ABSOLUTE_TIME_EPOCH: f64 = 978_307_200.0; // 2001-01-01 00:00:00 UTC
+
+/// The certificate Apple signs provisioning profiles with, for iOS and macOS alike
+const PROFILE_SIGNING_COMMON_NAME: &str = "Apple iPhone OS Provisioning Profile Signing";
+
+/// SHA-256 fingerprint of the Apple Root CA certificate
+const APPLE_ROOT_CA_SHA256: [u8; 32] = [
+    0xb0, 0xb1, 0x73, 0x0e, 0xcb, 0xc7, 0xff, 0x45, 0x05, 0x14, 0x2c, 0x49, 0xf1, 0x29, 0x5e, 0x6e,
+    0xda, 0x6b, 0xca, 0xed, 0x7e, 0x2c, 0x68, 0xc5, 0xbe, 0x91, 0xb5, 0xa1, 0x10, 0x01, 0xf0, 0x24,
+];
+
+///
+/// A provisioning profile embedded in an application bundle
+///
+/// # Fields
+///
+/// `team_identifiers`: the `TeamIdentifier` array
+///
+/// `expiration_date`: the `ExpirationDate` of the profile, a profile without one is expired
+///
+/// `provisioned_devices`: the device UDIDs the profile is limited to
+///
+/// `provisions_all_devices`: set for enterprise and Developer ID profiles
+///
+/// `entitlements`: the entitlements the profile permits
+///
+#[derive(Debug, PartialEq)]
+pub struct ProvisioningProfile {
+    pub name: Option<String>,
+    pub uuid: Option<String>,
+    pub team_identifiers: Vec<String>,
+    pub expiration_date: Option<SystemTime>,
+    pub provisioned_devices: Vec<String>,
+    pub provisions_all_devices: bool,
+    pub entitlements: BTreeMap<String, PropertyValue>,
+}
+
+impl ProvisioningProfile {
+    /// Read the embedded profile from a macOS (`Contents/embedded.provisionprofile`)
+    /// or iOS (`embedded.mobileprovision`) bundle, if there is one
+    pub(crate) fn from_bundle(bundle: &Path) -> Result<Option<Self>, Error> {
+        for name in &[
+            "Contents/embedded.provisionprofile",
+            "embedded.mobileprovision",
+        ] {
+            let path = bundle.join(name);
+            if path.is_file() {
+                let data = std::fs::read(path).map_err(Error::IoError)?;
+                return Self::from_data(&data).map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Verify the CMS signature of the profile and parse the signed property list
+    fn from_data(data: &[u8]) -> Result<Self, Error> {
+        let content = decode_signed_content(data)?;
+
+        let (plist, _) = create_with_data(content, kCFPropertyListImmutable)
+            .map_err(|_| Error::MalformedSignature)?;
+        let plist = unsafe { CFType::wrap_under_create_rule(plist) };
+
+        let Some(PropertyValue::Dictionary(mut dict)) = property_value(&plist) else {
+            return Err(Error::MalformedSignature);
+        };
+
+        let strings = |value: Option<PropertyValue>| match value {
+            Some(PropertyValue::Array(values)) => values
+                .into_iter()
+                .filter_map(|v| match v {
+                    PropertyValue::String(s) => Some(s),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Ok(ProvisioningProfile {
+            name: match dict.remove("Name") {
+                Some(PropertyValue::String(name)) => Some(name),
+                _ => None,
+            },
+            uuid: match dict.remove("UUID") {
+                Some(PropertyValue::String(uuid)) => Some(uuid),
+                _ => None,
+            },
+            team_identifiers: strings(dict.remove("TeamIdentifier")),
+            expiration_date: match dict.remove("ExpirationDate") {
+                Some(PropertyValue::Date(date)) => Some(date),
+                _ => None,
+            },
+            provisioned_devices: strings(dict.remove("ProvisionedDevices")),
+            provisions_all_devices: matches!(
+                dict.remove("ProvisionsAllDevices"),
+                Some(PropertyValue::Boolean(true))
+            ),
+            entitlements: match dict.remove("Entitlements") {
+                Some(PropertyValue::Dictionary(entitlements)) => entitlements,
+                _ => BTreeMap::new(),
+            },
+        })
+    }
+
+    /// Check whether the profile has passed its expiration date, or has none
+    #[must_use]
+    pub fn is_expired(&self) -> bool {
+        !self
+            .expiration_date
+            .is_some_and(|date| date >= SystemTime::now())
+    }
+
+    /// List the keys of `entitlements` that this profile does not permit.
+    /// Wildcard values such as `TEAMID.*` in the profile match any value with that prefix,
+    /// or each value of an array. A profile value of `*` permits any value.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use codesign_verify::CodeSignVerifier;
+    ///
+    /// let ctx = CodeSignVerifier::for_file("/Applications/Example.app").unwrap().verify().unwrap();
+    /// let profile = ctx.provisioning_profile().unwrap().unwrap();
+    /// assert!(profile.unpermitted_entitlements(&ctx.entitlements()).is_empty());
+    /// ```
+    #[must_use]
+    pub fn unpermitted_entitlements(
+        &self,
+        entitlements: &BTreeMap<String, PropertyValue>,
+    ) -> Vec<String> {
+        entitlements
+            .iter()
+            .filter(|(key, wanted)| {
+                !self
+                    .entitlements
+                    .get(*key)
+                    .is_some_and(|allowed| permits(allowed, wanted))
+            })
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+}
+
+fn permits(allowed: &PropertyValue, wanted: &PropertyValue) -> bool {
+    match (allowed, wanted) {
+        (PropertyValue::String(allowed), _) if allowed == "*" => true,
+        (PropertyValue::String(allowed), PropertyValue::String(wanted)) => {
+            match allowed.strip_suffix('*') {
+                Some(prefix) => wanted.starts_with(prefix),
+                None => allowed == wanted,
+            }
+        }
+        (PropertyValue::Array(allowed), PropertyValue::Array(wanted)) => {
+            wanted.iter().all(|w| allowed.iter().any(|a| permits(a, w)))
+        }
+        (PropertyValue::Array(allowed), wanted) => allowed.iter().any(|a| permits(a, wanted)),
+        (PropertyValue::String(_), PropertyValue::Array(wanted)) => {
+            wanted.iter().all(|w| permits(allowed, w))
+        }
+        (PropertyValue::Dictionary(allowed), PropertyValue::Dictionary(wanted)) => wanted
+            .iter()
+            .all(|(key, w)| allowed.get(key).is_some_and(|a| permits(a, w))),
+        (PropertyValue::Boolean(true), PropertyValue::Boolean(_)) => true,
+        (allowed, wanted) => allowed == wanted,
+    }
+}
+
+/// Decode a CMS message, verify that Apple's provisioning profile signing certificate signed it
+/// and return the content
+fn decode_signed_content(data: &[u8]) -> Result<CFData, Error> {
+    let mut decoder: CMSDecoderRef = std::ptr::null_mut();
+
+    let decoder = unsafe {
+        match CMSDecoderCreate(Some(&mut decoder)) {
+            errSecSuccess if !decoder.is_null() => CMSDecoder::wrap_under_create_rule(decoder),
+            err => return Err(Error::OsError(err)),
+        }
+    };
+
+    let status = unsafe {
+        CMSDecoderUpdateMessage(
+            decoder.as_concrete_TypeRef(),
+            data.as_ptr().cast(),
+            data.len(),
+        )
+    };
+    if status != errSecSuccess {
+        return Err(Error::OsError(status));
+    }
+
+    let status = unsafe { CMSDecoderFinalizeMessage(decoder.as_concrete_TypeRef()) };
+    if status != errSecSuccess {
+        return Err(Error::OsError(status));
+    }
+
+    let mut signers = 0;
+    let status = unsafe { CMSDecoderGetNumSigners(decoder.as_concrete_TypeRef(), &mut signers) };
+    if status != errSecSuccess {
+        return Err(Error::OsError(status));
+    }
+
+    if signers == 0 {
+        return Err(Error::Unsigned);
+    }
+
+    let policy = unsafe { CFType::wrap_under_create_rule(SecPolicyCreateBasicX509()) };
+    let mut signer_status = kCMSSignerUnsigned;
+    let mut cert_status = errSecSuccess;
+    let mut trust: CFTypeRef = std::ptr::null();
+
+    let status = unsafe {
+        CMSDecoderCopySignerStatus(
+            decoder.as_concrete_TypeRef(),
+            0,
+            policy.as_CFTypeRef(),
+            1, // Evaluate the trust of the signer certificate
+            &mut signer_status,
+            Some(&mut trust),
+            &mut cert_status,
+        )
+    };
+    let trust = (!trust.is_null()).then(|| unsafe { CFType::wrap_under_create_rule(trust) });
+
+    match (status, signer_status) {
+        (errSecSuccess, kCMSSignerValid) => {}
+        (errSecSuccess, kCMSSignerInvalidSignature) => return Err(Error::BadSignature),
+        // The trust evaluation failed, `cert_status` holds its result code when there is one
+        (errSecSuccess, kCMSSignerInvalidCert) if cert_status != errSecSuccess => {
+            return Err(Error::OsError(cert_status))
+        }
+        (errSecSuccess, kCMSSignerInvalidCert) => return Err(Error::UnknownSigner),
+        (errSecSuccess, kCMSSignerUnsigned) => return Err(Error::Unsigned),
+        (errSecSuccess, _) => return Err(Error::MalformedSignature),
+        (err, _) => return Err(Error::OsError(err)),
+    }
+
+    // Any certificate trusted by the system passes the basic X.509 policy, so the chain must
+    // end in the Apple Root CA and start with the profile signing certificate
+    let trust = trust.ok_or(Error::UnknownSigner)?;
+    let (leaf, anchor) = unsafe {
+        let count = SecTrustGetCertificateCount(trust.as_CFTypeRef());
+        if count < 2 {
+            return Err(Error::UnknownSigner);
+        }
+
+        (
+            SecCertificate::wrap_under_get_rule(SecTrustGetCertificateAtIndex(
+                trust.as_CFTypeRef(),
+                0,
+            )),
+            SecCertificate::wrap_under_get_rule(SecTrustGetCertificateAtIndex(
+                trust.as_CFTypeRef(),
+                count - 1,
+            )),
+        )
+    };
+
+    let anchor_data = unsafe {
+        CFData::wrap_under_create_rule(SecCertificateCopyData(anchor.as_concrete_TypeRef()))
+    };
+    let leaf_name = unsafe {
+        let summary = SecCertificateCopySubjectSummary(leaf.as_concrete_TypeRef());
+        (!summary.is_null()).then(|| CFString::wrap_under_create_rule(summary).to_string())
+    };
+
+    use sha2::Digest;
+    if sha2::Sha256::digest(anchor_data.bytes()).as_slice() != APPLE_ROOT_CA_SHA256
+        || leaf_name.as_deref() != Some(PROFILE_SIGNING_COMMON_NAME)
+    {
+        return Err(Error::UnknownSigner);
+    }
+
+    let mut content: CFDataRef = std::ptr::null();
+
+    unsafe {
+        match CMSDecoderCopyContent(decoder.as_concrete_TypeRef(), Some(&mut content)) {
+            errSecSuccess if !content.is_null() => Ok(CFData::wrap_under_create_rule(content)),
+            err => Err(Error::OsError(err)),
+        }
+    }
+}
+
+/// Convert a property list object into a `PropertyValue`, unsupported types are skipped
+pub(crate) fn property_value(value: &CFType) -> Option<PropertyValue> {
+    if let Some(b) = value.downcast::<CFBoolean>() {
+        Some(PropertyValue::Boolean(b.into()))
+    } else if let Some(n) = value.downcast::<CFNumber>() {
+        n.to_i64().map(PropertyValue::Integer)
+    } else if let Some(s) = value.downcast::<CFString>() {
+        Some(PropertyValue::String(s.to_string()))
+    } else if let Some(d) = value.downcast::<CFData>() {
+        Some(PropertyValue::Data(d.bytes().to_vec()))
+    } else if let Some(d) = value.downcast::<CFDate>() {
+        let secs = d.abs_time() + CF_ABSOLUTE_TIME_EPOCH;
+        Some(PropertyValue::Date(
+            SystemTime::UNIX_EPOCH + Duration::from_secs_f64(secs.max(0.0)),
+        ))
+    } else if let Some(a) = value.downcast::<CFArray>() {
+        Some(PropertyValue::Array(
+            a.iter()
+                .filter_map(|v| property_value(&unsafe { CFType::wrap_under_get_rule(*v) }))
+                .collect(),
+        ))
+    } else if let Some(d) = value.downcast::<CFDictionary>() {
+        let (keys, values) = d.get_keys_and_values();
+        let mut dict = BTreeMap::new();
+
+        for (key, value) in keys.into_iter().zip(values) {
+            let key = unsafe { CFType::wrap_under_get_rule(key) };
+            let value = unsafe { CFType::wrap_under_get_rule(value) };
+
+            if let (Some(key), Some(value)) = (key.downcast::<CFString>(), property_value(&value)) {
+                dict.insert(key.to_string(), value);
+            }
+        }
+
+        Some(PropertyValue::Dictionary(dict))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(s: &str) -> PropertyValue {
+        PropertyValue::String(s.to_string())
+    }
+
+    #[test]
+    fn test_is_expired() {
+        let mut profile = ProvisioningProfile {
+            name: None,
+            uuid: None,
+            team_identifiers: Vec::new(),
+            expiration_date: None,
+            provisioned_devices: Vec::new(),
+            provisions_all_devices: false,
+            entitlements: BTreeMap::new(),
+        };
+        assert!(profile.is_expired());
+
+        profile.expiration_date = Some(SystemTime::now() + Duration::from_secs(3600));
+        assert!(!profile.is_expired());
+
+        profile.expiration_date = Some(SystemTime::now() - Duration::from_secs(3600));
+        assert!(profile.is_expired());
+    }
+
+    #[test]
+    fn test_unpermitted_entitlements() {
+        let mut allowed = BTreeMap::new();
+        allowed.insert("application-identifier".to_string(), string("TEAM.*"));
+        allowed.insert(
+            "keychain-access-groups".to_string(),
+            PropertyValue::Array(vec![string("TEAM.*"), string("com.apple.token")]),
+        );
+        allowed.insert("get-task-allow".to_string(), PropertyValue::Boolean(false));
+        allowed.insert(
+            "com.apple.developer.associated-domains".to_string(),
+            string("*"),
+        );
+        allowed.insert(
+            "com.apple.security.application-groups".to_string(),
+            string("group.TEAM.*"),
+        );
+
+        let profile = ProvisioningProfile {
+            name: None,
+            uuid: None,
+            team_identifiers: vec!["TEAM".to_string()],
+            expiration_date: None,
+            provisioned_devices: Vec::new(),
+            provisions_all_devices: true,
+            entitlements: allowed,
+        };
+
+        let mut wanted = BTreeMap::new();
+        wanted.insert(
+            "application-identifier".to_string(),
+            string("TEAM.com.example"),
+        );
+        wanted.insert(
+            "keychain-access-groups".to_string(),
+            PropertyValue::Array(vec![string("TEAM.com.example")]),
+        );
+        wanted.insert(
+            "com.apple.developer.associated-domains".to_string(),
+            PropertyValue::Array(vec![string("applinks:example.com")]),
+        );
+        wanted.insert(
+            "com.apple.security.application-groups".to_string(),
+            PropertyValue::Array(vec![
+                string("group.TEAM.shared"),
+                string("group.TEAM.cache"),
+            ]),
+        );
+        assert!(profile.unpermitted_entitlements(&wanted).is_empty());
+
+        // Each value of an array must match the wildcard
+        wanted.insert(
+            "com.apple.security.application-groups".to_string(),
+            PropertyValue::Array(vec![
+                string("group.TEAM.shared"),
+                string("group.OTHER.shared"),
+            ]),
+        );
+
+        wanted.insert("get-task-allow".to_string(), PropertyValue::Boolean(true));
+        wanted.insert(
+            "com.apple.developer.icloud-services".to_string(),
+            string("*"),
+        );
+        assert_eq!(
+            profile.unpermitted_entitlements(&wanted),
+            vec![
+                "com.apple.developer.icloud-services",
+                "com.apple.security.application-groups",
+                "get-task-allow"
+            ]
+        );
+    }
+}
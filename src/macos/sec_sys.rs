@@ -1,9 +1,12 @@
 pub use core_foundation::array::{CFArray, CFArrayRef};
-pub use core_foundation::base::{CFType, CFTypeID, OSStatus, TCFType};
+pub use core_foundation::base::{Boolean, CFType, CFTypeID, CFTypeRef, OSStatus, TCFType};
+pub use core_foundation::boolean::CFBoolean;
 pub use core_foundation::data::{CFData, CFDataRef};
+pub use core_foundation::date::CFDate;
 pub use core_foundation::dictionary::{CFDictionary, CFDictionaryRef};
 pub use core_foundation::error::{CFError, CFErrorRef};
 pub use core_foundation::number::CFNumber;
+pub use core_foundation::propertylist::{create_with_data, kCFPropertyListImmutable};
 pub use core_foundation::string::{CFString, CFStringRef};
 pub use core_foundation::url::{CFURLRef, CFURL};
 pub use core_foundation::{declare_TCFType, impl_CFTypeDescription, impl_TCFType};
@@ -11,21 +14,33 @@ pub use core_foundation::{declare_TCFType, impl_CFTypeDescription, impl_TCFType}
 pub const errSecSuccess: OSStatus = 0;
 pub const errSecCSUnsigned: OSStatus = -67062;
 
+pub const kSecCodeSignatureAdhoc: u32 = 0x0002;
+
+pub type CMSSignerStatus = u32;
+pub const kCMSSignerUnsigned: CMSSignerStatus = 0;
+pub const kCMSSignerValid: CMSSignerStatus = 1;
+pub const kCMSSignerNeedsDetachedContent: CMSSignerStatus = 2;
+pub const kCMSSignerInvalidSignature: CMSSignerStatus = 3;
+pub const kCMSSignerInvalidCert: CMSSignerStatus = 4;
+
 pub struct __SecCode {}
 pub struct __SecStaticCode {}
 pub struct __SecCertificate {}
 pub struct __SecRequirement {}
+pub struct __CMSDecoder {}
 
 pub type SecCertificateRef = *const __SecCertificate;
 pub type SecCodeRef = *const __SecCode;
 pub type SecStaticCodeRef = *const __SecStaticCode;
 pub type SecRequirementRef = *const __SecRequirement;
+pub type CMSDecoderRef = *const __CMSDecoder;
 
 extern "C" {
     pub fn SecCertificateGetTypeID() -> CFTypeID;
     pub fn SecCodeGetTypeID() -> CFTypeID;
     pub fn SecStaticCodeGetTypeID() -> CFTypeID;
     pub fn SecRequirementGetTypeID() -> CFTypeID;
+    pub fn CMSDecoderGetTypeID() -> CFTypeID;
 }
 
 declare_TCFType!(SecCertificate, SecCertificateRef);
@@ -44,11 +59,17 @@ declare_TCFType!(SecRequirement, SecRequirementRef);
 impl_TCFType!(SecRequirement, SecRequirementRef, SecRequirementGetTypeID);
 impl_CFTypeDescription!(SecRequirement);
 
+declare_TCFType!(CMSDecoder, CMSDecoderRef);
+impl_TCFType!(CMSDecoder, CMSDecoderRef, CMSDecoderGetTypeID);
+impl_CFTypeDescription!(CMSDecoder);
+
 #[repr(u32)]
 #[allow(dead_code, non_camel_case_types)]
 pub enum SecCSFlags {
     kSecCSDefaultFlags = 0,
     kSecCSSigningInformation = 1 << 1,
+    kSecCSRequirementInformation = 1 << 2,
+    kSecCSSigningAndRequirementInformation = 1 << 1 | 1 << 2, // The flags can't be or'ed in this enum
     kSecCSConsiderExpiration = 1 << 31,
     kSecCSEnforceRevocationChecks = 1 << 30,
     kSecCSCheckTrustedAnchors = 1 << 27,
@@ -103,6 +124,47 @@ extern "C" {
         information: Option<&mut CFDictionaryRef>,
     ) -> OSStatus;
 
+    pub fn SecCodeCopyPath(
+        code: SecStaticCodeRef,
+        flags: SecCSFlags,
+        path: Option<&mut CFURLRef>,
+    ) -> OSStatus;
+
+    pub fn CMSDecoderCreate(decoder: Option<&mut CMSDecoderRef>) -> OSStatus;
+
+    pub fn CMSDecoderUpdateMessage(
+        decoder: CMSDecoderRef,
+        msg_bytes: *const std::ffi::c_void,
+        msg_bytes_len: usize,
+    ) -> OSStatus;
+
+    pub fn CMSDecoderFinalizeMessage(decoder: CMSDecoderRef) -> OSStatus;
+
+    pub fn CMSDecoderGetNumSigners(decoder: CMSDecoderRef, num_signers: &mut usize) -> OSStatus;
+
+    pub fn CMSDecoderCopySignerStatus(
+        decoder: CMSDecoderRef,
+        signer_index: usize,
+        policy_or_array: CFTypeRef,
+        evaluate_sec_trust: Boolean,
+        signer_status: &mut CMSSignerStatus,
+        sec_trust: Option<&mut CFTypeRef>,
+        cert_verify_result_code: &mut OSStatus,
+    ) -> OSStatus;
+
+    pub fn CMSDecoderCopyContent(
+        decoder: CMSDecoderRef,
+        content: Option<&mut CFDataRef>,
+    ) -> OSStatus;
+
+    pub fn SecPolicyCreateBasicX509() -> CFTypeRef;
+
+    pub fn SecTrustGetCertificateCount(trust: CFTypeRef) -> isize;
+
+    pub fn SecTrustGetCertificateAtIndex(trust: CFTypeRef, ix: isize) -> SecCertificateRef;
+
+    pub fn SecCertificateCopySubjectSummary(certificate: SecCertificateRef) -> CFStringRef;
+
     pub fn CFErrorCopyUserInfo(err: CFErrorRef) -> CFDictionaryRef;
 
     pub fn SecCertificateCopyData(certificate: SecCertificateRef) -> CFDataRef;
@@ -115,6 +177,10 @@ extern "C" {
 
     pub static kSecGuestAttributePid: CFStringRef;
     pub static kSecCodeInfoCertificates: CFStringRef;
+    pub static kSecCodeInfoMainExecutable: CFStringRef;
+    pub static kSecCodeInfoEntitlementsDict: CFStringRef;
+    pub static kSecCodeInfoFlags: CFStringRef;
+    pub static kSecCodeInfoUnique: CFStringRef;
 
     pub static kSecPropertyKeyValue: CFStringRef;
     pub static kSecPropertyKeyLabel: CFStringRef;
@@ -0,0 +1,49 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+///
+/// A file written to the temporary directory for a test, removed when dropped.
+/// The name is unique to the test process, so concurrent runs don't share files.
+///
+pub(crate) struct TempFile(PathBuf);
+
+impl TempFile {
+    /// Write `contents` to a new temporary file, `name` ends its file name (e.g. "tampered.rpm")
+    pub(crate) fn new<C: AsRef<[u8]>>(name: &str, contents: C) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "codesign-verify-{}-{}-{name}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&path, contents).unwrap();
+        TempFile(path)
+    }
+}
+
+impl AsRef<Path> for TempFile {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl std::ops::Deref for TempFile {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// The path of `name` in the Linux test data directory
+#[cfg(target_os = "linux")]
+pub(crate) fn testdata(name: &str) -> PathBuf {
+    Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/linux/testdata")).join(name)
+}
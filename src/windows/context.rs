@@ -1,11 +1,11 @@
 use super::wintrust_sys::{
-    CertGetNameStringW, WTHelperGetProvCertFromChain, WTHelperGetProvSignerFromChain,
-    WTHelperProvDataFromStateData, WinVerifyTrust, CERT_NAME_ATTR_TYPE, CERT_NAME_ISSUER_FLAG,
-    DWORD, HANDLE, INVALID_HANDLE_VALUE, PCCERT_CONTEXT, TRUST_E_NO_SIGNER_CERT,
-    WINTRUST_ACTION_GENERIC_VERIFY_V2, WINTRUST_DATA, WTD_REVOKE_NONE, WTD_STATEACTION_CLOSE,
-    WTD_UICONTEXT_EXECUTE, WTD_UI_NONE,
+    CertFindExtension, CertGetNameStringW, WTHelperGetProvCertFromChain,
+    WTHelperGetProvSignerFromChain, WTHelperProvDataFromStateData, WinVerifyTrust,
+    CERT_NAME_ATTR_TYPE, CERT_NAME_ISSUER_FLAG, DWORD, HANDLE, INVALID_HANDLE_VALUE,
+    PCCERT_CONTEXT, TRUST_E_NO_SIGNER_CERT, WINTRUST_ACTION_GENERIC_VERIFY_V2, WINTRUST_DATA,
+    WTD_REVOKE_NONE, WTD_STATEACTION_CLOSE, WTD_UICONTEXT_EXECUTE, WTD_UI_NONE,
 };
-use crate::Name;
+use crate::{hex, Name};
 use windows_sys::Win32::Foundation::WIN32_ERROR;
 
 #[allow(non_camel_case_types)]
@@ -140,6 +140,27 @@ impl Context {
             .fold(String::new(), |v, s| format!("{s:02x}{v}"))
     }
 
+    pub fn has_extension(&self, oid: &str) -> bool {
+        let key = std::ffi::CString::new(oid).unwrap();
+
+        unsafe {
+            let cert_info = self
+                .leaf_cert_ptr
+                .as_ref()
+                .unwrap()
+                .pCertInfo
+                .as_ref()
+                .unwrap();
+
+            !CertFindExtension(
+                key.as_ptr().cast(),
+                cert_info.cExtension,
+                cert_info.rgExtension,
+            )
+            .is_null()
+        }
+    }
+
     pub fn subject_name(&self) -> Name {
         Name {
             common_name: self.get_oid_name(false, "2.5.4.3"),
@@ -166,11 +187,7 @@ impl Context {
         };
 
         use sha1::Digest;
-        let hash = sha1::Sha1::digest(cert_data);
-
-        hash.as_slice()
-            .iter()
-            .fold(String::new(), |s, byte| s + &format!("{byte:02x}"))
+        hex(&sha1::Sha1::digest(cert_data))
     }
 
     #[allow(clippy::items_after_statements)]
@@ -181,10 +198,6 @@ impl Context {
         };
 
         use sha2::Digest;
-        let hash = sha2::Sha256::digest(cert_data);
-
-        hash.as_slice()
-            .iter()
-            .fold(String::new(), |s, byte| s + &format!("{byte:02x}"))
+        hex(&sha2::Sha256::digest(cert_data))
     }
 }
@@ -2,22 +2,27 @@
 mod context;
 mod wintrust_sys;
 
-use super::Error;
+use super::{hex, Error};
+use std::os::windows::io::{AsRawHandle, FromRawHandle};
 use windows_sys::Win32::Foundation::WIN32_ERROR;
 use wintrust_sys::{
     CloseHandle, CreateFileW, CryptCATAdminAcquireContext2, CryptCATAdminCalcHashFromFileHandle2,
     CryptCATAdminEnumCatalogFromHash, CryptCATAdminReleaseCatalogContext,
-    CryptCATAdminReleaseContext, CryptCATCatalogInfoFromContext, GetLastError, OpenProcess,
-    QueryFullProcessImageNameW, WinVerifyTrust, BCRYPT_SHA256_ALGORITHM, BYTE, CATALOG_INFO, DWORD,
-    ERROR_INVALID_PARAMETER, FALSE, FILE_SHARE_READ, GENERIC_READ, HANDLE, INVALID_HANDLE_VALUE,
-    OPEN_EXISTING, PROCESS_QUERY_LIMITED_INFORMATION, TRUST_E_NOSIGNATURE,
+    CryptCATAdminReleaseContext, CryptCATCatalogInfoFromContext, GetFinalPathNameByHandleW,
+    GetLastError, OpenProcess, QueryFullProcessImageNameW, ReOpenFile, WinVerifyTrust,
+    BCRYPT_SHA256_ALGORITHM, BYTE, CATALOG_INFO, DWORD, ERROR_INVALID_PARAMETER, FALSE,
+    FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE, GENERIC_READ, HANDLE,
+    INVALID_HANDLE_VALUE, OPEN_EXISTING, PROCESS_QUERY_LIMITED_INFORMATION, TRUST_E_NOSIGNATURE,
     WINTRUST_ACTION_GENERIC_VERIFY_V2, WINTRUST_CATALOG_INFO, WINTRUST_DATA, WINTRUST_FILE_INFO,
     WTD_CACHE_ONLY_URL_RETRIEVAL, WTD_CHOICE_CATALOG, WTD_CHOICE_FILE, WTD_DISABLE_MD2_MD4,
     WTD_NO_IE4_CHAIN_FLAG, WTD_REVOCATION_CHECK_END_CERT, WTD_REVOKE_NONE, WTD_STATEACTION_VERIFY,
     WTD_UICONTEXT_EXECUTE, WTD_UI_NONE, WTD_USE_DEFAULT_OSVER_CHECK,
 };
 
-pub(crate) struct Verifier(Vec<u16>);
+pub(crate) struct Verifier {
+    path: Vec<u16>,
+    file: Option<std::fs::File>, // Verified instead of opening the path
+}
 pub(crate) use context::Context;
 
 #[allow(clippy::struct_field_names)]
@@ -60,7 +65,42 @@ impl Verifier {
         let mut path_vec: Vec<u16> = path.as_ref().as_os_str().encode_wide().collect();
         path_vec.push(0); // Make sure path is null terminated
 
-        Self(path_vec)
+        Self {
+            path: path_vec,
+            file: None,
+        }
+    }
+
+    // Reopen the file of the handle, WinVerifyTrust reads the file through it. A duplicate of the
+    // handle would share its file offset, which reading moves.
+    #[allow(clippy::cast_possible_wrap)]
+    pub fn for_handle(handle: std::os::windows::io::BorrowedHandle<'_>) -> Result<Self, Error> {
+        let handle = unsafe {
+            ReOpenFile(
+                handle.as_raw_handle() as HANDLE,
+                GENERIC_READ,
+                FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                0,
+            )
+        };
+
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(Error::OsError(unsafe { GetLastError() } as i32));
+        }
+
+        let file = unsafe { std::fs::File::from_raw_handle(handle as _) };
+        let path = unsafe { get_handle_path(file.as_raw_handle() as HANDLE)? };
+
+        Ok(Self {
+            path,
+            file: Some(file),
+        })
+    }
+
+    fn handle(&self) -> HANDLE {
+        self.file
+            .as_ref()
+            .map_or(0, |file| file.as_raw_handle() as HANDLE)
     }
 
     #[allow(clippy::cast_sign_loss)]
@@ -79,7 +119,8 @@ impl Verifier {
         unsafe {
             let mut file_info: WINTRUST_FILE_INFO = std::mem::zeroed();
             file_info.cbStruct = std::mem::size_of::<WINTRUST_FILE_INFO>() as u32;
-            file_info.pcwszFilePath = self.0.as_ptr();
+            file_info.pcwszFilePath = self.path.as_ptr();
+            file_info.hFile = self.handle();
 
             match Self::verify_internal(Some(&mut file_info), None) {
                 Ok(context) => Ok(context),
@@ -96,8 +137,12 @@ impl Verifier {
 
     #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
     unsafe fn verify_catalog_signed(&self) -> Result<Context, Error> {
+        if self.file.is_some() {
+            return self.verify_catalog_signed_handle(self.handle(), CleanupContext::new(0));
+        }
+
         let h_file = CreateFileW(
-            self.0.as_ptr(),
+            self.path.as_ptr(),
             GENERIC_READ,
             FILE_SHARE_READ,
             std::ptr::null_mut(),
@@ -111,8 +156,15 @@ impl Verifier {
             return Err(Error::OsError(err));
         }
 
-        let mut ctx = CleanupContext::new(h_file);
+        self.verify_catalog_signed_handle(h_file, CleanupContext::new(h_file))
+    }
 
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    unsafe fn verify_catalog_signed_handle(
+        &self,
+        h_file: HANDLE,
+        mut ctx: CleanupContext,
+    ) -> Result<Context, Error> {
         let mut h_cat_admin: HANDLE = 0;
         let result = CryptCATAdminAcquireContext2(
             &mut h_cat_admin,
@@ -164,17 +216,15 @@ impl Verifier {
             return Err(Error::OsError(err));
         }
 
-        let hash_str = hash_buffer.iter().fold(String::new(), |mut acc, &val| {
-            acc.push_str(&format!("{val:02x}"));
-            acc
-        });
+        let hash_str = hex(&hash_buffer);
         let mut hash: Vec<u16> = hash_str.encode_utf16().collect();
         hash.push(0); // Make sure hash is null terminated
 
         let mut wci: WINTRUST_CATALOG_INFO = std::mem::zeroed();
         wci.cbStruct = std::mem::size_of::<WINTRUST_CATALOG_INFO>() as u32;
         wci.pcwszCatalogFilePath = ci.wszCatalogFile.as_ptr();
-        wci.pcwszMemberFilePath = self.0.as_ptr();
+        wci.pcwszMemberFilePath = self.path.as_ptr();
+        wci.hMemberFile = h_file;
         wci.pcwszMemberTag = hash.as_ptr();
 
         match Self::verify_internal(None, Some(&mut wci)) {
@@ -246,6 +296,23 @@ fn get_process_path(proc_id: u32) -> Result<String, Error> {
     }
 }
 
+// The null terminated path of an open file
+#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+unsafe fn get_handle_path(handle: HANDLE) -> Result<Vec<u16>, Error> {
+    let mut buf = vec![0u16; 2048];
+
+    loop {
+        match GetFinalPathNameByHandleW(handle, buf.as_mut_ptr(), buf.len() as _, 0) as usize {
+            0 => return Err(Error::OsError(GetLastError() as i32)),
+            len if len < buf.len() => {
+                buf.truncate(len + 1); // Keep the null terminator
+                return Ok(buf);
+            }
+            len => buf.resize(len, 0), // The required size, including the null terminator
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     // This imports all the items from the parent module.
@@ -263,6 +330,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_file_handle() {
+        use std::io::{Read, Seek};
+        use std::os::windows::io::AsHandle;
+
+        let mut file = std::fs::File::open("c:\\windows\\system32\\svchost.exe").unwrap();
+        file.read_exact(&mut [0; 4]).unwrap();
+
+        let context = Verifier::for_handle(file.as_handle())
+            .unwrap()
+            .verify()
+            .unwrap();
+        assert_eq!(
+            context.issuer_name().organization.unwrap(),
+            "Microsoft Corporation"
+        );
+        assert_eq!(file.stream_position().unwrap(), 4);
+    }
+
     #[test]
     fn test_embeded_signed_file() {
         verify_file(
@@ -7,7 +7,10 @@ pub use windows_sys::Win32::Foundation::{
 pub use windows_sys::Win32::Security::Cryptography::Catalog::*;
 pub use windows_sys::Win32::Security::Cryptography::*;
 pub use windows_sys::Win32::Security::WinTrust::*;
-pub use windows_sys::Win32::Storage::FileSystem::{CreateFileW, FILE_SHARE_READ, OPEN_EXISTING};
+pub use windows_sys::Win32::Storage::FileSystem::{
+    CreateFileW, GetFinalPathNameByHandleW, ReOpenFile, FILE_SHARE_DELETE, FILE_SHARE_READ,
+    FILE_SHARE_WRITE, OPEN_EXISTING,
+};
 pub use windows_sys::Win32::System::Threading::{
     OpenProcess, QueryFullProcessImageNameW, PROCESS_QUERY_LIMITED_INFORMATION,
 };